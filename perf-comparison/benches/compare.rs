@@ -125,6 +125,34 @@ fn compare_large(c: &mut Criterion) {
     });
     group.finish();
 }
+/// `parse_str` skips decoding, which `parse_reader` has to do even for UTF-8 input.
+fn compare_str_and_reader(c: &mut Criterion) {
+    let mut group = c.benchmark_group("str_and_reader_compare");
+    for file in [TINY_XML, MEDIUM_XML] {
+        let xml = std::fs::read_to_string(get_bench_file_path(file)).unwrap();
+
+        group.bench_function(BenchmarkId::new("parse_str", file), |b| {
+            b.iter(|| {
+                let doc = edit_xml::Document::parse_str(&xml).unwrap();
+                black_box(doc);
+            })
+        });
+        group.bench_function(BenchmarkId::new("parse_reader", file), |b| {
+            b.iter(|| {
+                let doc = edit_xml::Document::parse_reader(xml.as_bytes()).unwrap();
+                black_box(doc);
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group! {
+    name = str_and_reader;
+    config = Criterion::default().sample_size(100);
+    targets = compare_str_and_reader
+}
+
 criterion_group! {
     name = tiny;
     config = Criterion::default().sample_size(200);
@@ -143,4 +171,4 @@ criterion_group! {
     targets = compare_large
 }
 
-criterion_main!(tiny, medium, large);
+criterion_main!(tiny, medium, large, str_and_reader);
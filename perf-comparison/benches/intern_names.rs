@@ -0,0 +1,73 @@
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::hint::black_box;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use edit_xml::{Document, ReadOptions};
+use xml_lib_perf_comparison::{get_bench_file_path, LARGE_XML};
+
+/// Counts the bytes currently allocated, to compare the memory used by documents.
+struct CountingAllocator;
+
+static ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATED.fetch_add(layout.size(), Ordering::Relaxed);
+        unsafe { System.alloc(layout) }
+    }
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        ALLOCATED.fetch_sub(layout.size(), Ordering::Relaxed);
+        unsafe { System.dealloc(ptr, layout) }
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+fn parse(xml: &str, intern_names: bool) -> Document {
+    let opts = ReadOptions {
+        intern_names,
+        ..Default::default()
+    };
+    Document::parse_str_with_opts(xml, opts).unwrap()
+}
+
+fn intern_names(c: &mut Criterion) {
+    let xml = std::fs::read_to_string(get_bench_file_path(LARGE_XML)).unwrap();
+
+    for intern_names in [false, true] {
+        let before = ALLOCATED.load(Ordering::Relaxed);
+        let doc = parse(&xml, intern_names);
+        let used = ALLOCATED.load(Ordering::Relaxed) - before;
+        // Both element names and attribute names are interned
+        let attributes: usize = doc
+            .root_element()
+            .unwrap()
+            .child_elements_recursive(&doc)
+            .iter()
+            .map(|elem| elem.attribute_count(&doc))
+            .sum();
+        println!(
+            "intern_names: {intern_names}, {} elements, {attributes} attributes, {used} bytes retained",
+            doc.number_of_elements(),
+        );
+        drop(doc);
+    }
+
+    let mut group = c.benchmark_group("intern_names");
+    for intern_names in [false, true] {
+        group.bench_function(BenchmarkId::new(LARGE_XML, intern_names), |b| {
+            b.iter(|| black_box(parse(&xml, intern_names)))
+        });
+    }
+    group.finish();
+}
+
+criterion_group! {
+    name = large;
+    config = Criterion::default().sample_size(10);
+    targets = intern_names
+}
+
+criterion_main!(large);
@@ -0,0 +1,319 @@
+use crate::{Document, Element, Name, Node};
+
+/// A difference between two documents. Returned by [`Document::diff`].
+///
+/// `path` is the `/` separated full names of the element and its ancestors, starting at the root element.
+/// A name is followed by `[n]` if it is the n-th child element with that name, counting from 1,
+/// and n is greater than 1. e.g. `/root/list/item[2]`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DiffOp {
+    /// The element at `path` only exists in the other document.
+    /// Its descendants are not reported separately.
+    ElementAdded { path: String },
+    /// The element at `path` only exists in this document.
+    /// Its descendants are not reported separately.
+    ElementRemoved { path: String },
+    /// The attribute only exists in the other document.
+    AttributeAdded {
+        path: String,
+        name: String,
+        value: String,
+    },
+    /// The attribute only exists in this document.
+    AttributeRemoved {
+        path: String,
+        name: String,
+        value: String,
+    },
+    /// The attribute has a different value in the other document.
+    AttributeChanged {
+        path: String,
+        name: String,
+        old: String,
+        new: String,
+    },
+    /// The text and CDATA children of the element, concatenated, are different in the other document.
+    /// Text of descendant elements is compared at their own path.
+    TextChanged {
+        path: String,
+        old: String,
+        new: String,
+    },
+}
+
+impl Document {
+    /// Compares the elements of this document with `other`, matching elements by their path.
+    ///
+    /// This is a coarse diff: an element that is inserted before its siblings with the same name
+    /// shifts their paths, so they are reported as changed.
+    /// Attributes are reported in order of their names.
+    /// Namespace declarations, comments, processing instructions and the XML declaration are not compared.
+    ///
+    /// ```
+    /// use edit_xml::{DiffOp, Document};
+    ///
+    /// let xml = r#"<?xml version="1.0"?><list><item id="1">A</item></list>"#;
+    /// let doc = Document::parse_str(xml).unwrap();
+    /// let mut other = Document::parse_str(xml).unwrap();
+    /// let item = other.root_element().unwrap().find(&other, "item").unwrap();
+    /// item.set_attribute(&mut other, "id", "2");
+    /// assert_eq!(
+    ///     doc.diff(&other),
+    ///     vec![DiffOp::AttributeChanged {
+    ///         path: "/list/item".to_string(),
+    ///         name: "id".to_string(),
+    ///         old: "1".to_string(),
+    ///         new: "2".to_string(),
+    ///     }]
+    /// );
+    /// ```
+    pub fn diff(&self, other: &Document) -> Vec<DiffOp> {
+        let mut ops = Vec::new();
+        diff_children(
+            self,
+            self.container(),
+            other,
+            other.container(),
+            "",
+            &mut ops,
+        );
+        ops
+    }
+}
+
+/// Child elements of `elem` with their paths.
+fn child_paths(doc: &Document, elem: Element, path: &str) -> Vec<(String, Element)> {
+    let mut seen: Vec<(&str, usize)> = Vec::new();
+    elem.child_elements(doc)
+        .into_iter()
+        .map(|child| {
+            let name = child.full_name(doc);
+            let index = match seen.iter_mut().find(|(seen, _)| *seen == name) {
+                Some((_, count)) => {
+                    *count += 1;
+                    *count
+                }
+                None => {
+                    seen.push((name, 1));
+                    1
+                }
+            };
+            let child_path = if index == 1 {
+                format!("{path}/{name}")
+            } else {
+                format!("{path}/{name}[{index}]")
+            };
+            (child_path, child)
+        })
+        .collect()
+}
+
+fn diff_children(
+    doc: &Document,
+    elem: Element,
+    other: &Document,
+    other_elem: Element,
+    path: &str,
+    ops: &mut Vec<DiffOp>,
+) {
+    let children = child_paths(doc, elem, path);
+    let other_children = child_paths(other, other_elem, path);
+    for (child_path, child) in &children {
+        match other_children
+            .iter()
+            .find(|(other_path, _)| other_path == child_path)
+        {
+            Some((_, other_child)) => {
+                diff_element(doc, *child, other, *other_child, child_path, ops);
+            }
+            None => ops.push(DiffOp::ElementRemoved {
+                path: child_path.clone(),
+            }),
+        }
+    }
+    for (other_path, _) in other_children {
+        if !children
+            .iter()
+            .any(|(child_path, _)| *child_path == other_path)
+        {
+            ops.push(DiffOp::ElementAdded { path: other_path });
+        }
+    }
+}
+
+fn diff_element(
+    doc: &Document,
+    elem: Element,
+    other: &Document,
+    other_elem: Element,
+    path: &str,
+    ops: &mut Vec<DiffOp>,
+) {
+    let attributes = elem.attributes(doc);
+    let other_attributes = other_elem.attributes(other);
+    let mut names: Vec<&Name> = attributes.keys().chain(other_attributes.keys()).collect();
+    names.sort();
+    names.dedup();
+    for name in names {
+        let op = match (attributes.get(name), other_attributes.get(name)) {
+            (Some(old), Some(new)) if old != new => DiffOp::AttributeChanged {
+                path: path.to_owned(),
+                name: name.to_string(),
+                old: old.clone(),
+                new: new.clone(),
+            },
+            (Some(value), None) => DiffOp::AttributeRemoved {
+                path: path.to_owned(),
+                name: name.to_string(),
+                value: value.clone(),
+            },
+            (None, Some(value)) => DiffOp::AttributeAdded {
+                path: path.to_owned(),
+                name: name.to_string(),
+                value: value.clone(),
+            },
+            _ => continue,
+        };
+        ops.push(op);
+    }
+
+    let old = own_text(doc, elem);
+    let new = own_text(other, other_elem);
+    if old != new {
+        ops.push(DiffOp::TextChanged {
+            path: path.to_owned(),
+            old,
+            new,
+        });
+    }
+    diff_children(doc, elem, other, other_elem, path, ops);
+}
+
+/// Text and CDATA children of `elem`, concatenated.
+fn own_text(doc: &Document, elem: Element) -> String {
+    let mut text = String::new();
+    for node in elem.children(doc) {
+        if let Node::Text(content) | Node::CData(content) = node {
+            text.push_str(content);
+        }
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::DiffOp;
+    use crate::{Document, Element};
+
+    #[test]
+    fn test_diff_unchanged() {
+        let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+            <root a="1" b="2"><item>A</item><item>B<![CDATA[C]]></item></root>
+            "#;
+        let doc = Document::parse_str(xml).unwrap();
+        assert_eq!(doc.diff(&doc), vec![]);
+        assert_eq!(doc.diff(&Document::parse_str(xml).unwrap()), vec![]);
+    }
+
+    #[test]
+    fn test_diff_modified_copy() {
+        let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+            <root version="1" old="x">
+                <list>
+                    <item id="1">A</item>
+                    <item id="2">B<sub /></item>
+                    <item id="3">C</item>
+                </list>
+                <removed><child /></removed>
+            </root>
+            "#;
+        let doc = Document::parse_str(xml).unwrap();
+        let mut other = Document::parse_str(xml).unwrap();
+        let root = other.root_element().unwrap();
+        root.set_attribute(&mut other, "version", "2");
+        root.mut_attributes(&mut other).remove("old");
+        root.set_attribute(&mut other, "new", "y");
+        let list = root.find(&other, "list").unwrap();
+        let items = list.find_all(&other, "item");
+        items[1].set_text_content(&mut other, "Changed");
+        items[2].detach(&mut other).unwrap();
+        let removed = root.find(&other, "removed").unwrap();
+        removed.detach(&mut other).unwrap();
+        let added = Element::build("added")
+            .create_element("child", |child| child)
+            .finish(&mut other);
+        root.push_child(&mut other, added).unwrap();
+
+        assert_eq!(
+            doc.diff(&other),
+            vec![
+                DiffOp::AttributeAdded {
+                    path: "/root".to_owned(),
+                    name: "new".to_owned(),
+                    value: "y".to_owned(),
+                },
+                DiffOp::AttributeRemoved {
+                    path: "/root".to_owned(),
+                    name: "old".to_owned(),
+                    value: "x".to_owned(),
+                },
+                DiffOp::AttributeChanged {
+                    path: "/root".to_owned(),
+                    name: "version".to_owned(),
+                    old: "1".to_owned(),
+                    new: "2".to_owned(),
+                },
+                DiffOp::TextChanged {
+                    path: "/root/list/item[2]".to_owned(),
+                    old: "B".to_owned(),
+                    new: "Changed".to_owned(),
+                },
+                DiffOp::ElementRemoved {
+                    path: "/root/list/item[2]/sub".to_owned(),
+                },
+                DiffOp::ElementRemoved {
+                    path: "/root/list/item[3]".to_owned(),
+                },
+                DiffOp::ElementRemoved {
+                    path: "/root/removed".to_owned(),
+                },
+                DiffOp::ElementAdded {
+                    path: "/root/added".to_owned(),
+                },
+            ]
+        );
+
+        // The diff the other way around
+        let reversed = other.diff(&doc);
+        assert_eq!(reversed.len(), 8);
+        assert!(reversed.contains(&DiffOp::ElementAdded {
+            path: "/root/removed".to_owned(),
+        }));
+        assert!(reversed.contains(&DiffOp::ElementRemoved {
+            path: "/root/added".to_owned(),
+        }));
+    }
+
+    #[test]
+    fn test_diff_root() {
+        let doc = Document::parse_str(r#"<?xml version="1.0"?><a />"#).unwrap();
+        let other = Document::parse_str(r#"<?xml version="1.0"?><b />"#).unwrap();
+        assert_eq!(
+            doc.diff(&other),
+            vec![
+                DiffOp::ElementRemoved {
+                    path: "/a".to_owned(),
+                },
+                DiffOp::ElementAdded {
+                    path: "/b".to_owned(),
+                },
+            ]
+        );
+        assert_eq!(
+            doc.diff(&Document::new()),
+            vec![DiffOp::ElementRemoved {
+                path: "/a".to_owned(),
+            }]
+        );
+    }
+}
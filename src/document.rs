@@ -1,12 +1,14 @@
 use crate::ElementBuilder;
 use crate::element::{Element, ElementData};
 use crate::error::{EditXMLError, Result};
-use crate::parser::{DocumentParser, ReadOptions};
+use crate::parser::{DocumentParser, ParseResult, ReadOptions, XML_WHITESPACE};
 use crate::types::StandaloneValue;
+use crate::utils::HashMap;
 use quick_xml::Writer;
 use quick_xml::events::{BytesCData, BytesDecl, BytesEnd, BytesPI, BytesStart, BytesText, Event};
+use std::borrow::Cow;
 use std::fs::File;
-use std::io::{Read, Write};
+use std::io::{BufWriter, Read, Write};
 use std::path::Path;
 use std::str::FromStr;
 #[cfg(feature = "document-breakdown")]
@@ -15,6 +17,12 @@ mod breakdown;
 pub use breakdown::*;
 mod node;
 pub use node::*;
+mod source;
+mod statistics;
+mod validate;
+pub use source::*;
+pub use statistics::*;
+pub use validate::*;
 
 /// Represents a XML document or a document fragment.
 ///
@@ -55,6 +63,7 @@ pub struct Document {
 
     pub(crate) version: String,
     pub(crate) standalone: Option<StandaloneValue>,
+    pub(crate) declared_encoding: Option<String>,
 }
 impl Default for Document {
     fn default() -> Self {
@@ -65,6 +74,7 @@ impl Default for Document {
             container,
             version: String::from("1.0"),
             standalone: None,
+            declared_encoding: None,
         }
     }
 }
@@ -83,6 +93,7 @@ impl Document {
             container,
             version: String::from("1.0"),
             standalone: None,
+            declared_encoding: None,
         }
     }
     /// Get the number of elements in the document.
@@ -106,6 +117,25 @@ impl Document {
     /// assert_eq!(name.text_content(&doc), "Cool Name");
     /// ```
     pub fn new_with_root<N, F>(root_name: N, f: F) -> Document
+    where
+        N: Into<String>,
+        F: FnOnce(ElementBuilder) -> ElementBuilder,
+    {
+        Self::build_with_root(root_name, f).0
+    }
+
+    /// Same as [`Document::new_with_root`], but also returns the root element.
+    ///
+    /// # Examples
+    /// ```
+    /// use edit_xml::{Document, Element};
+    /// let (mut doc, root) = Document::build_with_root("root", |root| root.attribute("id", "main"));
+    /// let name = Element::build("name").add_text("Cool Name").push_to(&mut doc, root);
+    /// assert_eq!(root.attribute(&doc, "id"), Some("main"));
+    /// assert_eq!(name.parent(&doc), Some(root));
+    /// assert_eq!(doc.root_element(), Some(root));
+    /// ```
+    pub fn build_with_root<N, F>(root_name: N, f: F) -> (Document, Element)
     where
         N: Into<String>,
         F: FnOnce(ElementBuilder) -> ElementBuilder,
@@ -113,7 +143,7 @@ impl Document {
         let mut doc = Document::new();
         let root = f(ElementBuilder::new(root_name)).finish(&mut doc);
         doc.push_root_node(root).unwrap();
-        doc
+        (doc, root)
     }
 
     /// Get 'container' element of Document.
@@ -143,11 +173,115 @@ impl Document {
         self.container.children(self)
     }
 
+    /// Get the encoding label of the XML declaration the document was parsed from, as written in the source.
+    ///
+    /// The content is already decoded, and the document is always written as UTF-8.
+    ///
+    /// ```
+    /// use edit_xml::Document;
+    /// let doc = Document::parse_str(r#"<?xml version="1.0" encoding="ISO-8859-1"?><root />"#).unwrap();
+    /// assert_eq!(doc.declared_encoding(), Some("ISO-8859-1"));
+    /// let doc = Document::parse_str(r#"<?xml version="1.0"?><root />"#).unwrap();
+    /// assert_eq!(doc.declared_encoding(), None);
+    /// ```
+    pub fn declared_encoding(&self) -> Option<&str> {
+        self.declared_encoding.as_deref()
+    }
+
+    /// Get the content of the first [`Node::DocType`] among the root nodes.
+    ///
+    /// ```
+    /// use edit_xml::Document;
+    /// let doc = Document::parse_str(r#"<?xml version="1.0"?>
+    /// <!DOCTYPE html>
+    /// <html />
+    /// "#).unwrap();
+    /// assert_eq!(doc.doctype(), Some("html"));
+    /// ```
+    pub fn doctype(&self) -> Option<&str> {
+        self.root_nodes().iter().find_map(|node| match node {
+            Node::DocType(doctype) => Some(doctype.as_str()),
+            _ => None,
+        })
+    }
+
+    /// Replace the content of the first root [`Node::DocType`].
+    ///
+    /// If the document doesn't have one, it is inserted as the first root node, right after the XML declaration.
+    ///
+    /// If `content` is `None`, the existing [`Node::DocType`] is removed.
+    pub fn set_doctype(&mut self, content: Option<String>) {
+        let container = self.container;
+        let position = self.root_nodes().iter().position(Node::is_doctype);
+        match (position, content) {
+            (Some(index), Some(content)) => {
+                container.children_mut(self)[index] = Node::DocType(content);
+            }
+            (Some(index), None) => {
+                container.remove_child(self, index);
+            }
+            (None, Some(content)) => {
+                container
+                    .children_mut(self)
+                    .insert(0, Node::DocType(content));
+            }
+            (None, None) => {}
+        }
+    }
+
     /// Get first root node that is an element.
     pub fn root_element(&self) -> Option<Element> {
         self.container.child_elements(self).first().copied()
     }
 
+    /// Like [`Document::root_element`], but returns an error if there is no root element.
+    ///
+    /// # Errors
+    ///    - [EditXMLError::NoRootElement]: The document doesn't have a root element.
+    ///
+    /// # Examples
+    /// ```
+    /// use edit_xml::{Document, Element, EditXMLError};
+    /// fn root_name(doc: &Document) -> Result<&str, EditXMLError> {
+    ///     let root = doc.require_root_element()?;
+    ///     Ok(root.name(doc))
+    /// }
+    /// let doc = Document::new_with_root("root", |root| root);
+    /// assert_eq!(root_name(&doc).unwrap(), "root");
+    /// ```
+    pub fn require_root_element(&self) -> Result<Element> {
+        self.root_element().ok_or(EditXMLError::NoRootElement)
+    }
+
+    /// Calls `f` with the root element and the document, returning its result.
+    ///
+    /// # Errors
+    ///    - [EditXMLError::NoRootElement]: The document doesn't have a root element.
+    ///
+    /// # Examples
+    /// ```
+    /// use edit_xml::Document;
+    /// let mut doc = Document::new_with_root("root", |root| root);
+    /// doc.with_root(|root, doc| {
+    ///     root.set_attribute(doc, "id", "main");
+    ///     root.set_text_content(doc, "Hello");
+    /// })
+    /// .unwrap();
+    /// let root = doc.root_element().unwrap();
+    /// assert_eq!(root.attribute(&doc, "id"), Some("main"));
+    /// assert_eq!(root.text_content(&doc), "Hello");
+    ///
+    /// let mut empty = Document::new();
+    /// assert!(empty.with_root(|_, _| ()).is_err());
+    /// ```
+    pub fn with_root<F, R>(&mut self, f: F) -> Result<R>
+    where
+        F: FnOnce(Element, &mut Document) -> R,
+    {
+        let root = self.require_root_element()?;
+        Ok(root.modify(self, f))
+    }
+
     /// Push a node to end of root nodes.
     /// If doc has no [`Element`], pushing a [`Node::Element`] is
     /// equivalent to setting it as root element.
@@ -156,6 +290,122 @@ impl Document {
         let elem = self.container;
         elem.push_child(self, node)
     }
+    /// Appends all root nodes of `other` to the end of this document's root nodes.
+    ///
+    /// Elements of `other` are moved into this document and get new ids,
+    /// so [`Element`]s obtained from `other` can't be used with this document.
+    ///
+    /// The XML declaration of `other` is discarded.
+    ///
+    /// # Examples
+    /// ```
+    /// use edit_xml::Document;
+    /// let mut doc = Document::parse_str(r#"<?xml version="1.0"?><a />"#).unwrap();
+    /// let other = Document::parse_str(r#"<?xml version="1.0"?><b><c /></b>"#).unwrap();
+    /// doc.append_document(other);
+    /// assert_eq!(doc.root_nodes().len(), 2);
+    /// ```
+    pub fn append_document(&mut self, mut other: Document) {
+        let other_container = other.container;
+        let nodes = std::mem::take(other_container.children_mut(&mut other));
+        self.store.reserve(other.store.len() - 1);
+        for node in nodes {
+            let node = match node {
+                Node::Element(elem) => Node::Element(elem.move_to_document(&mut other, self)),
+                node => node,
+            };
+            self.push_root_node(node).unwrap();
+        }
+    }
+
+    /// Removes whitespace-only text nodes from the whole document.
+    ///
+    /// See [`Element::trim_whitespace_nodes`].
+    pub fn trim_whitespace_nodes(&mut self) {
+        self.container().trim_whitespace_nodes(self);
+    }
+
+    /// Shrinks the capacity of the element storage as much as possible.
+    ///
+    /// Elements are never removed from the storage, even when they are detached or removed from the tree.
+    /// Use [`Document::compact`] to also remove them.
+    pub fn shrink_to_fit(&mut self) {
+        self.store.shrink_to_fit();
+    }
+
+    /// Removes all elements that are not reachable from the [container](Document::container),
+    /// and shrinks the element storage.
+    ///
+    /// This changes the ids of the remaining elements, so every [`Element`] obtained before
+    /// is invalid afterwards. Returns a map from the old elements to the new elements.
+    /// Detached elements, including new elements that weren't pushed to the tree yet, are removed.
+    ///
+    /// # Examples
+    /// ```
+    /// use edit_xml::Document;
+    /// let mut doc = Document::parse_str(r#"<?xml version="1.0"?>
+    /// <root><removed><child /></removed><kept /></root>
+    /// "#).unwrap();
+    /// let root = doc.root_element().unwrap();
+    /// let kept = root.find(&doc, "kept").unwrap();
+    /// root.find(&doc, "removed").unwrap().detach(&mut doc).unwrap();
+    /// assert_eq!(doc.number_of_elements(), 5);
+    ///
+    /// let map = doc.compact();
+    /// assert_eq!(doc.number_of_elements(), 3);
+    /// let kept = map[&kept];
+    /// assert_eq!(kept.name(&doc), "kept");
+    /// assert_eq!(kept.parent(&doc), doc.root_element());
+    /// ```
+    pub fn compact(&mut self) -> HashMap<Element, Element> {
+        let mut new_ids: Vec<Option<Element>> = vec![None; self.store.len()];
+        // Old ids in their new order
+        let mut order = Vec::with_capacity(self.store.len());
+        let mut stack = vec![self.container];
+        while let Some(elem) = stack.pop() {
+            new_ids[elem.id] = Some(Element { id: order.len() });
+            order.push(elem.id);
+            stack.extend(elem.iter_child_elements(self));
+        }
+
+        let mut old_store = std::mem::take(&mut self.store);
+        let mut store = Vec::with_capacity(order.len());
+        let mut map = HashMap::with_capacity(order.len());
+        for old_id in order {
+            let mut data = std::mem::take(&mut old_store[old_id]);
+            data.remap_ids(&new_ids);
+            let new = Element { id: store.len() };
+            store.push(data);
+            map.insert(Element { id: old_id }, new);
+        }
+        self.counter = store.len();
+        self.store = store;
+        map
+    }
+
+    /// Frees the storage of elements that are not reachable from the [container](Document::container),
+    /// such as detached or removed elements. Returns how many elements were freed.
+    ///
+    /// # Warning
+    /// The remaining elements get new ids, so every [`Element`] obtained before is invalid afterwards,
+    /// and may now point to a different element. Use [`Document::compact`] to get a map from the old elements to the new ones.
+    ///
+    /// # Examples
+    /// ```
+    /// use edit_xml::{Document, Element};
+    /// let mut doc = Document::new_with_root("root", |root| root);
+    /// let root = doc.root_element().unwrap();
+    /// let child = Element::build("child").push_to(&mut doc, root);
+    /// child.detach(&mut doc).unwrap();
+    /// assert_eq!(doc.collect_garbage(), 1);
+    /// assert_eq!(doc.number_of_elements(), 2);
+    /// ```
+    pub fn collect_garbage(&mut self) -> usize {
+        let before = self.store.len();
+        self.compact();
+        before - self.store.len()
+    }
+
     #[inline(always)]
     pub(crate) fn push_to_store(&mut self, data: ElementData) -> Element {
         let elem = Element { id: self.counter };
@@ -176,10 +426,42 @@ impl Document {
 ///
 impl Document {
     pub fn parse_str(str: &str) -> Result<Document> {
-        DocumentParser::parse_reader(str.as_bytes(), ReadOptions::default())
+        DocumentParser::parse_str(str, ReadOptions::default())
     }
+    /// Parses a `str`. It is already valid UTF-8, so it isn't decoded
+    /// and the encoding declared in the document or set in [`ReadOptions::encoding`] is ignored.
     pub fn parse_str_with_opts(str: &str, opts: ReadOptions) -> Result<Document> {
-        DocumentParser::parse_reader(str.as_bytes(), opts)
+        DocumentParser::parse_str(str, opts)
+    }
+
+    /// Parses a `str` like [`Document::parse_str`], and also returns the [`ParseWarning`](crate::ParseWarning)s found.
+    ///
+    /// ```
+    /// use edit_xml::{Document, ParseWarning, ReadOptions};
+    ///
+    /// let opts = ReadOptions {
+    ///     ignore_unmatched_end_tags: true,
+    ///     collect_warnings: true,
+    ///     ..ReadOptions::default()
+    /// };
+    /// let xml = r#"<?xml version="1.0"?><root></extra></root>"#;
+    /// let result = Document::parse_str_verbose_with_opts(xml, opts).unwrap();
+    /// assert_eq!(
+    ///     result.warnings,
+    ///     vec![ParseWarning::IgnoredEndTag { name: "extra".to_owned(), position: 27 }]
+    /// );
+    /// ```
+    pub fn parse_str_verbose(str: &str) -> Result<ParseResult> {
+        let opts = ReadOptions {
+            collect_warnings: true,
+            ..ReadOptions::default()
+        };
+        DocumentParser::parse_str_verbose(str, opts)
+    }
+    /// See [`Document::parse_str_verbose`].
+    /// Warnings are only collected if [`ReadOptions::collect_warnings`] is set.
+    pub fn parse_str_verbose_with_opts(str: &str, opts: ReadOptions) -> Result<ParseResult> {
+        DocumentParser::parse_str_verbose(str, opts)
     }
 
     pub fn parse_file<P: AsRef<Path>>(path: P) -> Result<Document> {
@@ -200,21 +482,208 @@ impl Document {
 }
 
 /// Options when writing XML.
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+///
+/// Use [`WriteOptions::builder`] to create options that differ from the defaults.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
 pub struct WriteOptions {
     /// Byte character to indent with. (default: `b' '`)
     pub indent_char: u8,
     /// How many indent_char should be used for indent. (default: 2)
     pub indent_size: usize,
+    /// Write each node on its own line, indented with `indent_char` and `indent_size`.
+    /// If `false`, nothing is written between nodes. (default: `true`)
+    pub pretty: bool,
     /// XML declaration should be written at the top. (default: `true`)
     pub write_decl: bool,
+    /// If a start tag including its indentation would be longer than this,
+    /// each attribute is written on its own line, indented one level beyond the element. (default: `None`)
+    pub max_line_width: Option<usize>,
+    /// Which characters are escaped in text and attribute values. (default: [`EscapeMode::Full`])
+    pub escape_mode: EscapeMode,
+    /// Written verbatim at the very top of the output, before the XML declaration. (default: `None`)
+    ///
+    /// A newline is added after it, unless it already ends with one.
+    /// It is not validated, so it is the caller's responsibility to keep the output well-formed.
+    /// Only comments, processing instructions and whitespace can come before the root element,
+    /// and nothing can come before the XML declaration, so set `write_decl` to `false` when using this
+    /// if the output needs to be parsed again.
+    pub preamble: Option<String>,
+    /// Write empty elements as `<tag />` instead of `<tag/>`. (default: `false`)
+    pub space_before_self_close: bool,
+    /// Write namespace declarations before the other attributes of an element,
+    /// instead of after them. (default: `false`)
+    pub namespace_decls_first: bool,
+    /// Write the text of elements whose children are all text or CDATA on its own line,
+    /// indented one level beyond the element, instead of inline between the tags.
+    /// Only applies when `pretty` is `true`. (default: `false`)
+    pub indent_text_nodes: bool,
 }
 impl Default for WriteOptions {
     fn default() -> Self {
         Self {
             indent_char: b' ',
             indent_size: 2,
+            pretty: true,
             write_decl: true,
+            max_line_width: None,
+            escape_mode: EscapeMode::Full,
+            preamble: None,
+            space_before_self_close: false,
+            namespace_decls_first: false,
+            indent_text_nodes: false,
+        }
+    }
+}
+
+impl WriteOptions {
+    /// Chain methods to build [`WriteOptions`], starting from the default options.
+    ///
+    /// ```
+    /// use edit_xml::{Document, EscapeMode, WriteOptions};
+    ///
+    /// let opts = WriteOptions::builder()
+    ///     .indent_char(b'\t')
+    ///     .indent_size(1)
+    ///     .write_decl(false)
+    ///     .escape_mode(EscapeMode::Minimal)
+    ///     .build();
+    /// let doc = Document::new_with_root("root", |root| root.create_element("child", |child| child));
+    /// assert_eq!(doc.write_str_with_opts(opts).unwrap(), "<root>\n\t<child/>\n</root>");
+    /// ```
+    pub fn builder() -> WriteOptionsBuilder {
+        WriteOptionsBuilder::default()
+    }
+
+    fn writer<W: Write>(&self, inner: W) -> Writer<W> {
+        let mut writer = if self.pretty {
+            Writer::new_with_indent(inner, self.indent_char, self.indent_size)
+        } else {
+            Writer::new(inner)
+        };
+        writer.config_mut().add_space_before_slash_in_empty_elements = self.space_before_self_close;
+        writer
+    }
+}
+/// Builder for [`WriteOptions`]. Created with [`WriteOptions::builder`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct WriteOptionsBuilder {
+    opts: WriteOptions,
+}
+impl From<WriteOptions> for WriteOptionsBuilder {
+    fn from(opts: WriteOptions) -> Self {
+        Self { opts }
+    }
+}
+impl WriteOptionsBuilder {
+    /// See [`WriteOptions::indent_char`]
+    pub fn indent_char(mut self, indent_char: u8) -> Self {
+        self.opts.indent_char = indent_char;
+        self
+    }
+    /// See [`WriteOptions::indent_size`]
+    pub fn indent_size(mut self, indent_size: usize) -> Self {
+        self.opts.indent_size = indent_size;
+        self
+    }
+    /// See [`WriteOptions::pretty`]
+    pub fn pretty(mut self, pretty: bool) -> Self {
+        self.opts.pretty = pretty;
+        self
+    }
+    /// See [`WriteOptions::write_decl`]
+    pub fn write_decl(mut self, write_decl: bool) -> Self {
+        self.opts.write_decl = write_decl;
+        self
+    }
+    /// See [`WriteOptions::max_line_width`]
+    pub fn max_line_width(mut self, max_line_width: usize) -> Self {
+        self.opts.max_line_width = Some(max_line_width);
+        self
+    }
+    /// See [`WriteOptions::escape_mode`]
+    pub fn escape_mode(mut self, escape_mode: EscapeMode) -> Self {
+        self.opts.escape_mode = escape_mode;
+        self
+    }
+    /// See [`WriteOptions::preamble`]
+    pub fn preamble<S: Into<String>>(mut self, preamble: S) -> Self {
+        self.opts.preamble = Some(preamble.into());
+        self
+    }
+    /// See [`WriteOptions::space_before_self_close`]
+    pub fn space_before_self_close(mut self, space_before_self_close: bool) -> Self {
+        self.opts.space_before_self_close = space_before_self_close;
+        self
+    }
+    /// See [`WriteOptions::namespace_decls_first`]
+    pub fn namespace_decls_first(mut self, namespace_decls_first: bool) -> Self {
+        self.opts.namespace_decls_first = namespace_decls_first;
+        self
+    }
+    /// See [`WriteOptions::indent_text_nodes`]
+    pub fn indent_text_nodes(mut self, indent_text_nodes: bool) -> Self {
+        self.opts.indent_text_nodes = indent_text_nodes;
+        self
+    }
+    pub fn build(self) -> WriteOptions {
+        self.opts
+    }
+}
+
+/// Which characters are escaped when writing text and attribute values.
+///
+/// Comment, CData, and PI content is never escaped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum EscapeMode {
+    /// Escape `<`, `>`, `&`, `'` and `"`.
+    ///
+    /// Attribute values and namespace declarations are escaped twice, so `a&b` is written as `a&amp;amp;b`
+    /// and parsed again as `a&amp;b`. This is how attributes have always been written.
+    /// Use [`EscapeMode::Minimal`] to escape them once.
+    #[default]
+    Full,
+    /// Only escape characters that must be escaped to be well-formed.
+    ///
+    /// `<` and `&` are escaped in text, as well as `>` in `]]>`.
+    /// `<`, `&` and `"` are escaped in attribute values.
+    Minimal,
+}
+impl EscapeMode {
+    fn escape_text<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        match self {
+            EscapeMode::Full => quick_xml::escape::escape(text),
+            EscapeMode::Minimal => {
+                let escaped = quick_xml::escape::minimal_escape(text);
+                if escaped.contains("]]>") {
+                    Cow::Owned(escaped.replace("]]>", "]]&gt;"))
+                } else {
+                    escaped
+                }
+            }
+        }
+    }
+    fn push_attribute(&self, start: &mut BytesStart, key: &str, value: &str) {
+        match self {
+            // Escaped again by push_attribute
+            EscapeMode::Full => {
+                let value = quick_xml::escape::escape(value);
+                start.push_attribute((key, value.as_ref()));
+            }
+            EscapeMode::Minimal => {
+                let mut escaped = String::with_capacity(value.len());
+                for c in value.chars() {
+                    match c {
+                        '<' => escaped.push_str("&lt;"),
+                        '&' => escaped.push_str("&amp;"),
+                        '"' => escaped.push_str("&quot;"),
+                        c => escaped.push(c),
+                    }
+                }
+                start.push_attribute((key.as_bytes(), escaped.as_bytes()));
+            }
         }
     }
 }
@@ -229,7 +698,7 @@ impl Document {
         self.write_file_with_opts(path, WriteOptions::default())
     }
     pub fn write_file_with_opts<P: AsRef<Path>>(&self, path: P, opts: WriteOptions) -> Result<()> {
-        let mut file = File::open(path)?;
+        let mut file = BufWriter::new(File::create(path)?);
         self.write_with_opts(&mut file, opts)
     }
 
@@ -242,16 +711,95 @@ impl Document {
         Ok(String::from_utf8(buf)?)
     }
 
+    /// Consumes the document and writes it to a `String` with default [`WriteOptions`].
+    ///
+    /// ```
+    /// use edit_xml::Document;
+    ///
+    /// let xml = Document::new_with_root("root", |root| root.attribute("id", "1"))
+    ///     .into_string()
+    ///     .unwrap();
+    /// assert_eq!(xml, "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<root id=\"1\"/>");
+    /// ```
+    pub fn into_string(self) -> Result<String> {
+        self.write_str_with_opts(WriteOptions::default())
+    }
+    /// Consumes the document and writes it to a `String`.
+    pub fn into_string_with_opts(self, opts: WriteOptions) -> Result<String> {
+        self.write_str_with_opts(opts)
+    }
+
+    /// Writes the document with each node on its own line, indented by 2 spaces, and the XML declaration.
+    ///
+    /// ```
+    /// use edit_xml::Document;
+    ///
+    /// let doc = Document::new_with_root("root", |root| root.create_element("child", |child| child));
+    /// assert_eq!(
+    ///     doc.pretty_string().unwrap(),
+    ///     "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<root>\n  <child/>\n</root>"
+    /// );
+    /// ```
+    pub fn pretty_string(&self) -> Result<String> {
+        self.write_str_with_opts(WriteOptions::default())
+    }
+    /// Writes the document without indentation or whitespace between nodes.
+    ///
+    /// ```
+    /// use edit_xml::Document;
+    ///
+    /// let doc = Document::new_with_root("root", |root| root.create_element("child", |child| child));
+    /// assert_eq!(
+    ///     doc.compact_string().unwrap(),
+    ///     "<?xml version=\"1.0\" encoding=\"UTF-8\"?><root><child/></root>"
+    /// );
+    /// ```
+    pub fn compact_string(&self) -> Result<String> {
+        let opts = WriteOptions {
+            pretty: false,
+            ..Default::default()
+        };
+        self.write_str_with_opts(opts)
+    }
+
     pub fn write(&self, writer: &mut impl Write) -> Result<()> {
         self.write_with_opts(writer, WriteOptions::default())
     }
+    /// Writes the document to `writer`, and flushes it.
     pub fn write_with_opts(&self, writer: &mut impl Write, opts: WriteOptions) -> Result<()> {
         let container = self.container();
-        let mut writer = Writer::new_with_indent(writer, opts.indent_char, opts.indent_size);
+        if let Some(preamble) = &opts.preamble {
+            writer.write_all(preamble.as_bytes())?;
+            if !preamble.ends_with('\n') {
+                writer.write_all(b"\n")?;
+            }
+        }
+        let mut writer = opts.writer(writer);
         if opts.write_decl {
             self.write_decl(&mut writer)?;
         }
-        self.write_nodes(&mut writer, container.children(self))?;
+        self.write_nodes(&mut writer, container.children(self), &opts, 0)?;
+        writer.write_event(Event::Eof)?;
+        writer.into_inner().flush()?;
+        Ok(())
+    }
+
+    /// Writes `nodes` without the XML declaration. Used for partial serialization.
+    pub(crate) fn write_nodes_str(&self, nodes: &[Node], opts: WriteOptions) -> Result<String> {
+        let mut buf: Vec<u8> = Vec::with_capacity(200);
+        self.write_nodes_to(&mut buf, nodes, &opts)?;
+        Ok(String::from_utf8(buf)?)
+    }
+
+    /// Writes `nodes` to `writer` without the XML declaration or preamble.
+    pub(crate) fn write_nodes_to(
+        &self,
+        writer: &mut impl Write,
+        nodes: &[Node],
+        opts: &WriteOptions,
+    ) -> Result<()> {
+        let mut writer = opts.writer(writer);
+        self.write_nodes(&mut writer, nodes, opts, 0)?;
         writer.write_event(Event::Eof)?;
         Ok(())
     }
@@ -266,15 +814,24 @@ impl Document {
         Ok(())
     }
 
-    fn write_nodes(&self, writer: &mut Writer<impl Write>, nodes: &[Node]) -> Result<()> {
+    fn write_nodes(
+        &self,
+        writer: &mut Writer<impl Write>,
+        nodes: &[Node],
+        opts: &WriteOptions,
+        depth: usize,
+    ) -> Result<()> {
         for node in nodes {
             match node {
-                Node::Element(eid) => self.write_element(writer, *eid)?,
-                Node::Text(text) => writer.write_event(Event::Text(BytesText::new(text)))?,
+                Node::Element(eid) => self.write_element_with_opts(writer, *eid, opts, depth)?,
+                Node::Text(text) => writer.write_event(Event::Text(BytesText::from_escaped(
+                    opts.escape_mode.escape_text(text),
+                )))?,
+                // quick-xml writes the whitespace after `<!DOCTYPE`
                 Node::DocType(text) => writer.write_event(Event::DocType(
-                    BytesText::new(&format!(" {text}")), // add a whitespace before text
+                    BytesText::from_escaped(text.trim_matches(XML_WHITESPACE)),
                 ))?,
-                // Comment, CData, and PI content is not escaped.
+                // DocType, Comment, CData, and PI content is not escaped.
                 Node::Comment(text) => {
                     // Unescaped Text??
                     writer.write_event(Event::Comment(BytesText::new(text)))?
@@ -290,31 +847,118 @@ impl Document {
     }
 
     pub fn write_element(&self, writer: &mut Writer<impl Write>, element: Element) -> Result<()> {
+        self.write_element_with_opts(writer, element, &WriteOptions::default(), 0)
+    }
+
+    fn write_element_with_opts(
+        &self,
+        writer: &mut Writer<impl Write>,
+        element: Element,
+        opts: &WriteOptions,
+        depth: usize,
+    ) -> Result<()> {
         let name_bytes = element.full_name(self);
         let mut start = BytesStart::new(name_bytes);
-        for (key, val) in element.attributes(self) {
-            let val = quick_xml::escape::escape(val.as_str());
-            start.push_attribute((key.as_str(), val.as_ref()));
+        if opts.namespace_decls_first {
+            self.push_namespace_decls(&mut start, element, opts);
+            self.push_attributes(&mut start, element, opts);
+        } else {
+            self.push_attributes(&mut start, element, opts);
+            self.push_namespace_decls(&mut start, element, opts);
         }
-        for (prefix, val) in element.namespace_decls(self) {
-            let attr_name = if prefix.is_empty() {
-                "xmlns".to_string()
-            } else {
-                format!("xmlns:{prefix}")
-            };
-
-            let val = quick_xml::escape::escape(val.as_str());
-            start.push_attribute((attr_name.as_str(), val.as_ref()));
+        if let Some(max_line_width) = opts.max_line_width {
+            // `<` + start + `/>`, or ` />`
+            let width = depth * opts.indent_size
+                + start.len()
+                + if opts.space_before_self_close { 4 } else { 3 };
+            if width > max_line_width {
+                start = wrap_attributes(&start, name_bytes, opts, depth)?;
+            }
         }
         if element.has_children(self) {
             writer.write_event(Event::Start(start))?;
-            self.write_nodes(writer, element.children(self))?;
+            let children = element.children(self);
+            if opts.pretty
+                && opts.indent_text_nodes
+                && children
+                    .iter()
+                    .all(|node| matches!(node, Node::Text(_) | Node::CData(_)))
+            {
+                // Text doesn't trigger a line break in quick-xml, so the end tag is indented here.
+                writer.write_indent()?;
+                self.write_nodes(writer, children, opts, depth + 1)?;
+                let indent =
+                    String::from(opts.indent_char as char).repeat(depth * opts.indent_size);
+                writer
+                    .get_mut()
+                    .write_all(format!("\n{indent}").as_bytes())?;
+            } else {
+                self.write_nodes(writer, children, opts, depth + 1)?;
+            }
             writer.write_event(Event::End(BytesEnd::new(name_bytes)))?;
         } else {
             writer.write_event(Event::Empty(start))?;
         }
         Ok(())
     }
+
+    fn push_attributes(&self, start: &mut BytesStart, element: Element, opts: &WriteOptions) {
+        for (key, val) in element.attributes(self) {
+            opts.escape_mode.push_attribute(start, key, val);
+        }
+    }
+
+    fn push_namespace_decls(&self, start: &mut BytesStart, element: Element, opts: &WriteOptions) {
+        for (prefix, val) in element.namespace_decls(self) {
+            let attr_name = if prefix.is_empty() {
+                "xmlns".to_string()
+            } else {
+                format!("xmlns:{prefix}")
+            };
+            opts.escape_mode.push_attribute(start, &attr_name, val);
+        }
+    }
+}
+
+/// Writes a fragment of nodes, without the XML declaration or a root element.
+///
+/// [`Node::Element`]s are written with their descendants, which are read from `doc`.
+/// The nodes don't need to be part of the document tree, e.g. elements that are not pushed to any parent.
+///
+/// ```
+/// use edit_xml::{Document, Element, Node, WriteOptions, write_nodes_to_string};
+///
+/// let mut doc = Document::new();
+/// let a = Element::build("a").add_text("1").finish(&mut doc);
+/// let nodes = vec![Node::Element(a), Node::Text("text".to_string())];
+/// let opts = WriteOptions::builder().pretty(false).build();
+/// assert_eq!(write_nodes_to_string(&doc, &nodes, opts).unwrap(), "<a>1</a>text");
+/// ```
+pub fn write_nodes_to_string(doc: &Document, nodes: &[Node], opts: WriteOptions) -> Result<String> {
+    doc.write_nodes_str(nodes, opts)
+}
+
+/// Rebuilds `start` with each attribute on its own line, indented one level beyond the element.
+fn wrap_attributes(
+    start: &BytesStart,
+    name: &str,
+    opts: &WriteOptions,
+    depth: usize,
+) -> Result<BytesStart<'static>> {
+    let indent = String::from(opts.indent_char as char).repeat((depth + 1) * opts.indent_size);
+    let mut content = String::with_capacity(start.len() * 2);
+    content.push_str(name);
+    for attr in start.attributes() {
+        let attr = attr?;
+        content.push('\n');
+        content.push_str(&indent);
+        content.push_str(std::str::from_utf8(attr.key.as_ref())?);
+        content.push_str("=\"");
+        // The value is already escaped
+        content.push_str(std::str::from_utf8(&attr.value)?);
+        content.push('"');
+    }
+    Ok(BytesStart::from_content(content, name.len()))
 }
 
 impl FromStr for Document {
@@ -347,4 +991,533 @@ mod tests {
             basic.children(&doc).last().unwrap().as_element().unwrap()
         )
     }
+
+    #[test]
+    fn test_require_root_element() {
+        let mut doc = Document::new();
+        assert!(matches!(
+            doc.require_root_element(),
+            Err(EditXMLError::NoRootElement)
+        ));
+
+        doc.push_root_node(Node::Comment("Comment".to_owned()))
+            .unwrap();
+        assert!(matches!(
+            doc.require_root_element(),
+            Err(EditXMLError::NoRootElement)
+        ));
+
+        let root = Element::new(&mut doc, "root");
+        doc.push_root_node(root).unwrap();
+        assert_eq!(doc.require_root_element().unwrap(), root);
+    }
+
+    #[test]
+    fn test_clone_into_document() {
+        let src = Document::from_str(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+        <src xmlns:p="urn:p">
+            <p:item id="1">Text<inner><!-- Comment --></inner></p:item>
+        </src>
+        "#,
+        )
+        .unwrap();
+        let src_written = src.write_str().unwrap();
+        let item = src
+            .root_element()
+            .unwrap()
+            .first_child_element(&src)
+            .unwrap();
+
+        let mut dest = Document::new_with_root("dest", |root| root);
+        let dest_root = dest.root_element().unwrap();
+        dest_root.set_namespace_decl(&mut dest, "p", "urn:p");
+        let copy = item.clone_into_document(&src, &mut dest);
+        assert_eq!(copy.parent(&dest), None);
+        dest_root.push_child(&mut dest, copy).unwrap();
+
+        assert_eq!(copy.full_name(&dest), "p:item");
+        assert_eq!(copy.namespace(&dest), Some("urn:p"));
+        assert_eq!(copy.attribute(&dest, "id"), Some("1"));
+        let inner = copy.find(&dest, "inner").unwrap();
+        assert_eq!(inner.parent(&dest), Some(copy));
+        assert!(inner.children(&dest)[0].is_comment());
+
+        // The source is unchanged
+        assert_eq!(src.write_str().unwrap(), src_written);
+        let dest_written = dest.write_str().unwrap();
+        assert!(
+            dest_written.contains("<p:item id=\"1\">Text<inner>"),
+            "{dest_written}"
+        );
+        let reparsed = Document::from_str(&dest_written).unwrap();
+        let reparsed_item = reparsed
+            .root_element()
+            .unwrap()
+            .first_child_element(&reparsed)
+            .unwrap();
+        assert_eq!(reparsed_item.breakdown(&reparsed), item.breakdown(&src));
+    }
+
+    #[test]
+    fn test_append_document() {
+        let mut doc = Document::from_str(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+        <first id="1">Text</first>
+        "#,
+        )
+        .unwrap();
+        let other = Document::from_str(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+        <!-- Comment -->
+        <p:second xmlns:p="urn:p">
+            <p:inner attr="value">Inner</p:inner>
+        </p:second>
+        "#,
+        )
+        .unwrap();
+        doc.append_document(other);
+
+        let root_nodes = doc.root_nodes();
+        assert_eq!(root_nodes.len(), 3);
+        assert!(root_nodes[1].is_comment());
+        let second = root_nodes[2].as_element().unwrap();
+        assert!(second.is_root(&doc));
+        let inner = second.first_child_element(&doc).unwrap();
+        assert_eq!(inner.parent(&doc), Some(second));
+        assert_eq!(inner.attribute(&doc, "attr"), Some("value"));
+        assert_eq!(inner.namespace(&doc), Some("urn:p"));
+        assert_eq!(doc.number_of_elements(), 4);
+
+        let written = doc.write_str().unwrap();
+        let reparsed = Document::from_str(&written).unwrap();
+        assert_eq!(reparsed.breakdown(), doc.breakdown());
+    }
+
+    #[test]
+    fn test_escape_mode() {
+        let mut doc = Document::new_with_root("root", |root| {
+            root.attribute("attr", "a > b & 'c' \"d\"")
+                .add_text("1 < 2 > 0 & 'a' \"b\" ]]>")
+        });
+        let root = doc.root_element().unwrap();
+        root.set_namespace_decl(&mut doc, "p", "urn:a&b");
+        let write = |escape_mode| {
+            let opts = WriteOptions {
+                escape_mode,
+                write_decl: false,
+                ..Default::default()
+            };
+            doc.write_str_with_opts(opts).unwrap()
+        };
+
+        let full = write(EscapeMode::Full);
+        assert!(
+            full.contains(">1 &lt; 2 &gt; 0 &amp; &apos;a&apos; &quot;b&quot; ]]&gt;</root>"),
+            "{full}"
+        );
+        // Attribute and namespace values are escaped twice
+        assert!(
+            full.contains(
+                "attr=\"a &amp;gt; b &amp;amp; &amp;apos;c&amp;apos; &amp;quot;d&amp;quot;\""
+            ),
+            "{full}"
+        );
+        assert!(full.contains("xmlns:p=\"urn:a&amp;amp;b\""), "{full}");
+        let reparsed = Document::parse_str_with_opts(&full, ReadOptions::relaxed()).unwrap();
+        assert_eq!(
+            reparsed
+                .root_element()
+                .unwrap()
+                .attribute(&reparsed, "attr"),
+            Some("a &gt; b &amp; &apos;c&apos; &quot;d&quot;")
+        );
+        let minimal = write(EscapeMode::Minimal);
+        assert!(
+            minimal.contains(">1 &lt; 2 > 0 &amp; 'a' \"b\" ]]&gt;</root>"),
+            "{minimal}"
+        );
+        assert!(
+            minimal.contains("attr=\"a > b &amp; 'c' &quot;d&quot;\""),
+            "{minimal}"
+        );
+        assert!(minimal.contains("xmlns:p=\"urn:a&amp;b\""), "{minimal}");
+
+        let read_opts = ReadOptions {
+            trim_text: false,
+            ..ReadOptions::relaxed()
+        };
+        let reparsed = Document::parse_str_with_opts(&minimal, read_opts).unwrap();
+        let reparsed_root = reparsed.root_element().unwrap();
+        assert_eq!(
+            reparsed_root.attribute(&reparsed, "attr"),
+            Some("a > b & 'c' \"d\"")
+        );
+        assert_eq!(
+            reparsed_root.namespace_for_prefix(&reparsed, "p"),
+            Some("urn:a&b")
+        );
+        assert_eq!(
+            reparsed_root.text_content(&reparsed),
+            "1 < 2 > 0 & 'a' \"b\" ]]>"
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_options_serde() {
+        let read_opts = ReadOptions::relaxed();
+        let json = serde_json::to_string(&read_opts).unwrap();
+        assert_eq!(
+            serde_json::from_str::<ReadOptions>(&json).unwrap(),
+            read_opts
+        );
+
+        let write_opts = WriteOptions::default();
+        let json = serde_json::to_string(&write_opts).unwrap();
+        assert_eq!(
+            serde_json::from_str::<WriteOptions>(&json).unwrap(),
+            write_opts
+        );
+
+        // Missing fields use the default values
+        let write_opts: WriteOptions =
+            serde_json::from_str(r#"{"indent_size": 4, "escape_mode": "Minimal"}"#).unwrap();
+        assert_eq!(
+            write_opts,
+            WriteOptions {
+                indent_size: 4,
+                escape_mode: EscapeMode::Minimal,
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn test_shrink_to_fit() {
+        let mut doc = Document::new_with_store_size(1000);
+        let root = Element::new(&mut doc, "root");
+        doc.push_root_node(root).unwrap();
+        assert!(doc.store.capacity() > 100);
+        doc.shrink_to_fit();
+        assert!(doc.store.capacity() < 100);
+        assert_eq!(doc.number_of_elements(), 2);
+    }
+
+    #[test]
+    fn test_compact() {
+        let mut doc = Document::from_str(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+        <root>
+            <a><removed>Text</removed><kept attr="1">Kept</kept></a>
+            <b><c /></b>
+        </root>
+        "#,
+        )
+        .unwrap();
+        let root = doc.root_element().unwrap();
+        let a = root.find(&doc, "a").unwrap();
+        a.find(&doc, "removed").unwrap().detach(&mut doc).unwrap();
+        let b = root.find(&doc, "b").unwrap();
+        let c = b.find(&doc, "c").unwrap();
+        b.detach(&mut doc).unwrap();
+        Element::new(&mut doc, "unused");
+        let expected = doc.write_str().unwrap();
+        assert_eq!(doc.number_of_elements(), 8);
+
+        let map = doc.compact();
+        assert_eq!(doc.number_of_elements(), 4);
+        assert_eq!(map.len(), 4);
+        assert!(!map.contains_key(&b));
+        assert!(!map.contains_key(&c));
+        assert_eq!(doc.write_str().unwrap(), expected);
+
+        let root = map[&root];
+        assert_eq!(doc.root_element(), Some(root));
+        let a = map[&a];
+        assert_eq!(a.parent(&doc), Some(root));
+        let kept = a.find(&doc, "kept").unwrap();
+        assert_eq!(kept.parent(&doc), Some(a));
+        assert_eq!(kept.attribute(&doc, "attr"), Some("1"));
+
+        // New elements get new ids after compaction
+        let new = Element::new(&mut doc, "new");
+        assert_eq!(doc.number_of_elements(), 5);
+        root.push_child(&mut doc, new).unwrap();
+        assert_eq!(new.name(&doc), "new");
+    }
+
+    #[test]
+    fn test_collect_garbage() {
+        let mut doc = Document::new_with_root("root", |root| root);
+        for i in 0..100 {
+            let root = doc.root_element().unwrap();
+            let elem = Element::build("item")
+                .attribute("i", i.to_string())
+                .add_text("Text")
+                .push_to(&mut doc, root);
+            Element::build("inner").push_to(&mut doc, elem);
+            if i % 10 != 0 {
+                elem.detach(&mut doc).unwrap();
+            }
+        }
+        assert_eq!(doc.number_of_elements(), 202);
+        let expected = doc.write_str().unwrap();
+
+        assert_eq!(doc.collect_garbage(), 180);
+        assert_eq!(doc.number_of_elements(), 22);
+        assert_eq!(doc.write_str().unwrap(), expected);
+        assert_eq!(doc.collect_garbage(), 0);
+
+        let root = doc.root_element().unwrap();
+        let items = root.child_elements(&doc);
+        assert_eq!(items.len(), 10);
+        for (i, item) in items.into_iter().enumerate() {
+            let i = (i * 10).to_string();
+            assert_eq!(item.attribute(&doc, "i"), Some(i.as_str()));
+            assert_eq!(item.parent(&doc), Some(root));
+            let inner = item.first_child_element(&doc).unwrap();
+            assert_eq!(inner.parent(&doc), Some(item));
+        }
+    }
+
+    #[test]
+    fn test_write_flushes() {
+        let doc = Document::new_with_root("root", |root| root.add_text("Text"));
+        let mut writer = std::io::BufWriter::new(Vec::new());
+        doc.write(&mut writer).unwrap();
+        assert!(writer.buffer().is_empty());
+        let written = String::from_utf8(writer.get_ref().clone()).unwrap();
+        assert_eq!(written, doc.write_str().unwrap());
+        assert!(written.ends_with("<root>Text</root>"));
+    }
+
+    #[test]
+    fn test_write_file() {
+        let doc = Document::new_with_root("root", |root| root.add_text("Text"));
+        let path = std::env::temp_dir().join(format!("edit-xml-write-{}.xml", std::process::id()));
+        doc.write_file(&path).unwrap();
+        let read = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(read, doc.write_str().unwrap());
+    }
+
+    #[test]
+    fn test_preamble() {
+        let doc = Document::new_with_root("svg", |root| root.attribute("width", "10"));
+        let opts = WriteOptions {
+            preamble: Some("<!-- Generated file. Do not edit. -->".to_owned()),
+            write_decl: false,
+            ..Default::default()
+        };
+        let written = doc.write_str_with_opts(opts).unwrap();
+        assert_eq!(
+            written,
+            "<!-- Generated file. Do not edit. -->\n<svg width=\"10\"/>"
+        );
+        let reparsed = Document::parse_str_with_opts(&written, ReadOptions::relaxed()).unwrap();
+        assert!(reparsed.root_nodes()[0].is_comment());
+        assert_eq!(reparsed.root_element().unwrap().name(&reparsed), "svg");
+
+        let opts = WriteOptions {
+            preamble: Some("banner\n".to_owned()),
+            ..Default::default()
+        };
+        let written = doc.write_str_with_opts(opts).unwrap();
+        assert!(written.starts_with("banner\n<?xml"), "{written}");
+    }
+
+    #[test]
+    fn test_namespace_decls_first() {
+        let xml = r#"<root xmlns="urn:default" id="1"><p:child xmlns:p="urn:p" p:id="2" /></root>"#;
+        let doc = Document::parse_str_with_opts(xml, ReadOptions::relaxed()).unwrap();
+        let opts = WriteOptions {
+            write_decl: false,
+            indent_size: 0,
+            ..Default::default()
+        };
+        assert_eq!(
+            doc.write_str_with_opts(opts.clone()).unwrap(),
+            "<root id=\"1\" xmlns=\"urn:default\">\n<p:child p:id=\"2\" xmlns:p=\"urn:p\"/>\n</root>"
+        );
+        let opts = WriteOptions {
+            namespace_decls_first: true,
+            ..opts
+        };
+        assert_eq!(
+            doc.write_str_with_opts(opts).unwrap(),
+            "<root xmlns=\"urn:default\" id=\"1\">\n<p:child xmlns:p=\"urn:p\" p:id=\"2\"/>\n</root>"
+        );
+    }
+
+    #[test]
+    fn test_write_nodes_to_string() {
+        let mut doc = Document::new_with_root("root", |root| root);
+        let first = Element::build("first")
+            .attribute("id", "1")
+            .create_element("child", |child| child.add_text("a & b"))
+            .finish(&mut doc);
+        let second = Element::new(&mut doc, "second");
+        let nodes = vec![
+            Node::Element(first),
+            Node::Text("between".to_owned()),
+            Node::Element(second),
+        ];
+        let opts = WriteOptions {
+            pretty: false,
+            ..Default::default()
+        };
+        assert_eq!(
+            write_nodes_to_string(&doc, &nodes, opts).unwrap(),
+            r#"<first id="1"><child>a &amp; b</child></first>between<second/>"#
+        );
+        assert_eq!(
+            write_nodes_to_string(&doc, &nodes, WriteOptions::default()).unwrap(),
+            "<first id=\"1\">\n  <child>a &amp; b</child>\n</first>between<second/>"
+        );
+        assert_eq!(
+            write_nodes_to_string(&doc, &[], WriteOptions::default()).unwrap(),
+            ""
+        );
+        // The fragment isn't part of the document
+        assert_eq!(
+            doc.write_str().unwrap(),
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<root/>"
+        );
+    }
+
+    #[test]
+    fn test_indent_text_nodes() {
+        let doc = Document::new_with_root("root", |root| {
+            root.create_element("a", |e| e.add_text("x"))
+                .create_element("b", |e| e.create_element("c", |e| e.add_text("y")))
+        });
+        let opts = WriteOptions {
+            write_decl: false,
+            ..Default::default()
+        };
+        assert_eq!(
+            doc.write_str_with_opts(opts.clone()).unwrap(),
+            "<root>\n  <a>x</a>\n  <b>\n    <c>y</c>\n  </b>\n</root>"
+        );
+        let opts = WriteOptions {
+            indent_text_nodes: true,
+            ..opts
+        };
+        assert_eq!(
+            doc.write_str_with_opts(opts.clone()).unwrap(),
+            "<root>\n  <a>\n    x\n  </a>\n  <b>\n    <c>\n      y\n    </c>\n  </b>\n</root>"
+        );
+        let opts = WriteOptions {
+            pretty: false,
+            ..opts
+        };
+        assert_eq!(
+            doc.write_str_with_opts(opts).unwrap(),
+            "<root><a>x</a><b><c>y</c></b></root>"
+        );
+    }
+
+    #[test]
+    fn test_space_before_self_close() {
+        let doc = Document::new_with_root("root", |root| {
+            root.create_element("empty", |e| e)
+                .create_element("attr", |e| e.attribute("id", "1"))
+        });
+        let opts = WriteOptions {
+            write_decl: false,
+            ..Default::default()
+        };
+        assert_eq!(
+            doc.write_str_with_opts(opts.clone()).unwrap(),
+            "<root>\n  <empty/>\n  <attr id=\"1\"/>\n</root>"
+        );
+        let opts = WriteOptions {
+            space_before_self_close: true,
+            ..opts
+        };
+        assert_eq!(
+            doc.write_str_with_opts(opts.clone()).unwrap(),
+            "<root>\n  <empty />\n  <attr id=\"1\" />\n</root>"
+        );
+        let root = doc.root_element().unwrap();
+        assert_eq!(
+            root.inner_xml_with_opts(&doc, opts).unwrap(),
+            "<empty />\n<attr id=\"1\" />"
+        );
+    }
+
+    #[test]
+    fn test_max_line_width() {
+        let mut doc = Document::new_with_root("root", |root| {
+            root.create_element("wide", |elem| {
+                elem.attribute("first", "a b")
+                    .attribute("second", "second value")
+                    .attribute("third", "3")
+                    .add_text("Text")
+            })
+            .create_element("narrow", |elem| elem.attribute("a", "1"))
+        });
+        let root = doc.root_element().unwrap();
+        root.set_namespace_decl(&mut doc, "p", "urn:example:namespace");
+        let opts = WriteOptions {
+            max_line_width: Some(30),
+            ..Default::default()
+        };
+        let written = doc.write_str_with_opts(opts).unwrap();
+        let lines: Vec<&str> = written.lines().collect();
+        assert!(lines.contains(&"<root"), "{written}");
+        assert!(
+            lines.contains(&"  xmlns:p=\"urn:example:namespace\">"),
+            "{written}"
+        );
+        assert!(lines.contains(&"  <wide"), "{written}");
+        // Attribute order isn't stable, so the last attribute may be followed by `>Text</wide>`
+        for attr in ["first=\"a b\"", "second=\"second value\"", "third=\"3\""] {
+            let indented = format!("    {attr}");
+            assert!(
+                lines.iter().any(|line| line.starts_with(&indented)),
+                "{written}"
+            );
+        }
+        assert!(lines.contains(&"  <narrow a=\"1\"/>"), "{written}");
+
+        let reparsed = Document::from_str(&written).unwrap();
+        assert_eq!(reparsed.breakdown(), doc.breakdown());
+        let unwrapped = doc.write_str().unwrap();
+        assert_eq!(
+            Document::from_str(&unwrapped).unwrap().breakdown(),
+            reparsed.breakdown()
+        );
+    }
+
+    #[test]
+    fn test_doctype() {
+        let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+        <!-- Comment -->
+        <!DOCTYPE note SYSTEM "note.dtd">
+        <note />
+        "#;
+        let mut doc = Document::from_str(xml).unwrap();
+        assert_eq!(doc.doctype(), Some(r#"note SYSTEM "note.dtd""#));
+
+        doc.set_doctype(Some("note".to_owned()));
+        assert_eq!(doc.doctype(), Some("note"));
+        assert_eq!(doc.root_nodes().len(), 3);
+        assert!(doc.root_nodes()[1].is_doctype());
+
+        doc.set_doctype(None);
+        assert_eq!(doc.doctype(), None);
+        assert_eq!(doc.root_nodes().len(), 2);
+
+        doc.set_doctype(Some("html".to_owned()));
+        assert_eq!(doc.doctype(), Some("html"));
+        assert!(doc.root_nodes()[0].is_doctype());
+        let written = doc.write_str().unwrap();
+        assert!(written.contains("<!DOCTYPE") && written.contains("html>"));
+
+        let mut empty = Document::new();
+        empty.set_doctype(None);
+        assert!(empty.is_empty());
+    }
 }
@@ -7,6 +7,9 @@ pub use breakdown::*;
 use crate::{Document, Element, element::ElementDebug};
 
 /// Represents an XML node.
+///
+/// [`Element`] is a handle to data stored in the [`Document`], so cloning a `Node::Element`
+/// results in two nodes pointing to the same element. Use [`Node::clone_deep`] to copy the element itself.
 #[derive(Debug, Clone, PartialEq)]
 pub enum Node {
     /// XML Element
@@ -20,8 +23,21 @@ pub enum Node {
     /// Processing Instruction ([specification](https://www.w3.org/TR/xml/#sec-pi))
     PI(String),
     /// Document Type Declaration ([specification](https://www.w3.org/TR/xml/#sec-prolog-dtd))
+    ///
+    /// The content between `<!DOCTYPE` and `>`, e.g. `note SYSTEM "note.dtd"`.
+    /// It is not escaped when written, and whitespace around it is ignored.
     DocType(String),
 }
+/// The kind of a [`Node`], without its content.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum NodeKind {
+    Element,
+    Text,
+    Comment,
+    CData,
+    PI,
+    DocType,
+}
 impl From<Element> for Node {
     fn from(elem: Element) -> Self {
         Node::Element(elem)
@@ -48,6 +64,35 @@ macro_rules! enum_is {
     };
 }
 impl Node {
+    /// Returns the [`NodeKind`] of this node.
+    ///
+    /// ```
+    /// use edit_xml::{Document, NodeKind};
+    /// use std::collections::HashMap;
+    ///
+    /// let doc = Document::parse_str(r#"<?xml version="1.0"?>
+    /// <root>Text<!-- Comment --><a /><b /></root>
+    /// "#).unwrap();
+    /// let root = doc.root_element().unwrap();
+    /// let mut counts: HashMap<NodeKind, usize> = HashMap::new();
+    /// for node in root.children(&doc) {
+    ///     *counts.entry(node.kind()).or_default() += 1;
+    /// }
+    /// assert_eq!(counts[&NodeKind::Element], 2);
+    /// assert_eq!(counts[&NodeKind::Text], 1);
+    /// assert_eq!(counts[&NodeKind::Comment], 1);
+    /// assert!(!counts.contains_key(&NodeKind::CData));
+    /// ```
+    pub fn kind(&self) -> NodeKind {
+        match self {
+            Node::Element(_) => NodeKind::Element,
+            Node::Text(_) => NodeKind::Text,
+            Node::Comment(_) => NodeKind::Comment,
+            Node::CData(_) => NodeKind::CData,
+            Node::PI(_) => NodeKind::PI,
+            Node::DocType(_) => NodeKind::DocType,
+        }
+    }
     /// Useful to use inside `filter_map`.
     ///
     /// ```
@@ -74,6 +119,32 @@ impl Node {
             _ => None,
         }
     }
+    /// Clones the node, copying the element and its descendants if it is an element.
+    ///
+    /// [`Node::clone`] only copies the [`Element`] handle, so modifying the element of the clone
+    /// also modifies the original. The element returned by this method is independent, and has no parent.
+    ///
+    /// ```
+    /// use edit_xml::{Document, Element, Node};
+    ///
+    /// let mut doc = Document::new();
+    /// let elem = Element::new_with_text(&mut doc, "item", "Original");
+    /// let node = Node::Element(elem);
+    ///
+    /// let alias = node.clone();
+    /// let copy = node.clone_deep(&mut doc);
+    /// copy.as_element().unwrap().set_text_content(&mut doc, "Copy");
+    ///
+    /// assert_eq!(alias.text_content(&doc), "Original");
+    /// assert_eq!(copy.text_content(&doc), "Copy");
+    /// ```
+    pub fn clone_deep(&self, doc: &mut Document) -> Node {
+        match self {
+            Node::Element(elem) => Node::Element(elem.deep_clone(doc)),
+            node => node.clone(),
+        }
+    }
+
     #[cfg_attr(feature = "tracing", tracing::instrument)]
     pub(crate) fn build_text_content<'a>(&self, doc: &'a Document, buf: &'a mut String) {
         match self {
@@ -96,16 +167,30 @@ impl Node {
     }
     /// Returns content if node is `Text`, `CData`, or `PI`.
     ///
-    /// If node is `Element` Cow will be owned.
+    /// If node is `Element` Cow will be owned, and contain [Element::text_content()].
     /// Otherwise, Cow will be borrowed.
     ///
     /// If None is returned it is a comment or doctype
-    pub fn possible_borrowed_text(&self) -> Option<Cow<'_, str>> {
+    ///
+    /// ```
+    /// use edit_xml::{Document, Node};
+    ///
+    /// let doc = Document::parse_str(r#"<?xml version="1.0"?>
+    /// <root><a>Hello<b>World</b></a></root>
+    /// "#).unwrap();
+    /// let a = doc.root_element().unwrap().find(&doc, "a").unwrap();
+    /// let node = Node::Element(a);
+    /// assert_eq!(node.possible_borrowed_text(&doc).unwrap(), "HelloWorld");
+    /// let node = Node::Text("Text".to_string());
+    /// assert_eq!(node.possible_borrowed_text(&doc).unwrap(), "Text");
+    /// assert!(Node::Comment("Comment".to_string()).possible_borrowed_text(&doc).is_none());
+    /// ```
+    pub fn possible_borrowed_text(&self, doc: &Document) -> Option<Cow<'_, str>> {
         match self {
             Node::Text(text) => Some(Cow::Borrowed(text)),
             Node::CData(text) => Some(Cow::Borrowed(text)),
             Node::PI(text) => Some(Cow::Borrowed(text)),
-            Node::Element(element) => Some(Cow::Owned(element.text_content(&Document::new()))),
+            Node::Element(element) => Some(Cow::Owned(element.text_content(doc))),
             _ => None,
         }
     }
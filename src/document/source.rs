@@ -0,0 +1,111 @@
+use crate::Document;
+use crate::error::Result;
+use crate::parser::{DocumentParser, ReadOptions};
+use std::fmt;
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+/// Input accepted by [`Document::load`].
+///
+/// A `&str` or `String` is parsed as xml, not as a file path.
+/// Use a [`Path`] to read a file.
+pub enum Source<'a> {
+    /// Xml file to read.
+    Path(&'a Path),
+    /// Xml text. It is parsed like [`Document::parse_str`].
+    Str(&'a str),
+    /// Xml bytes. Their encoding is detected like [`Document::parse_reader`].
+    Bytes(&'a [u8]),
+    /// Reader of xml bytes.
+    Reader(Box<dyn Read + 'a>),
+}
+
+impl<'a> Source<'a> {
+    /// Creates a [`Source::Reader`].
+    pub fn reader<R: Read + 'a>(reader: R) -> Self {
+        Source::Reader(Box::new(reader))
+    }
+}
+
+impl fmt::Debug for Source<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Source::Path(path) => f.debug_tuple("Path").field(path).finish(),
+            Source::Str(str) => f.debug_tuple("Str").field(str).finish(),
+            Source::Bytes(bytes) => f.debug_tuple("Bytes").field(bytes).finish(),
+            Source::Reader(_) => f.write_str("Reader(..)"),
+        }
+    }
+}
+
+impl<'a> From<&'a Path> for Source<'a> {
+    fn from(path: &'a Path) -> Self {
+        Source::Path(path)
+    }
+}
+impl<'a> From<&'a PathBuf> for Source<'a> {
+    fn from(path: &'a PathBuf) -> Self {
+        Source::Path(path)
+    }
+}
+impl<'a> From<&'a str> for Source<'a> {
+    fn from(str: &'a str) -> Self {
+        Source::Str(str)
+    }
+}
+impl<'a> From<&'a String> for Source<'a> {
+    fn from(str: &'a String) -> Self {
+        Source::Str(str)
+    }
+}
+impl<'a> From<&'a [u8]> for Source<'a> {
+    fn from(bytes: &'a [u8]) -> Self {
+        Source::Bytes(bytes)
+    }
+}
+impl<'a> From<&'a Vec<u8>> for Source<'a> {
+    fn from(bytes: &'a Vec<u8>) -> Self {
+        Source::Bytes(bytes)
+    }
+}
+impl<'a> From<Box<dyn Read + 'a>> for Source<'a> {
+    fn from(reader: Box<dyn Read + 'a>) -> Self {
+        Source::Reader(reader)
+    }
+}
+
+impl Document {
+    /// Parses a document from any [`Source`].
+    ///
+    /// The `parse_*` methods can be used when the kind of input is known.
+    ///
+    /// ```
+    /// use edit_xml::{Document, Source};
+    ///
+    /// let doc = Document::load(r#"<?xml version="1.0"?><root>Text</root>"#).unwrap();
+    /// assert_eq!(doc.root_element().unwrap().text_content(&doc), "Text");
+    ///
+    /// let doc = Document::load(br#"<?xml version="1.0"?><root>Bytes</root>"#.as_slice()).unwrap();
+    /// assert_eq!(doc.root_element().unwrap().text_content(&doc), "Bytes");
+    ///
+    /// let reader = std::io::Cursor::new(r#"<?xml version="1.0"?><root>Reader</root>"#);
+    /// let doc = Document::load(Source::reader(reader)).unwrap();
+    /// assert_eq!(doc.root_element().unwrap().text_content(&doc), "Reader");
+    /// ```
+    pub fn load<'a, S: Into<Source<'a>>>(source: S) -> Result<Document> {
+        Self::load_with_opts(source, ReadOptions::default())
+    }
+    /// Parses a document from any [`Source`] with custom [`ReadOptions`].
+    pub fn load_with_opts<'a, S: Into<Source<'a>>>(
+        source: S,
+        opts: ReadOptions,
+    ) -> Result<Document> {
+        match source.into() {
+            Source::Path(path) => DocumentParser::parse_reader(File::open(path)?, opts),
+            Source::Str(str) => DocumentParser::parse_str(str, opts),
+            Source::Bytes(bytes) => DocumentParser::parse_reader(bytes, opts),
+            Source::Reader(reader) => DocumentParser::parse_reader(reader, opts),
+        }
+    }
+}
@@ -0,0 +1,105 @@
+use crate::utils::HashSet;
+use crate::{Document, Element, Node};
+
+/// Summary of the contents of a [`Document`]. Created with [`Document::statistics`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DocumentStatistics {
+    /// Number of elements, excluding the container.
+    pub elements: usize,
+    /// Number of [`Node::Text`] nodes.
+    pub text_nodes: usize,
+    /// Number of [`Node::Comment`] nodes.
+    pub comments: usize,
+    /// Number of [`Node::CData`] nodes.
+    pub cdata: usize,
+    /// Number of [`Node::PI`] nodes.
+    pub processing_instructions: usize,
+    /// Depth of the most deeply nested element. Root elements have a depth of 1.
+    pub max_depth: usize,
+    /// Number of distinct element names, including their namespace prefix.
+    pub distinct_element_names: usize,
+}
+
+impl Document {
+    /// Counts the nodes of the document in a single traversal.
+    ///
+    /// ```
+    /// use edit_xml::Document;
+    ///
+    /// let doc = Document::parse_str(r#"<?xml version="1.0"?>
+    /// <root>
+    ///     <item>Text</item>
+    ///     <item><!-- Comment --></item>
+    /// </root>
+    /// "#).unwrap();
+    /// let stats = doc.statistics();
+    /// assert_eq!(stats.elements, 3);
+    /// assert_eq!(stats.text_nodes, 1);
+    /// assert_eq!(stats.comments, 1);
+    /// assert_eq!(stats.max_depth, 2);
+    /// assert_eq!(stats.distinct_element_names, 2);
+    /// ```
+    pub fn statistics(&self) -> DocumentStatistics {
+        let mut stats = DocumentStatistics::default();
+        let mut names = HashSet::default();
+        // (element, depth of its children)
+        let mut stack: Vec<(Element, usize)> = vec![(self.container(), 1)];
+        while let Some((element, depth)) = stack.pop() {
+            for child in element.children(self) {
+                match child {
+                    Node::Element(child) => {
+                        stats.elements += 1;
+                        stats.max_depth = stats.max_depth.max(depth);
+                        names.insert(child.full_name(self));
+                        stack.push((*child, depth + 1));
+                    }
+                    Node::Text(_) => stats.text_nodes += 1,
+                    Node::Comment(_) => stats.comments += 1,
+                    Node::CData(_) => stats.cdata += 1,
+                    Node::PI(_) => stats.processing_instructions += 1,
+                    Node::DocType(_) => {}
+                }
+            }
+        }
+        stats.distinct_element_names = names.len();
+        stats
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_statistics() {
+        let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+        <!DOCTYPE root>
+        <?pi?>
+        <root>
+            <a>
+                <b>
+                    <c>Deep</c>
+                    <c><![CDATA[data]]></c>
+                </b>
+                <p:b xmlns:p="urn:p" />
+            </a>
+            <!-- Comment -->
+            <a>Text</a>
+        </root>
+        "#;
+        let doc = Document::parse_str(xml).unwrap();
+        assert_eq!(
+            doc.statistics(),
+            DocumentStatistics {
+                elements: 7,
+                text_nodes: 2,
+                comments: 1,
+                cdata: 1,
+                processing_instructions: 1,
+                max_depth: 4,
+                distinct_element_names: 5,
+            }
+        );
+        assert_eq!(Document::new().statistics(), DocumentStatistics::default());
+    }
+}
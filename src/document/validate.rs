@@ -0,0 +1,266 @@
+use crate::utils::names;
+use crate::{Document, Element, Node};
+use thiserror::Error;
+
+/// A reason a [`Document`] is not well-formed XML. Returned by [`Document::validate`].
+///
+/// `parent` is the element containing the invalid node, which is the container for root nodes.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum ValidationIssue {
+    /// The document has no root element.
+    #[error("Document has no root element")]
+    NoRootElement,
+    /// The document has more than one root element.
+    #[error("Document has {0} root elements")]
+    MultipleRootElements(usize),
+    /// The document has more than one [`Node::DocType`].
+    #[error("Document has multiple DOCTYPE declarations")]
+    MultipleDocTypes,
+    /// A [`Node::DocType`] comes after the root element, or is not a root node.
+    #[error("DOCTYPE declaration must come before the root element")]
+    MisplacedDocType { parent: Element },
+    /// A [`Node::Text`] that isn't whitespace only, or a [`Node::CData`], is a root node.
+    #[error("Text content outside of the root element")]
+    ContentOutsideRoot,
+    /// The name of an element is not a valid XML `QName`.
+    #[error("Invalid element name `{name}`")]
+    InvalidElementName { element: Element, name: String },
+    /// The name of an attribute or namespace declaration is not a valid XML `QName`.
+    #[error("Invalid attribute name `{name}`")]
+    InvalidAttributeName { element: Element, name: String },
+    /// A [`Node::Comment`] contains `--` or ends with `-`.
+    #[error("Comment contains `--` or ends with `-`")]
+    InvalidComment { parent: Element },
+    /// A [`Node::CData`] contains `]]>`.
+    #[error("CDATA section contains `]]>`")]
+    InvalidCData { parent: Element },
+    /// A [`Node::PI`] target is not a valid name or is `xml`, or its content contains `?>`.
+    #[error("Invalid processing instruction")]
+    InvalidProcessingInstruction { parent: Element },
+}
+
+impl Document {
+    /// Checks that the document can be written as well-formed XML.
+    ///
+    /// Documents built or modified through the API can have, for example, multiple root elements
+    /// or invalid names, which are written as is. Returns every issue found.
+    ///
+    /// ```
+    /// use edit_xml::{Document, Element, ValidationIssue};
+    ///
+    /// let mut doc = Document::new_with_root("root", |root| root);
+    /// assert!(doc.validate().is_ok());
+    ///
+    /// let second = Element::new(&mut doc, "second");
+    /// doc.push_root_node(second).unwrap();
+    /// assert_eq!(doc.validate(), Err(vec![ValidationIssue::MultipleRootElements(2)]));
+    /// ```
+    pub fn validate(&self) -> Result<(), Vec<ValidationIssue>> {
+        let mut issues = Vec::new();
+        let container = self.container();
+        let mut root_elements = 0;
+        let mut doctypes = 0;
+        for node in container.children(self) {
+            match node {
+                Node::Element(_) => root_elements += 1,
+                Node::DocType(_) => {
+                    doctypes += 1;
+                    if doctypes == 2 {
+                        issues.push(ValidationIssue::MultipleDocTypes);
+                    }
+                    if root_elements > 0 {
+                        issues.push(ValidationIssue::MisplacedDocType { parent: container });
+                    }
+                }
+                Node::Text(text)
+                    if !text.chars().all(|c| matches!(c, ' ' | '\t' | '\r' | '\n')) =>
+                {
+                    issues.push(ValidationIssue::ContentOutsideRoot)
+                }
+                Node::CData(_) => issues.push(ValidationIssue::ContentOutsideRoot),
+                _ => {}
+            }
+        }
+        match root_elements {
+            0 => issues.push(ValidationIssue::NoRootElement),
+            1 => {}
+            count => issues.push(ValidationIssue::MultipleRootElements(count)),
+        }
+
+        self.validate_children(container, &mut issues);
+        for element in container.child_elements_recursive(self) {
+            let name = element.full_name(self);
+            if !names::is_valid_qname(name) {
+                issues.push(ValidationIssue::InvalidElementName {
+                    element,
+                    name: name.to_owned(),
+                });
+            }
+            for name in element.attribute_names(self) {
+                if !names::is_valid_qname(name) {
+                    issues.push(ValidationIssue::InvalidAttributeName {
+                        element,
+                        name: name.to_owned(),
+                    });
+                }
+            }
+            for prefix in element.namespace_decls(self).keys() {
+                if !prefix.is_empty() && !names::is_valid_ncname(prefix) {
+                    issues.push(ValidationIssue::InvalidAttributeName {
+                        element,
+                        name: format!("xmlns:{prefix}"),
+                    });
+                }
+            }
+            self.validate_children(element, &mut issues);
+        }
+
+        if issues.is_empty() {
+            Ok(())
+        } else {
+            Err(issues)
+        }
+    }
+
+    /// Checks the non-element children of `parent`.
+    fn validate_children(&self, parent: Element, issues: &mut Vec<ValidationIssue>) {
+        for node in parent.children(self) {
+            match node {
+                Node::Comment(comment) if comment.contains("--") || comment.ends_with('-') => {
+                    issues.push(ValidationIssue::InvalidComment { parent })
+                }
+                Node::CData(cdata) if cdata.contains("]]>") => {
+                    issues.push(ValidationIssue::InvalidCData { parent })
+                }
+                Node::PI(pi) => {
+                    let target = pi.split([' ', '\t', '\r', '\n']).next().unwrap_or_default();
+                    if !names::is_valid_ncname(target)
+                        || target.eq_ignore_ascii_case("xml")
+                        || pi.contains("?>")
+                    {
+                        issues.push(ValidationIssue::InvalidProcessingInstruction { parent });
+                    }
+                }
+                Node::DocType(_) if !parent.is_container() => {
+                    issues.push(ValidationIssue::MisplacedDocType { parent })
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn issues(doc: &Document) -> Vec<ValidationIssue> {
+        doc.validate().err().unwrap_or_default()
+    }
+
+    #[test]
+    fn test_valid_document() {
+        let doc = Document::parse_str(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+            <!DOCTYPE root>
+            <?pi data?>
+            <!-- Comment -->
+            <root xmlns:p="urn:p" p:attr="1">
+                <p:child><![CDATA[Data]]></p:child>
+            </root>
+            "#,
+        )
+        .unwrap();
+        assert_eq!(doc.validate(), Ok(()));
+    }
+
+    #[test]
+    fn test_root_issues() {
+        let mut doc = Document::new();
+        assert_eq!(issues(&doc), vec![ValidationIssue::NoRootElement]);
+
+        doc.push_root_node(Node::Text("  \n".to_owned())).unwrap();
+        let root = Element::new(&mut doc, "root");
+        doc.push_root_node(root).unwrap();
+        assert_eq!(doc.validate(), Ok(()));
+
+        let second = Element::new(&mut doc, "second");
+        doc.push_root_node(second).unwrap();
+        doc.push_root_node(Node::DocType("root".to_owned()))
+            .unwrap();
+        doc.push_root_node(Node::DocType("root".to_owned()))
+            .unwrap();
+        doc.push_root_node(Node::Text("text".to_owned())).unwrap();
+        doc.push_root_node(Node::CData("data".to_owned())).unwrap();
+        let container = doc.container();
+        assert_eq!(
+            issues(&doc),
+            vec![
+                ValidationIssue::MisplacedDocType { parent: container },
+                ValidationIssue::MultipleDocTypes,
+                ValidationIssue::MisplacedDocType { parent: container },
+                ValidationIssue::ContentOutsideRoot,
+                ValidationIssue::ContentOutsideRoot,
+                ValidationIssue::MultipleRootElements(2),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_name_issues() {
+        let mut doc = Document::new();
+        let root = Element::build("1root")
+            .attribute("a b", "value")
+            .push_to_root_node(&mut doc);
+        root.set_namespace_decl(&mut doc, "p:q", "urn:p");
+        assert_eq!(
+            issues(&doc),
+            vec![
+                ValidationIssue::InvalidElementName {
+                    element: root,
+                    name: "1root".to_owned()
+                },
+                ValidationIssue::InvalidAttributeName {
+                    element: root,
+                    name: "a b".to_owned()
+                },
+                ValidationIssue::InvalidAttributeName {
+                    element: root,
+                    name: "xmlns:p:q".to_owned()
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_node_issues() {
+        let mut doc = Document::new();
+        let root = Element::build("root").push_to_root_node(&mut doc);
+        let container = doc.container();
+        for node in [
+            Node::Comment("a -- b".to_owned()),
+            Node::Comment("ends with -".to_owned()),
+            Node::CData("a ]]> b".to_owned()),
+            Node::PI("xml version".to_owned()),
+            Node::PI("1pi".to_owned()),
+            Node::PI("pi ?> data".to_owned()),
+            Node::DocType("root".to_owned()),
+        ] {
+            root.push_child(&mut doc, node).unwrap();
+        }
+        doc.push_root_node(Node::Comment("-".to_owned())).unwrap();
+        assert_eq!(
+            issues(&doc),
+            vec![
+                ValidationIssue::InvalidComment { parent: container },
+                ValidationIssue::InvalidComment { parent: root },
+                ValidationIssue::InvalidComment { parent: root },
+                ValidationIssue::InvalidCData { parent: root },
+                ValidationIssue::InvalidProcessingInstruction { parent: root },
+                ValidationIssue::InvalidProcessingInstruction { parent: root },
+                ValidationIssue::InvalidProcessingInstruction { parent: root },
+                ValidationIssue::MisplacedDocType { parent: root },
+            ]
+        );
+    }
+}
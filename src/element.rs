@@ -1,23 +1,44 @@
-use crate::document::{Document, Node};
+use crate::document::{Document, Node, NodeKind, WriteOptions};
 use crate::error::{EditXMLError, Result};
-use crate::utils::HashMap;
+use crate::name::Name;
+use crate::parser::ReadOptions;
+use crate::utils::{HashMap, names};
+use std::borrow::Cow;
 #[cfg(feature = "document-breakdown")]
 mod breakdown;
 #[cfg(feature = "document-breakdown")]
 pub use breakdown::*;
 mod builder;
 mod debug;
+mod path;
 mod sort;
+mod with_doc;
 pub use builder::ElementBuilder;
 pub use debug::ElementDebug;
+pub use with_doc::ElementWithDoc;
 
 #[derive(Debug, Default)]
 pub(crate) struct ElementData {
-    full_name: String,
-    attributes: HashMap<String, String>, // q:attr="val" => {"q:attr": "val"}
+    /// Shared between elements with the same name when parsed with [`ReadOptions::intern_names`].
+    full_name: Name,
+    attributes: HashMap<Name, String>, // q:attr="val" => {"q:attr": "val"}
     namespace_decls: HashMap<String, String>, // local namespace newly defined in attributes
     parent: Option<Element>,
     children: Vec<Node>,
+    source_position: Option<usize>,
+}
+impl ElementData {
+    /// Replaces the ids of its parent and children with `new_ids[old_id]`.
+    ///
+    /// The parent is removed if it has no new id.
+    pub(crate) fn remap_ids(&mut self, new_ids: &[Option<Element>]) {
+        self.parent = self.parent.and_then(|parent| new_ids[parent.id]);
+        for child in &mut self.children {
+            if let Node::Element(elem) = child {
+                *elem = new_ids[elem.id].unwrap_or(*elem);
+            }
+        }
+    }
 }
 /// Represents an XML element. It acts as a pointer to actual element data stored in Document.
 ///
@@ -53,19 +74,61 @@ pub(crate) struct ElementData {
 ///     .collect();
 /// ```
 ///
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct Element {
     pub(crate) id: usize,
 }
 
+/// Options for [`Element::text_content_with`]. Comments are never included.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TextExtractOptions {
+    /// Include the content of [`Node::CData`]. (default: `true`)
+    pub include_cdata: bool,
+    /// Include the content of [`Node::PI`]. (default: `false`)
+    pub include_pi: bool,
+    /// Include the text of child elements. (default: `true`)
+    pub recursive: bool,
+}
+impl Default for TextExtractOptions {
+    fn default() -> Self {
+        Self {
+            include_cdata: true,
+            include_pi: false,
+            recursive: true,
+        }
+    }
+}
+
 impl Element {
     /// Create a new empty element with `full_name`.
     ///
     /// If full_name contains `:`,
     /// everything before that will be interpreted as a namespace prefix.
+    ///
+    /// The name is not validated. It is the caller's responsibility to pass a valid XML name,
+    /// otherwise the written document can't be parsed again. Use [`Element::try_new`] to validate.
     pub fn new<S: Into<String>>(doc: &mut Document, full_name: S) -> Self {
+        let full_name: String = full_name.into();
         Self::with_data(doc, full_name.into(), HashMap::new(), HashMap::new())
     }
+    /// Create a new empty element with `full_name`, validating the name first.
+    ///
+    /// # Errors
+    ///    - [EditXMLError::InvalidName]: `full_name` is not a valid XML `QName`.
+    ///
+    /// ```
+    /// use edit_xml::{Document, Element};
+    /// let mut doc = Document::new();
+    /// assert!(Element::try_new(&mut doc, "pre:name").is_ok());
+    /// assert!(Element::try_new(&mut doc, "1name").is_err());
+    /// ```
+    pub fn try_new<S: Into<String>>(doc: &mut Document, full_name: S) -> Result<Self> {
+        let full_name = full_name.into();
+        if !names::is_valid_qname(&full_name) {
+            return Err(EditXMLError::InvalidName(full_name));
+        }
+        Ok(Self::new(doc, full_name))
+    }
     /// Create a new element with `full_name` and `text`.
     ///
     /// ```
@@ -103,8 +166,8 @@ impl Element {
     #[inline(always)]
     pub(crate) fn with_data(
         doc: &mut Document,
-        full_name: String,
-        attributes: HashMap<String, String>,
+        full_name: Name,
+        attributes: HashMap<Name, String>,
         namespace_decls: HashMap<String, String>,
     ) -> Element {
         Self::with_data_and_children_size(doc, full_name, attributes, namespace_decls, 1)
@@ -112,8 +175,8 @@ impl Element {
     #[inline(always)]
     pub(crate) fn with_data_and_children_size(
         doc: &mut Document,
-        full_name: String,
-        attributes: HashMap<String, String>,
+        full_name: Name,
+        attributes: HashMap<Name, String>,
         namespace_decls: HashMap<String, String>,
         children_size: usize,
     ) -> Element {
@@ -123,6 +186,7 @@ impl Element {
             namespace_decls,
             children: Vec::with_capacity(children_size),
             parent: None,
+            source_position: None,
         };
         doc.push_to_store(elem_data)
     }
@@ -156,6 +220,119 @@ impl Element {
         }
     }
 
+    /// Calls `f` with this element and the document, returning its result.
+    ///
+    /// Useful for grouping multiple modifications of the same element.
+    ///
+    /// ```
+    /// use edit_xml::{Document, Element};
+    /// let mut doc = Document::new();
+    /// let elem = Element::new(&mut doc, "item").modify(&mut doc, |elem, doc| {
+    ///     elem.set_attribute(doc, "id", "1");
+    ///     elem.set_text_content(doc, "First");
+    ///     elem
+    /// });
+    /// assert_eq!(elem.attribute(&doc, "id"), Some("1"));
+    /// ```
+    pub fn modify<F, R>(self, doc: &mut Document, f: F) -> R
+    where
+        F: FnOnce(Element, &mut Document) -> R,
+    {
+        f(self, doc)
+    }
+
+    /// Moves this element and its descendants from `src` into `dest`, returning the new element.
+    ///
+    /// The returned element has no parent. The data left in `src` is emptied.
+    pub(crate) fn move_to_document(self, src: &mut Document, dest: &mut Document) -> Element {
+        let ElementData {
+            full_name,
+            attributes,
+            namespace_decls,
+            children,
+            ..
+        } = std::mem::take(self.mut_data(src));
+        let elem = Self::with_data_and_children_size(
+            dest,
+            full_name,
+            attributes,
+            namespace_decls,
+            children.len(),
+        );
+        for child in children {
+            let child = match child {
+                Node::Element(child) => Node::Element(child.move_to_document(src, dest)),
+                node => node,
+            };
+            elem.push_child(dest, child).unwrap();
+        }
+        elem
+    }
+
+    /// Copies this element and its descendants from `src` into `dest`, returning the new element.
+    ///
+    /// The returned element has no parent, and `src` is left unchanged.
+    ///
+    /// ```
+    /// use edit_xml::Document;
+    ///
+    /// let template = Document::parse_str(r#"<?xml version="1.0"?>
+    /// <template><item type="a">Text</item></template>
+    /// "#).unwrap();
+    /// let item = template.root_element().unwrap().find(&template, "item").unwrap();
+    ///
+    /// let mut doc = Document::new_with_root("root", |root| root);
+    /// let root = doc.root_element().unwrap();
+    /// for _ in 0..2 {
+    ///     let copy = item.clone_into_document(&template, &mut doc);
+    ///     root.push_child(&mut doc, copy).unwrap();
+    /// }
+    /// assert_eq!(root.find_all(&doc, "item").len(), 2);
+    /// assert_eq!(item.parent(&template), template.root_element());
+    /// ```
+    pub fn clone_into_document(&self, src: &Document, dest: &mut Document) -> Element {
+        let data = self.data(src);
+        let elem = Self::with_data_and_children_size(
+            dest,
+            data.full_name.clone(),
+            data.attributes.clone(),
+            data.namespace_decls.clone(),
+            data.children.len(),
+        );
+        for child in &data.children {
+            let child = match child {
+                Node::Element(child) => Node::Element(child.clone_into_document(src, dest)),
+                node => node.clone(),
+            };
+            elem.push_child(dest, child).unwrap();
+        }
+        elem
+    }
+
+    /// Copies this element and its descendants, returning the new element.
+    ///
+    /// The returned element has no parent. Unlike copying the `Element` itself,
+    /// which only copies the handle, changes to the copy don't affect this element.
+    pub fn deep_clone(&self, doc: &mut Document) -> Element {
+        let data = self.data(doc);
+        let full_name = data.full_name.clone();
+        let attributes = data.attributes.clone();
+        let namespace_decls = data.namespace_decls.clone();
+        let children = data.children.clone();
+        let elem = Self::with_data_and_children_size(
+            doc,
+            full_name,
+            attributes,
+            namespace_decls,
+            children.len(),
+        );
+        for child in children {
+            let child = child.clone_deep(doc);
+            elem.push_child(doc, child).unwrap();
+        }
+        elem
+    }
+
     /// Creates an [ElementDebug]
     ///
     /// Used to debug an element with its children.
@@ -165,6 +342,26 @@ impl Element {
     ) -> ElementDebug<'element, 'doc> {
         ElementDebug { element: self, doc }
     }
+
+    /// Creates an [ElementWithDoc], which can index attribute values by name.
+    ///
+    /// ```
+    /// use edit_xml::{Document, Element};
+    /// let mut doc = Document::new();
+    /// let elem = Element::build("item").attribute("id", "1").finish(&mut doc);
+    /// assert_eq!(&elem.with(&doc)["id"], "1");
+    /// ```
+    ///
+    /// Indexing a missing attribute panics:
+    /// ```should_panic
+    /// use edit_xml::{Document, Element};
+    /// let mut doc = Document::new();
+    /// let elem = Element::build("item").finish(&mut doc);
+    /// let _ = &elem.with(&doc)["id"];
+    /// ```
+    pub fn with<'doc>(&self, doc: &'doc Document) -> ElementWithDoc<'doc> {
+        ElementWithDoc::new(*self, doc)
+    }
 }
 
 /// Below are methods that take `&Document` as its first argument.
@@ -184,16 +381,81 @@ impl Element {
         self.parent(doc).is_some_and(|p| p.is_container())
     }
 
+    /// Get the byte offset of this element's start tag (`<`) in the parsed input.
+    ///
+    /// Only recorded when parsing with [`ReadOptions::track_positions`](crate::ReadOptions::track_positions).
+    /// Returns `None` for elements that were created, not parsed.
+    ///
+    /// The offset is counted in the input as decoded into UTF-8, excluding any byte order mark.
+    /// For UTF-8 documents without a byte order mark, this is the offset in the original input.
+    pub fn source_position(&self, doc: &Document) -> Option<usize> {
+        self.data(doc).source_position
+    }
+
+    pub(crate) fn set_source_position(&self, doc: &mut Document, position: usize) {
+        self.mut_data(doc).source_position = Some(position);
+    }
+
     /// Get full name of element, including its namespace prefix.
     /// Use [`Element::name()`] to get its name without the prefix.
     pub fn full_name<'a>(&self, doc: &'a Document) -> &'a str {
         &self.data(doc).full_name
     }
 
+    /// Set full name of element, including its namespace prefix.
+    ///
+    /// The name is not validated, so a name like `a:b:c` is written as is and can't be parsed again.
+    /// Use [`Element::try_set_full_name`] to validate.
     pub fn set_full_name<S: Into<String>>(&self, doc: &mut Document, name: S) {
+        let name: String = name.into();
         self.mut_data(doc).full_name = name.into();
     }
 
+    /// Set full name of element, including its namespace prefix.
+    ///
+    /// # Errors
+    ///    - [EditXMLError::InvalidName]: `name` is not a valid XML `QName`.
+    ///      It can have at most one `:`, which separates the prefix from the name.
+    ///
+    /// ```
+    /// use edit_xml::{Document, Element};
+    /// let mut doc = Document::new();
+    /// let elem = Element::new(&mut doc, "name");
+    /// assert!(elem.try_set_full_name(&mut doc, "a:b:c").is_err());
+    /// assert_eq!(elem.full_name(&doc), "name");
+    /// elem.try_set_full_name(&mut doc, "ns:name").unwrap();
+    /// assert_eq!(elem.prefix(&doc), "ns");
+    /// ```
+    pub fn try_set_full_name<S: Into<String>>(&self, doc: &mut Document, name: S) -> Result<()> {
+        let name = name.into();
+        if !names::is_valid_qname(&name) {
+            return Err(EditXMLError::InvalidName(name));
+        }
+        self.set_full_name(doc, name);
+        Ok(())
+    }
+
+    /// Same as [`Element::full_name`]. The name as written in the tag, including its namespace prefix.
+    ///
+    /// ```
+    /// use edit_xml::{Document, Element};
+    /// let mut doc = Document::new();
+    /// let elem = Element::new(&mut doc, "svg:rect");
+    /// assert_eq!(elem.qualified_name(&doc), "svg:rect");
+    /// assert_eq!(elem.full_name(&doc), "svg:rect");
+    /// assert_eq!(elem.local_name(&doc), "rect");
+    /// assert_eq!(elem.name(&doc), "rect");
+    /// assert_eq!(elem.prefix(&doc), "svg");
+    ///
+    /// // Without a prefix, both names are the same
+    /// let elem = Element::new(&mut doc, "rect");
+    /// assert_eq!(elem.qualified_name(&doc), "rect");
+    /// assert_eq!(elem.local_name(&doc), "rect");
+    /// ```
+    pub fn qualified_name<'a>(&self, doc: &'a Document) -> &'a str {
+        self.full_name(doc)
+    }
+
     /// Get prefix and name of element. If it doesn't have prefix, will return an empty string.
     ///
     /// `<prefix: name` -> `("prefix", "name")`
@@ -219,9 +481,9 @@ impl Element {
         let (_, name) = Self::separate_prefix_name(&data.full_name);
         let prefix: String = prefix.into();
         if prefix.is_empty() {
-            data.full_name = name.to_string();
+            data.full_name = name.into();
         } else {
-            data.full_name = format!("{prefix}:{name}");
+            data.full_name = format!("{prefix}:{name}").into();
         }
     }
 
@@ -233,23 +495,99 @@ impl Element {
         self.prefix_name(doc).1
     }
 
+    /// Same as [`Element::name`]. The name without its namespace prefix.
+    /// Use [`Element::qualified_name`] to get the name with its prefix.
+    ///
+    /// ```
+    /// use edit_xml::{Document, Element};
+    /// let mut doc = Document::new();
+    /// let elem = Element::new(&mut doc, "xhtml:p");
+    /// assert_eq!(elem.local_name(&doc), "p");
+    /// assert_eq!(elem.local_name(&doc), elem.name(&doc));
+    /// ```
+    pub fn local_name<'a>(&self, doc: &'a Document) -> &'a str {
+        self.name(doc)
+    }
+
     /// Set name of element, preserving its prefix.
     ///
     /// `name` should not have a `:`,
     /// or everything before `:` may be interpreted as namespace prefix.
+    ///
+    /// The name is not validated. Use [`Element::try_set_name`] to validate.
     pub fn set_name<S: Into<String>>(&self, doc: &mut Document, name: S) {
         let data = self.mut_data(doc);
         let (prefix, _) = Self::separate_prefix_name(&data.full_name);
+        let name: String = name.into();
         if prefix.is_empty() {
             data.full_name = name.into();
         } else {
-            data.full_name = format!("{}:{}", prefix, name.into());
+            data.full_name = format!("{prefix}:{name}").into();
+        }
+    }
+
+    /// Set name of element, preserving its prefix.
+    ///
+    /// # Errors
+    ///    - [EditXMLError::InvalidName]: `name` is not a valid XML `NCName`. (It can't contain `:`)
+    pub fn try_set_name<S: Into<String>>(&self, doc: &mut Document, name: S) -> Result<()> {
+        let name = name.into();
+        if !names::is_valid_ncname(&name) {
+            return Err(EditXMLError::InvalidName(name));
+        }
+        self.set_name(doc, name);
+        Ok(())
+    }
+
+    /// Renames all descendant elements named `from` to `to`, preserving their prefixes.
+    ///
+    /// This element itself is not renamed. Returns the number of renamed elements.
+    ///
+    /// ```
+    /// use edit_xml::Document;
+    /// let mut doc = Document::parse_str(r#"<?xml version="1.0"?>
+    /// <root><old><p:old /></old><other /></root>
+    /// "#).unwrap();
+    /// let root = doc.root_element().unwrap();
+    /// assert_eq!(root.rename_descendants(&mut doc, "old", "new"), 2);
+    /// let new = root.find(&doc, "new").unwrap();
+    /// assert_eq!(new.first_child_element(&doc).unwrap().full_name(&doc), "p:new");
+    /// ```
+    pub fn rename_descendants(&self, doc: &mut Document, from: &str, to: &str) -> usize {
+        let mut count = 0;
+        for elem in self.child_elements_recursive(doc) {
+            if elem.name(doc) == from {
+                elem.set_name(doc, to);
+                count += 1;
+            }
+        }
+        count
+    }
+
+    /// Changes the prefix of all descendant elements with prefix `from_prefix` to `to_prefix`.
+    ///
+    /// An empty string matches or sets no prefix.
+    /// This element itself is not changed. Returns the number of changed elements.
+    pub fn set_prefix_all_descendants(
+        &self,
+        doc: &mut Document,
+        from_prefix: &str,
+        to_prefix: &str,
+    ) -> usize {
+        let mut count = 0;
+        for elem in self.child_elements_recursive(doc) {
+            if elem.prefix(doc) == from_prefix {
+                elem.set_prefix(doc, to_prefix);
+                count += 1;
+            }
         }
+        count
     }
 
     /// Get attributes of element.
     ///
     /// The attribute names may have namespace prefix. To strip the prefix and only its name, call [`Element::separate_prefix_name`].
+    /// The names are [`Name`]s, which deref to `str`.
     /// ```
     /// use edit_xml::{Document, Element};
     ///
@@ -265,28 +603,291 @@ impl Element {
     ///     // ("", "id"), ("pre", "name")
     /// }
     /// ```
-    pub fn attributes<'a>(&self, doc: &'a Document) -> &'a HashMap<String, String> {
+    pub fn attributes<'a>(&self, doc: &'a Document) -> &'a HashMap<Name, String> {
         &self.data(doc).attributes
     }
 
+    /// Iterate over the full names of the attributes of this element.
+    ///
+    /// Namespace declarations are not included. The order is not specified.
+    ///
+    /// ```
+    /// use edit_xml::{Document, Element};
+    ///
+    /// let mut doc = Document::new();
+    /// let element = Element::build("name")
+    ///     .attribute("id", "name")
+    ///     .attribute("pre:name", "value")
+    ///     .finish(&mut doc);
+    ///
+    /// let mut names: Vec<&str> = element.attribute_names(&doc).collect();
+    /// names.sort();
+    /// assert_eq!(names, vec!["id", "pre:name"]);
+    /// assert_eq!(element.attribute_count(&doc), 2);
+    /// ```
+    pub fn attribute_names<'a>(&self, doc: &'a Document) -> impl Iterator<Item = &'a str> + 'a {
+        self.attributes(doc).keys().map(Name::as_str)
+    }
+
+    /// Get the attributes whose namespace prefix is `prefix`, as `(full name, value)` pairs.
+    ///
+    /// An empty `prefix` matches the attributes without a prefix. Namespace declarations are not included.
+    ///
+    /// ```
+    /// use edit_xml::{Document, Element};
+    ///
+    /// let mut doc = Document::new();
+    /// let element = Element::build("use")
+    ///     .attribute("xlink:href", "#icon")
+    ///     .attribute("xlink:title", "Icon")
+    ///     .attribute("x", "10")
+    ///     .finish(&mut doc);
+    ///
+    /// let mut xlink: Vec<(&str, &str)> = element.attributes_with_prefix(&doc, "xlink").collect();
+    /// xlink.sort();
+    /// assert_eq!(xlink, vec![("xlink:href", "#icon"), ("xlink:title", "Icon")]);
+    /// let unprefixed: Vec<(&str, &str)> = element.attributes_with_prefix(&doc, "").collect();
+    /// assert_eq!(unprefixed, vec![("x", "10")]);
+    /// ```
+    pub fn attributes_with_prefix<'a>(
+        &self,
+        doc: &'a Document,
+        prefix: &str,
+    ) -> impl Iterator<Item = (&'a str, &'a str)> {
+        self.attributes(doc)
+            .iter()
+            .filter(move |(name, _)| Self::separate_prefix_name(name).0 == prefix)
+            .map(|(name, value)| (name.as_str(), value.as_str()))
+    }
+
+    /// Get the attributes of this element as `(full name, value)` pairs, sorted by full name.
+    ///
+    /// Namespace declarations are not included. The document isn't modified.
+    ///
+    /// ```
+    /// use edit_xml::{Document, Element};
+    ///
+    /// let mut doc = Document::new();
+    /// let element = Element::build("name")
+    ///     .attribute("pre:name", "value")
+    ///     .attribute("id", "name")
+    ///     .attribute("class", "big")
+    ///     .finish(&mut doc);
+    ///
+    /// assert_eq!(
+    ///     element.attributes_sorted(&doc),
+    ///     vec![("class", "big"), ("id", "name"), ("pre:name", "value")]
+    /// );
+    /// ```
+    pub fn attributes_sorted<'a>(&self, doc: &'a Document) -> Vec<(&'a str, &'a str)> {
+        let mut attributes: Vec<(&str, &str)> = self
+            .attributes(doc)
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.as_str()))
+            .collect();
+        attributes.sort_unstable_by_key(|(name, _)| *name);
+        attributes
+    }
+
+    /// Get the number of attributes of this element. Namespace declarations are not included.
+    pub fn attribute_count(&self, doc: &Document) -> usize {
+        self.attributes(doc).len()
+    }
+
     /// Get attribute value of an element by its full name. (Namespace prefix isn't stripped)
     pub fn attribute<'a>(&self, doc: &'a Document, name: &str) -> Option<&'a str> {
         self.attributes(doc).get(name).map(|v| v.as_str())
     }
 
+    /// Returns `true` if the element has an attribute with the full name `name`.
+    pub fn has_attribute(&self, doc: &Document, name: &str) -> bool {
+        self.attributes(doc).contains_key(name)
+    }
+
+    /// Get attribute value parsed as a boolean.
+    ///
+    /// `true`, `1`, `yes` are parsed as `true` and `false`, `0`, `no` are parsed as `false`. (Case insensitive)
+    ///
+    /// Returns `None` if the attribute doesn't exist or has any other value.
+    ///
+    /// ```
+    /// use edit_xml::{Document, Element};
+    /// let mut doc = Document::new();
+    /// let elem = Element::build("input")
+    ///     .attribute("disabled", "True")
+    ///     .attribute("checked", "0")
+    ///     .finish(&mut doc);
+    /// assert_eq!(elem.attribute_bool(&doc, "disabled"), Some(true));
+    /// assert_eq!(elem.attribute_bool(&doc, "checked"), Some(false));
+    /// assert_eq!(elem.attribute_bool(&doc, "hidden"), None);
+    /// ```
+    pub fn attribute_bool(&self, doc: &Document, name: &str) -> Option<bool> {
+        let value = self.attribute(doc, name)?.trim();
+        if ["true", "1", "yes"]
+            .iter()
+            .any(|v| value.eq_ignore_ascii_case(v))
+        {
+            Some(true)
+        } else if ["false", "0", "no"]
+            .iter()
+            .any(|v| value.eq_ignore_ascii_case(v))
+        {
+            Some(false)
+        } else {
+            None
+        }
+    }
+
+    /// Get attribute value parsed with [`FromStr`](std::str::FromStr).
+    ///
+    /// Returns `None` if the attribute doesn't exist.
+    ///
+    /// ```
+    /// use edit_xml::{Document, Element};
+    /// let mut doc = Document::new();
+    /// let elem = Element::build("item")
+    ///     .attribute("count", "3")
+    ///     .finish(&mut doc);
+    /// assert_eq!(elem.attribute_parsed::<u32>(&doc, "count"), Some(Ok(3)));
+    /// assert!(elem.attribute_parsed::<u32>(&doc, "missing").is_none());
+    /// ```
+    pub fn attribute_parsed<T: std::str::FromStr>(
+        &self,
+        doc: &Document,
+        name: &str,
+    ) -> Option<std::result::Result<T, T::Err>> {
+        self.attribute(doc, name).map(str::parse)
+    }
+
+    /// Returns `true` if the value of attribute `attr`, split on whitespace, contains `token`.
+    ///
+    /// Useful for space separated lists like `class="a b c"`, similar to DOM `classList`.
+    ///
+    /// ```
+    /// use edit_xml::{Document, Element};
+    /// let mut doc = Document::new();
+    /// let div = Element::build("div").attribute("class", "card  wide").finish(&mut doc);
+    /// assert!(div.attribute_contains_token(&doc, "class", "wide"));
+    /// assert!(!div.attribute_contains_token(&doc, "class", "car"));
+    ///
+    /// assert!(div.add_attribute_token(&mut doc, "class", "dark"));
+    /// assert_eq!(div.attribute(&doc, "class"), Some("card  wide dark"));
+    /// assert!(div.remove_attribute_token(&mut doc, "class", "card"));
+    /// assert_eq!(div.attribute(&doc, "class"), Some("wide dark"));
+    /// ```
+    pub fn attribute_contains_token(&self, doc: &Document, attr: &str, token: &str) -> bool {
+        self.attribute(doc, attr)
+            .is_some_and(|value| value.split_ascii_whitespace().any(|t| t == token))
+    }
+
+    /// Appends `token` to the value of attribute `attr`, creating it if it doesn't exist.
+    ///
+    /// Returns `false` if the token is already there, or `token` is empty or has whitespace,
+    /// in which case nothing is changed. The other tokens are kept as they are.
+    pub fn add_attribute_token(&self, doc: &mut Document, attr: &str, token: &str) -> bool {
+        if token.is_empty()
+            || token.contains(|c: char| c.is_ascii_whitespace())
+            || self.attribute_contains_token(doc, attr, token)
+        {
+            return false;
+        }
+        self.modify_attribute(doc, attr, |value| {
+            Some(match value.map(str::trim_end) {
+                Some(value) if !value.is_empty() => format!("{value} {token}"),
+                _ => token.to_owned(),
+            })
+        });
+        true
+    }
+
+    /// Removes every occurrence of `token` from the value of attribute `attr`.
+    ///
+    /// The remaining tokens are joined with a single space. The attribute is kept even if it becomes empty.
+    /// Returns `false` if the token wasn't there.
+    pub fn remove_attribute_token(&self, doc: &mut Document, attr: &str, token: &str) -> bool {
+        if !self.attribute_contains_token(doc, attr, token) {
+            return false;
+        }
+        self.modify_attribute(doc, attr, |value| {
+            let tokens: Vec<&str> = value
+                .unwrap_or_default()
+                .split_ascii_whitespace()
+                .filter(|t| *t != token)
+                .collect();
+            Some(tokens.join(" "))
+        });
+        true
+    }
+
     /// Add or set attribute.
     ///
     /// If `name` contains a `:`,
     /// everything before `:` will be interpreted as namespace prefix.
+    ///
+    /// The name is not validated. Use [`Element::try_set_attribute`] to validate.
     pub fn set_attribute<S, T>(&self, doc: &mut Document, name: S, value: T)
     where
         S: Into<String>,
         T: Into<String>,
     {
-        self.mut_attributes(doc).insert(name.into(), value.into());
+        self.mut_attributes(doc)
+            .insert(Name::from(name.into()), value.into());
+    }
+
+    /// Add or set attribute.
+    ///
+    /// # Errors
+    ///    - [EditXMLError::InvalidName]: `name` is not a valid XML `QName`.
+    pub fn try_set_attribute<S, T>(&self, doc: &mut Document, name: S, value: T) -> Result<()>
+    where
+        S: Into<String>,
+        T: Into<String>,
+    {
+        let name = name.into();
+        if !names::is_valid_qname(&name) {
+            return Err(EditXMLError::InvalidName(name));
+        }
+        self.set_attribute(doc, name, value);
+        Ok(())
+    }
+
+    /// Reads the attribute `name` and replaces it with the value returned by `f`.
+    ///
+    /// `f` gets the current value, or `None` if the attribute doesn't exist.
+    /// If `f` returns `None`, the attribute is removed.
+    ///
+    /// ```
+    /// use edit_xml::{Document, Element};
+    ///
+    /// let mut doc = Document::new();
+    /// let elem = Element::build("page").attribute("views", "41").finish(&mut doc);
+    /// elem.modify_attribute(&mut doc, "views", |views| {
+    ///     let views: u32 = views.and_then(|v| v.parse().ok()).unwrap_or(0);
+    ///     Some((views + 1).to_string())
+    /// });
+    /// assert_eq!(elem.attribute(&doc, "views"), Some("42"));
+    /// ```
+    pub fn modify_attribute<F>(&self, doc: &mut Document, name: &str, f: F)
+    where
+        F: FnOnce(Option<&str>) -> Option<String>,
+    {
+        let attributes = self.mut_attributes(doc);
+        match attributes.get_mut(name) {
+            Some(value) => match f(Some(value)) {
+                Some(new_value) => *value = new_value,
+                None => {
+                    attributes.remove(name);
+                }
+            },
+            None => {
+                if let Some(new_value) = f(None) {
+                    attributes.insert(name.into(), new_value);
+                }
+            }
+        }
     }
 
-    pub fn mut_attributes<'a>(&self, doc: &'a mut Document) -> &'a mut HashMap<String, String> {
+    pub fn mut_attributes<'a>(&self, doc: &'a mut Document) -> &'a mut HashMap<Name, String> {
         &mut self.mut_data(doc).attributes
     }
 
@@ -320,12 +921,91 @@ impl Element {
             .insert(prefix.into(), namespace.into());
     }
 
-    /// Get namespace value given prefix, for this element.
-    /// "xml" and "xmlns" returns its default namespace.
-    pub fn namespace_for_prefix<'a>(&self, doc: &'a Document, prefix: &str) -> Option<&'a str> {
-        match prefix {
-            "xml" => return Some("http://www.w3.org/XML/1998/namespace"),
-            "xmlns" => return Some("http://www.w3.org/2000/xmlns/"),
+    /// Puts this element in the namespace `uri`.
+    ///
+    /// Sets the prefix of this element to `prefix`, and declares `xmlns:prefix="uri"` on it.
+    /// If `prefix` is empty, the prefix is removed and the default namespace is declared instead.
+    pub fn set_namespace(&self, doc: &mut Document, prefix: &str, uri: &str) {
+        self.set_prefix(doc, prefix);
+        self.set_namespace_decl(doc, prefix, uri);
+    }
+
+    /// Sets the attribute `prefix:local` in the namespace `uri`, declaring the namespace if needed.
+    ///
+    /// If a prefix in scope of this element already maps to `uri`, it is used instead of `prefix`.
+    /// Otherwise `xmlns:prefix="uri"` is declared on this element. If `prefix` is already bound
+    /// to another namespace, a number is appended to it (`prefix1`, `prefix2`, ...) so the existing binding is kept.
+    /// An empty `prefix` is replaced with `ns`, as attributes without a prefix are never in a namespace.
+    ///
+    /// Returns the prefix of the attribute.
+    ///
+    /// ```
+    /// use edit_xml::{Document, Element};
+    ///
+    /// let mut doc = Document::parse_str(r#"<?xml version="1.0"?>
+    /// <root xmlns:x="http://www.w3.org/1999/xlink"><a/></root>
+    /// "#).unwrap();
+    /// let a = doc.root_element().unwrap().find(&doc, "a").unwrap();
+    ///
+    /// let prefix = a.set_attribute_ns(&mut doc, "xlink", "href", "http://www.w3.org/1999/xlink", "#top");
+    /// assert_eq!(prefix, "x");
+    /// assert_eq!(a.attribute(&doc, "x:href"), Some("#top"));
+    ///
+    /// let prefix = a.set_attribute_ns(&mut doc, "dc", "title", "http://purl.org/dc/elements/1.1/", "Top");
+    /// assert_eq!(prefix, "dc");
+    /// assert_eq!(a.namespace_for_prefix(&doc, "dc"), Some("http://purl.org/dc/elements/1.1/"));
+    /// ```
+    pub fn set_attribute_ns<T: Into<String>>(
+        &self,
+        doc: &mut Document,
+        prefix: &str,
+        local: &str,
+        uri: &str,
+        value: T,
+    ) -> String {
+        let prefix = self.bind_namespace(doc, prefix, uri);
+        self.set_attribute(doc, format!("{prefix}:{local}"), value);
+        prefix
+    }
+
+    /// Returns a non-empty prefix that maps to `uri` in scope of this element, declaring it if needed.
+    /// See [`Element::set_attribute_ns`]
+    fn bind_namespace(&self, doc: &mut Document, prefix: &str, uri: &str) -> String {
+        let prefix = if prefix.is_empty() { "ns" } else { prefix };
+        if self.namespace_for_prefix(doc, prefix) == Some(uri) {
+            return prefix.to_owned();
+        }
+        let mut elem = Some(*self);
+        while let Some(current) = elem {
+            let existing = current
+                .namespace_decls(doc)
+                .iter()
+                .filter(|(p, value)| {
+                    !p.is_empty() && *value == uri && self.namespace_for_prefix(doc, p) == Some(uri)
+                })
+                .map(|(p, _)| p)
+                .min();
+            if let Some(existing) = existing {
+                return existing.clone();
+            }
+            elem = current.parent(doc);
+        }
+        let mut new_prefix = prefix.to_owned();
+        let mut n = 1;
+        while self.namespace_for_prefix(doc, &new_prefix).is_some() {
+            new_prefix = format!("{prefix}{n}");
+            n += 1;
+        }
+        self.set_namespace_decl(doc, new_prefix.clone(), uri);
+        new_prefix
+    }
+
+    /// Get namespace value given prefix, for this element.
+    /// "xml" and "xmlns" returns its default namespace.
+    pub fn namespace_for_prefix<'a>(&self, doc: &'a Document, prefix: &str) -> Option<&'a str> {
+        match prefix {
+            "xml" => return Some("http://www.w3.org/XML/1998/namespace"),
+            "xmlns" => return Some("http://www.w3.org/2000/xmlns/"),
             _ => (),
         };
         let mut elem = *self;
@@ -338,6 +1018,72 @@ impl Element {
         }
     }
 
+    /// Removes namespace declarations of this element that are redundant,
+    /// because the prefix already resolves to the same namespace in the scope of its ancestors.
+    ///
+    /// Declarations that override an ancestor's prefix with a different namespace are kept,
+    /// as removing them would change the namespace of this element and its descendants.
+    /// Returns the number of removed declarations.
+    ///
+    /// ```
+    /// use edit_xml::Document;
+    ///
+    /// let mut doc = Document::parse_str(r#"<?xml version="1.0"?>
+    /// <root xmlns:p="urn:p"><child xmlns:p="urn:p" xmlns:q="urn:q" /></root>
+    /// "#).unwrap();
+    /// let child = doc.root_element().unwrap().find(&doc, "child").unwrap();
+    /// assert_eq!(child.remove_conflicting_namespace_decls(&mut doc), 1);
+    /// assert!(!child.namespace_decls(&doc).contains_key("p"));
+    /// ```
+    pub fn remove_conflicting_namespace_decls(&self, doc: &mut Document) -> usize {
+        let Some(parent) = self.parent(doc) else {
+            return 0;
+        };
+        let redundant: Vec<String> = self
+            .namespace_decls(doc)
+            .iter()
+            .filter(|(prefix, namespace)| {
+                parent.namespace_for_prefix(doc, prefix) == Some(namespace.as_str())
+            })
+            .map(|(prefix, _)| prefix.clone())
+            .collect();
+        let namespace_decls = self.mut_namespace_decls(doc);
+        for prefix in &redundant {
+            namespace_decls.remove(prefix);
+        }
+        redundant.len()
+    }
+
+    /// Changes namespace declarations of `old_uri` to `new_uri`, in this element and its descendants.
+    ///
+    /// The prefixes are unchanged. Returns the number of declarations changed.
+    ///
+    /// ```
+    /// use edit_xml::Document;
+    ///
+    /// let mut doc = Document::parse_str(r#"<?xml version="1.0"?>
+    /// <root xmlns="urn:schema:v1"><p:child xmlns:p="urn:schema:v1" /></root>
+    /// "#).unwrap();
+    /// let root = doc.root_element().unwrap();
+    /// assert_eq!(root.replace_namespace_uri(&mut doc, "urn:schema:v1", "urn:schema:v2"), 2);
+    /// let child = root.first_child_element(&doc).unwrap();
+    /// assert_eq!(child.namespace(&doc), Some("urn:schema:v2"));
+    /// ```
+    pub fn replace_namespace_uri(&self, doc: &mut Document, old_uri: &str, new_uri: &str) -> usize {
+        let mut count = 0;
+        let mut elements = vec![*self];
+        elements.extend(self.child_elements_recursive(doc));
+        for elem in elements {
+            for namespace in elem.mut_namespace_decls(doc).values_mut() {
+                if namespace == old_uri {
+                    *namespace = new_uri.to_owned();
+                    count += 1;
+                }
+            }
+        }
+        count
+    }
+
     pub(crate) fn build_text_content<'a>(&self, doc: &'a Document, buf: &'a mut String) {
         for child in self.children(doc) {
             child.build_text_content(doc, buf);
@@ -353,12 +1099,314 @@ impl Element {
         buf
     }
 
+    /// Get the [text content](Element::text_content) of this element, borrowing it if possible.
+    ///
+    /// Returns [`Cow::Borrowed`] if the element has a single [`Node::Text`] or [`Node::CData`] child,
+    /// [`Cow::Owned`] if the text of multiple nodes had to be concatenated, and `None` if the text content is empty.
+    ///
+    /// ```
+    /// use std::borrow::Cow;
+    /// use edit_xml::Document;
+    /// let doc = Document::parse_str(r#"<?xml version="1.0"?>
+    /// <root><a>Text</a><b>More <i>Text</i></b><c></c></root>
+    /// "#).unwrap();
+    /// let root = doc.root_element().unwrap();
+    /// let a = root.find(&doc, "a").unwrap();
+    /// assert!(matches!(a.text_borrowed(&doc), Some(Cow::Borrowed("Text"))));
+    /// let b = root.find(&doc, "b").unwrap();
+    /// assert_eq!(b.text_borrowed(&doc).unwrap(), "MoreText");
+    /// let c = root.find(&doc, "c").unwrap();
+    /// assert_eq!(c.text_borrowed(&doc), None);
+    /// ```
+    pub fn text_borrowed<'a>(&self, doc: &'a Document) -> Option<Cow<'a, str>> {
+        let text = match self.children(doc).as_slice() {
+            [Node::Text(text) | Node::CData(text)] => Cow::Borrowed(text.as_str()),
+            _ => Cow::Owned(self.text_content(doc)),
+        };
+        if text.is_empty() { None } else { Some(text) }
+    }
+
+    /// Concatenate the text content of this element, with [`TextExtractOptions`] deciding which nodes are included.
+    ///
+    /// Unlike [`Element::text_content`], PI content is excluded by default.
+    ///
+    /// ```
+    /// use edit_xml::{Document, TextExtractOptions};
+    /// let doc = Document::parse_str(r#"<?xml version="1.0"?>
+    /// <root>Hello<?pi?><![CDATA[ World]]><b>!</b></root>
+    /// "#).unwrap();
+    /// let root = doc.root_element().unwrap();
+    /// assert_eq!(root.text_content(&doc), "Hellopi World!");
+    /// assert_eq!(root.text_content_with(&doc, TextExtractOptions::default()), "Hello World!");
+    /// let opts = TextExtractOptions {
+    ///     include_cdata: false,
+    ///     recursive: false,
+    ///     ..Default::default()
+    /// };
+    /// assert_eq!(root.text_content_with(&doc, opts), "Hello");
+    /// ```
+    pub fn text_content_with(&self, doc: &Document, opts: TextExtractOptions) -> String {
+        let mut buf = String::new();
+        self.build_text_content_with(doc, &mut buf, &opts);
+        buf
+    }
+
+    fn build_text_content_with(&self, doc: &Document, buf: &mut String, opts: &TextExtractOptions) {
+        for child in self.children(doc) {
+            match child {
+                Node::Element(elem) if opts.recursive => {
+                    elem.build_text_content_with(doc, buf, opts)
+                }
+                Node::Text(text) => buf.push_str(text),
+                Node::CData(text) if opts.include_cdata => buf.push_str(text),
+                Node::PI(text) if opts.include_pi => buf.push_str(text),
+                _ => {}
+            }
+        }
+    }
+
+    /// Get the content of the first [`Node::Text`] child of this element.
+    ///
+    /// Unlike [`Element::text_content`], only direct children are checked and nothing is concatenated.
+    ///
+    /// ```
+    /// use edit_xml::Document;
+    /// let doc = Document::parse_str(r#"<?xml version="1.0"?>
+    /// <root><title><b>Bold</b>First<!--Comment-->Second</title><empty /></root>
+    /// "#).unwrap();
+    /// let root = doc.root_element().unwrap();
+    /// let title = root.find(&doc, "title").unwrap();
+    /// assert_eq!(title.first_text(&doc), Some("First"));
+    /// assert_eq!(title.text_content(&doc), "BoldFirstSecond");
+    /// assert_eq!(root.find(&doc, "empty").unwrap().first_text(&doc), None);
+    /// ```
+    pub fn first_text<'a>(&self, doc: &'a Document) -> Option<&'a str> {
+        self.children(doc).iter().find_map(|node| match node {
+            Node::Text(text) => Some(text.as_str()),
+            _ => None,
+        })
+    }
+
+    /// Concatenate the text of the descendant [`Node::Text`] and [`Node::CData`] nodes, with `separator` between each node.
+    ///
+    /// Empty nodes are skipped. Unlike [`Element::text_content`], PI content is excluded.
+    ///
+    /// ```
+    /// use edit_xml::Document;
+    /// let doc = Document::parse_str(r#"<?xml version="1.0"?>
+    /// <l><i>a</i><i>b</i></l>
+    /// "#).unwrap();
+    /// let root = doc.root_element().unwrap();
+    /// assert_eq!(root.text_content(&doc), "ab");
+    /// assert_eq!(root.text_content_joined(&doc, ","), "a,b");
+    /// ```
+    pub fn text_content_joined(&self, doc: &Document, separator: &str) -> String {
+        let mut texts = Vec::new();
+        self.collect_texts(doc, &mut texts);
+        texts.join(separator)
+    }
+
+    fn collect_texts<'a>(&self, doc: &'a Document, texts: &mut Vec<&'a str>) {
+        for child in self.children(doc) {
+            match child {
+                Node::Element(elem) => elem.collect_texts(doc, texts),
+                Node::Text(text) | Node::CData(text) if !text.is_empty() => texts.push(text),
+                _ => {}
+            }
+        }
+    }
+
+    /// Replaces every occurrence of `from` with `to` in the descendant [`Node::Text`] nodes.
+    ///
+    /// Returns the number of replacements. CDATA, comments and attribute values are not changed,
+    /// and a match spanning multiple text nodes is not found. Nothing is replaced if `from` is empty.
+    ///
+    /// ```
+    /// use edit_xml::Document;
+    /// let mut doc = Document::parse_str(r#"<?xml version="1.0"?>
+    /// <root><a>foo bar</a><b>foo</b></root>
+    /// "#).unwrap();
+    /// let root = doc.root_element().unwrap();
+    /// assert_eq!(root.replace_in_text(&mut doc, "foo", "baz"), 2);
+    /// assert_eq!(root.text_content(&doc), "baz barbaz");
+    /// ```
+    pub fn replace_in_text(&self, doc: &mut Document, from: &str, to: &str) -> usize {
+        if from.is_empty() {
+            return 0;
+        }
+        self.modify_texts(doc, |text| {
+            let count = text.matches(from).count();
+            if count > 0 {
+                *text = text.replace(from, to);
+            }
+            count
+        })
+    }
+
+    /// Replaces every match of `regex` in the descendant [`Node::Text`] nodes with `replacement`.
+    ///
+    /// `replacement` can refer to capture groups, see [`regex::Regex::replace_all`].
+    /// Returns the number of replacements. Like [`Element::replace_in_text`],
+    /// only text nodes are changed and matches don't span multiple nodes.
+    ///
+    /// Requires the `regex` feature.
+    #[cfg(feature = "regex")]
+    pub fn replace_in_text_regex(
+        &self,
+        doc: &mut Document,
+        regex: &regex::Regex,
+        replacement: &str,
+    ) -> usize {
+        self.modify_texts(doc, |text| {
+            let count = regex.find_iter(text).count();
+            if count > 0 {
+                *text = regex.replace_all(text, replacement).into_owned();
+            }
+            count
+        })
+    }
+
+    /// Calls `f` on this element's descendant text nodes and sums the returned counts.
+    fn modify_texts(&self, doc: &mut Document, mut f: impl FnMut(&mut String) -> usize) -> usize {
+        let mut count = 0;
+        for elem in std::iter::once(*self).chain(self.child_elements_recursive(doc)) {
+            for child in elem.children_mut(doc) {
+                if let Node::Text(text) = child {
+                    count += f(text);
+                }
+            }
+        }
+        count
+    }
+
+    /// Appends the [text content](Element::text_content) of this element to `buf`.
+    ///
+    /// Use this over [`Element::text_content`] to reuse a buffer across multiple elements.
+    ///
+    /// ```
+    /// use edit_xml::Document;
+    /// let doc = Document::parse_str(r#"<?xml version="1.0"?>
+    /// <root><a>Hello</a><b>World</b></root>
+    /// "#).unwrap();
+    /// let root = doc.root_element().unwrap();
+    /// let mut buf = String::new();
+    /// for elem in root.child_elements(&doc) {
+    ///     buf.clear();
+    ///     elem.write_text_content(&doc, &mut buf);
+    ///     assert!(!buf.is_empty());
+    /// }
+    /// ```
+    pub fn write_text_content(&self, doc: &Document, buf: &mut String) {
+        self.build_text_content(doc, buf);
+    }
+
     /// Clears all its children and inserts a [`Node::Text`] with given text.
+    ///
+    /// If the only child is already a [`Node::Text`], its content is replaced in place.
     pub fn set_text_content<S: Into<String>>(&self, doc: &mut Document, text: S) {
+        if let [Node::Text(existing)] = self.children_mut(doc).as_mut_slice() {
+            *existing = text.into();
+            return;
+        }
         self.clear_children(doc);
         let node = Node::Text(text.into());
         self.push_child(doc, node).unwrap();
     }
+
+    /// Clears all its children and inserts a [`Node::CData`] with given text.
+    ///
+    /// CDATA content is not escaped when written.
+    pub fn set_cdata_content<S: Into<String>>(&self, doc: &mut Document, text: S) {
+        self.clear_children(doc);
+        let node = Node::CData(text.into());
+        self.push_child(doc, node).unwrap();
+    }
+
+    /// Clears all its children and inserts `nodes` as its children.
+    ///
+    /// # Errors
+    /// The children are left unchanged if any of the nodes is invalid.
+    ///    - [EditXMLError::HasAParent]: An element in `nodes` already has a parent.
+    ///    - [EditXMLError::ContainerCannotMove]: `nodes` contains the container element.
+    ///    - [EditXMLError::CyclicParent]: `nodes` contains this element or one of its ancestors.
+    pub fn set_content_nodes(&self, doc: &mut Document, nodes: Vec<Node>) -> Result<()> {
+        self.validate_new_children(doc, &nodes)?;
+        self.clear_children(doc);
+        self.children_mut(doc).reserve(nodes.len());
+        for node in nodes {
+            self.push_child(doc, node)?;
+        }
+        Ok(())
+    }
+
+    /// Parses `xml` as a fragment and replaces its children with the parsed nodes.
+    ///
+    /// The fragment doesn't need an XML declaration and may have multiple top-level nodes.
+    /// Whitespace in text is preserved. Namespace prefixes in the fragment
+    /// are resolved against this element and its ancestors once inserted.
+    ///
+    /// # Errors
+    /// The children are left unchanged if `xml` can't be parsed.
+    ///
+    /// ```
+    /// use edit_xml::Document;
+    ///
+    /// let mut doc = Document::parse_str(r#"<?xml version="1.0"?>
+    /// <root><old /></root>
+    /// "#).unwrap();
+    /// let root = doc.root_element().unwrap();
+    /// root.set_inner_xml(&mut doc, "<b>hi</b> text").unwrap();
+    /// assert_eq!(root.inner_xml(&doc).unwrap(), "<b>hi</b> text");
+    /// ```
+    pub fn set_inner_xml(&self, doc: &mut Document, xml: &str) -> Result<()> {
+        let nodes = Self::parse_fragment(doc, xml)?;
+        self.set_content_nodes(doc, nodes)
+    }
+
+    /// Parses `xml` as a fragment and appends the parsed nodes after its existing children.
+    ///
+    /// Returns the appended nodes. The fragment is parsed like [`Element::set_inner_xml`].
+    ///
+    /// # Errors
+    /// The children are left unchanged if `xml` can't be parsed.
+    ///
+    /// ```
+    /// use edit_xml::Document;
+    ///
+    /// let mut doc = Document::parse_str(r#"<?xml version="1.0"?>
+    /// <root><old /></root>
+    /// "#).unwrap();
+    /// let root = doc.root_element().unwrap();
+    /// let nodes = root.append_inner_xml(&mut doc, "<b>hi</b> text").unwrap();
+    /// assert_eq!(nodes.len(), 2);
+    /// assert_eq!(root.inner_xml(&doc).unwrap(), "<old/>\n<b>hi</b> text");
+    /// ```
+    pub fn append_inner_xml(&self, doc: &mut Document, xml: &str) -> Result<Vec<Node>> {
+        let nodes = Self::parse_fragment(doc, xml)?;
+        for node in &nodes {
+            self.push_child(doc, node.clone())?;
+        }
+        Ok(nodes)
+    }
+
+    /// Parses `xml` as a fragment, and moves its top-level nodes to `doc`.
+    fn parse_fragment(doc: &mut Document, xml: &str) -> Result<Vec<Node>> {
+        let opts = ReadOptions {
+            require_decl: false,
+            trim_text: false,
+            ..ReadOptions::default()
+        };
+        let mut fragment = Document::parse_str_with_opts(xml, opts)?;
+        let fragment_container = fragment.container();
+        let nodes = std::mem::take(fragment_container.children_mut(&mut fragment))
+            .into_iter()
+            .map(|node| match node {
+                Node::Element(elem) => Node::Element(elem.move_to_document(&mut fragment, doc)),
+                node => node,
+            })
+            .collect();
+        Ok(nodes)
+    }
 }
 
 /// Below are methods related to finding nodes in tree.
@@ -373,6 +1421,135 @@ impl Element {
         self.parent(doc).is_some()
     }
 
+    /// Returns `true` if `possible_ancestor` is the parent of this element, or the parent of its parent, and so on.
+    ///
+    /// An element is not a descendant of itself.
+    ///
+    /// ```
+    /// use edit_xml::Document;
+    ///
+    /// let doc = Document::parse_str(r#"<?xml version="1.0"?>
+    /// <root><a><b /></a><c /></root>
+    /// "#).unwrap();
+    /// let root = doc.root_element().unwrap();
+    /// let a = root.find(&doc, "a").unwrap();
+    /// let b = a.find(&doc, "b").unwrap();
+    /// assert!(b.is_descendant_of(&doc, root));
+    /// assert!(root.is_ancestor_of(&doc, b));
+    /// assert!(!b.is_descendant_of(&doc, root.find(&doc, "c").unwrap()));
+    /// assert!(!b.is_descendant_of(&doc, b));
+    /// ```
+    pub fn is_descendant_of(&self, doc: &Document, possible_ancestor: Element) -> bool {
+        let mut current = self.parent(doc);
+        while let Some(elem) = current {
+            if elem == possible_ancestor {
+                return true;
+            }
+            current = elem.parent(doc);
+        }
+        false
+    }
+
+    /// Returns `true` if `possible_descendant` is a descendant of this element. See [`Element::is_descendant_of`].
+    pub fn is_ancestor_of(&self, doc: &Document, possible_descendant: Element) -> bool {
+        possible_descendant.is_descendant_of(doc, *self)
+    }
+
+    /// Find the nearest element that `f` returns `true` for, starting from this element and walking up its ancestors.
+    ///
+    /// The container is never passed to `f`.
+    ///
+    /// ```
+    /// use edit_xml::Document;
+    ///
+    /// let doc = Document::parse_str(r#"<?xml version="1.0"?>
+    /// <root lang="en"><section><p lang="de"><b /></p></section></root>
+    /// "#).unwrap();
+    /// let root = doc.root_element().unwrap();
+    /// let section = root.find(&doc, "section").unwrap();
+    /// let b = section.find(&doc, "p").unwrap().find(&doc, "b").unwrap();
+    /// let has_lang = |doc: &Document, elem: edit_xml::Element| elem.has_attribute(doc, "lang");
+    /// assert_eq!(b.closest(&doc, has_lang).unwrap().attribute(&doc, "lang"), Some("de"));
+    /// assert_eq!(section.closest(&doc, has_lang), Some(root));
+    /// assert_eq!(b.closest_named(&doc, "section"), Some(section));
+    /// ```
+    pub fn closest<F>(&self, doc: &Document, f: F) -> Option<Element>
+    where
+        F: Fn(&Document, Element) -> bool,
+    {
+        let mut current = Some(*self);
+        while let Some(elem) = current.filter(|elem| !elem.is_container()) {
+            if f(doc, elem) {
+                return Some(elem);
+            }
+            current = elem.parent(doc);
+        }
+        None
+    }
+
+    /// Find the nearest element named `name`, starting from this element and walking up its ancestors.
+    ///
+    /// The namespace prefix isn't compared. See [`Element::closest`].
+    pub fn closest_named(&self, doc: &Document, name: &str) -> Option<Element> {
+        self.closest(doc, |doc, elem| elem.name(doc) == name)
+    }
+
+    /// Get the index of this element among its parent's children. (Including non element nodes)
+    ///
+    /// Returns `None` if this element has no parent.
+    ///
+    /// ```
+    /// use edit_xml::{Document, Element};
+    ///
+    /// let doc = Document::parse_str(r#"<?xml version="1.0"?>
+    /// <root>Text<a /><b /></root>
+    /// "#).unwrap();
+    /// let root = doc.root_element().unwrap();
+    /// let b = root.find(&doc, "b").unwrap();
+    /// assert_eq!(b.index_in_parent(&doc), Some(2));
+    /// assert_eq!(root.index_in_parent(&doc), Some(0));
+    /// ```
+    pub fn index_in_parent(&self, doc: &Document) -> Option<usize> {
+        self.parent(doc)?
+            .children(doc)
+            .iter()
+            .position(|node| node.as_element() == Some(*self))
+    }
+
+    /// Get the content of the sibling immediately before this element, if it is a [`Node::Text`].
+    ///
+    /// ```
+    /// use edit_xml::{Document, ReadOptions};
+    ///
+    /// let opts = ReadOptions { trim_text: false, ..ReadOptions::default() };
+    /// let doc = Document::parse_str_with_opts(r#"<?xml version="1.0"?>
+    /// <p>Hello <b>x</b> world<i /></p>"#, opts).unwrap();
+    /// let p = doc.root_element().unwrap();
+    /// let b = p.find(&doc, "b").unwrap();
+    /// assert_eq!(b.text_before(&doc), Some("Hello "));
+    /// assert_eq!(b.text_after(&doc), Some(" world"));
+    /// ```
+    pub fn text_before<'a>(&self, doc: &'a Document) -> Option<&'a str> {
+        let parent = self.parent(doc)?;
+        let index = self.index_in_parent(doc)?;
+        match parent.children(doc).get(index.checked_sub(1)?)? {
+            Node::Text(text) => Some(text),
+            _ => None,
+        }
+    }
+
+    /// Get the content of the sibling immediately after this element, if it is a [`Node::Text`].
+    ///
+    /// See [`Element::text_before`].
+    pub fn text_after<'a>(&self, doc: &'a Document) -> Option<&'a str> {
+        let parent = self.parent(doc)?;
+        let index = self.index_in_parent(doc)?;
+        match parent.children(doc).get(index + 1)? {
+            Node::Text(text) => Some(text),
+            _ => None,
+        }
+    }
+
     /// Get child [`Node`]s of this element.
     pub fn children<'a>(&self, doc: &'a Document) -> &'a Vec<Node> {
         &self.data(doc).children
@@ -403,6 +1580,64 @@ impl Element {
         nodes
     }
 
+    /// Number of descendant nodes, i.e. `self.children_recursive(doc).len()` without collecting them.
+    ///
+    /// ```
+    /// use edit_xml::Document;
+    /// let doc = Document::parse_str(r#"<?xml version="1.0"?>
+    /// <root><a>Text<b/></a><!--Comment--></root>
+    /// "#).unwrap();
+    /// let root = doc.root_element().unwrap();
+    /// assert_eq!(root.descendant_count(&doc), 4);
+    /// assert_eq!(root.descendant_element_count(&doc), 2);
+    /// ```
+    pub fn descendant_count(&self, doc: &Document) -> usize {
+        self.count_descendants(doc, true)
+    }
+
+    /// Number of descendant elements, i.e. `self.child_elements_recursive(doc).len()` without collecting them.
+    pub fn descendant_element_count(&self, doc: &Document) -> usize {
+        self.count_descendants(doc, false)
+    }
+
+    /// Walks the descendants through their parent links, without recursion or allocation.
+    ///
+    /// Going back up to a parent finds the position of the element in its parent's children,
+    /// which is a linear search through its siblings.
+    fn count_descendants(&self, doc: &Document, count_other_nodes: bool) -> usize {
+        let mut count = 0;
+        let mut elem = *self;
+        // Index of the next child of `elem` to visit
+        let mut index = 0;
+        loop {
+            match elem.children(doc).get(index) {
+                Some(Node::Element(child)) => {
+                    count += 1;
+                    index += 1;
+                    if child.has_children(doc) {
+                        elem = *child;
+                        index = 0;
+                    }
+                }
+                Some(_) => {
+                    if count_other_nodes {
+                        count += 1;
+                    }
+                    index += 1;
+                }
+                None if elem == *self => return count,
+                None => {
+                    let parent = elem.parent(doc).expect("descendants have a parent");
+                    index = elem
+                        .index_in_parent(doc)
+                        .expect("element is a child of its parent")
+                        + 1;
+                    elem = parent;
+                }
+            }
+        }
+    }
+
     /// `!self.children(doc).is_empty()`
     #[inline]
     pub fn has_children(&self, doc: &Document) -> bool {
@@ -431,10 +1666,88 @@ impl Element {
             .iter()
             .any(|n| !n.is_text() || !n.text_content(doc).trim().is_empty())
     }
+    /// Iterate over child [`Node`]s of this element.
+    ///
+    /// Equivalent to `self.children(doc).iter()`.
+    ///
+    /// ```
+    /// use edit_xml::Document;
+    /// let doc = Document::parse_str(r#"<?xml version="1.0"?>
+    /// <root>Text<a /><!-- Comment --><b /></root>
+    /// "#).unwrap();
+    /// let root = doc.root_element().unwrap();
+    /// assert_eq!(root.iter_children(&doc).count(), 4);
+    ///
+    /// let names: Vec<&str> = root
+    ///     .iter_child_elements(&doc)
+    ///     .map(|elem| elem.name(&doc))
+    ///     .collect();
+    /// assert_eq!(names, vec!["a", "b"]);
+    /// ```
+    pub fn iter_children<'a>(&self, doc: &'a Document) -> std::slice::Iter<'a, Node> {
+        self.children(doc).iter()
+    }
+
+    /// Iterate over child [`Element`]s of this element.
+    ///
+    /// Unlike [`Element::child_elements()`], this doesn't allocate a `Vec`.
+    pub fn iter_child_elements<'a>(&self, doc: &'a Document) -> impl Iterator<Item = Element> + 'a {
+        self.iter_children(doc).filter_map(Node::as_element)
+    }
+
+    /// Iterate over child [`Node`]s of this element that are of `kind`.
+    ///
+    /// ```
+    /// use edit_xml::{Document, Node, NodeKind};
+    ///
+    /// let doc = Document::parse_str(r#"<?xml version="1.0"?>
+    /// <root><!-- First -->Text<child /><!-- Second --></root>
+    /// "#).unwrap();
+    /// let root = doc.root_element().unwrap();
+    /// let comments: Vec<&Node> = root.children_of_kind(&doc, NodeKind::Comment).collect();
+    /// assert_eq!(comments, vec![
+    ///     &Node::Comment(" First ".to_string()),
+    ///     &Node::Comment(" Second ".to_string()),
+    /// ]);
+    /// assert_eq!(root.children_of_kind(&doc, NodeKind::Element).count(), 1);
+    /// ```
+    pub fn children_of_kind<'a>(
+        &self,
+        doc: &'a Document,
+        kind: NodeKind,
+    ) -> impl Iterator<Item = &'a Node> + 'a {
+        self.iter_children(doc)
+            .filter(move |node| node.kind() == kind)
+    }
+
+    /// Get the first child [`Node`] of this element.
+    pub fn first_child<'a>(&self, doc: &'a Document) -> Option<&'a Node> {
+        self.children(doc).first()
+    }
+
+    /// Get the last child [`Node`] of this element.
+    pub fn last_child<'a>(&self, doc: &'a Document) -> Option<&'a Node> {
+        self.children(doc).last()
+    }
+
+    /// Get the first child [`Element`] of this element, skipping other nodes.
+    ///
+    /// Returns `None` if the element has no child elements.
+    pub fn first_child_element(&self, doc: &Document) -> Option<Element> {
+        self.iter_child_elements(doc).next()
+    }
+
+    /// Get the last child [`Element`] of this element, skipping other nodes.
+    ///
+    /// Returns `None` if the element has no child elements.
+    pub fn last_child_element(&self, doc: &Document) -> Option<Element> {
+        self.iter_children(doc).rev().find_map(Node::as_element)
+    }
+
     /// Get only child [`Element`]s of this element.
     ///
     /// This calls `.children().iter().filter_map().collect()`.
-    /// Use [`Element::children()`] if performance is important.
+    /// Use [`Element::children()`] or [`Element::iter_child_elements()`] if performance is important.
     pub fn child_elements(&self, doc: &Document) -> Vec<Element> {
         self.children(doc)
             .iter()
@@ -488,6 +1801,23 @@ impl Element {
             .find(|e| e.name(doc) == name)
     }
 
+    /// Find first direct child element with name `name`, ignoring ASCII case.
+    ///
+    /// The namespace prefix isn't compared, like [`Element::find`].
+    ///
+    /// ```
+    /// use edit_xml::{Document, ReadOptions};
+    /// let doc = Document::parse_str_with_opts("<HTML><Body /></HTML>", ReadOptions::relaxed()).unwrap();
+    /// let html = doc.root_element().unwrap();
+    /// let body = html.find_ignore_case(&doc, "body").unwrap();
+    /// assert_eq!(body.name(&doc), "Body");
+    /// assert!(html.find(&doc, "body").is_none());
+    /// ```
+    pub fn find_ignore_case(&self, doc: &Document, name: &str) -> Option<Element> {
+        self.iter_child_elements(doc)
+            .find(|e| e.name(doc).eq_ignore_ascii_case(name))
+    }
+
     /// Find all direct child element with name `name`.
     /// # Example
     /// ```
@@ -516,6 +1846,31 @@ impl Element {
             .filter(|e| e.name(doc) == name)
             .collect()
     }
+
+    /// Iterate over child elements with name `name`.
+    ///
+    /// Lazy version of [`Element::find_all`], which doesn't allocate a `Vec`.
+    ///
+    /// ```
+    /// use edit_xml::Document;
+    ///
+    /// let doc = Document::parse_str(r#"<?xml version="1.0"?>
+    /// <root><item>1</item><other /><item>2</item></root>
+    /// "#).unwrap();
+    /// let root = doc.root_element().unwrap();
+    /// let items: Vec<_> = root.children_named(&doc, "item").collect();
+    /// assert_eq!(items, root.find_all(&doc, "item"));
+    /// assert_eq!(root.children_named(&doc, "other").count(), 1);
+    /// assert!(root.children_named(&doc, "missing").next().is_none());
+    /// ```
+    pub fn children_named<'a>(
+        &self,
+        doc: &'a Document,
+        name: &'a str,
+    ) -> impl Iterator<Item = Element> + 'a {
+        self.iter_child_elements(doc)
+            .filter(move |e| e.name(doc) == name)
+    }
 }
 
 /// Below are functions that modify its tree-structure.
@@ -533,6 +1888,7 @@ impl Element {
     ///      call `element.detach()` to make it parentless first.
     ///      This is to make it explicit that you are changing an element's parent, not adding another.
     ///    - [EditXMLError::ContainerCannotMove]: The container element's parent must always be None.
+    ///    - [EditXMLError::CyclicParent]: The element is this element or one of its ancestors.
     pub fn push_child(&self, doc: &mut Document, node: impl Into<Node>) -> Result<()> {
         let node = node.into();
         if let Node::Element(new_child) = node {
@@ -542,17 +1898,49 @@ impl Element {
         Ok(())
     }
     pub(crate) fn push_child_element(&self, doc: &mut Document, new_child: Element) -> Result<()> {
+        self.validate_new_child(doc, new_child)?;
+        new_child.mut_data(doc).parent = Some(*self);
+        self.mut_data(doc).children.push(Node::Element(new_child));
+        Ok(())
+    }
+    /// Checks that `new_child` can become a child of this element.
+    fn validate_new_child(&self, doc: &Document, new_child: Element) -> Result<()> {
         if new_child.is_container() {
             return Err(EditXMLError::ContainerCannotMove);
         }
-        let child_data = new_child.mut_data(doc);
-        if child_data.parent.is_some() {
+        if new_child.has_parent(doc) {
             return Err(EditXMLError::HasAParent);
         }
-        child_data.parent = Some(*self);
-        self.mut_data(doc).children.push(Node::Element(new_child));
+        // `new_child` has no parent, so it can only be an ancestor if it is the root of this subtree
+        if new_child == *self || self.is_descendant_of(doc, new_child) {
+            return Err(EditXMLError::CyclicParent);
+        }
         Ok(())
     }
+    /// Pushes `nodes` after its existing children, in order. Equivalent to `vec.extend(nodes)`.
+    ///
+    /// # Errors
+    /// The children are left unchanged if any of the nodes is invalid. See [`Element::insert_children`].
+    ///
+    /// ```
+    /// use edit_xml::{Document, Element, Node};
+    ///
+    /// let mut doc = Document::new();
+    /// let root = Element::build("p").push_to_root_node(&mut doc);
+    /// let bold = Element::build("b").add_text("bold").finish(&mut doc);
+    /// root.push_children(&mut doc, vec![
+    ///     Node::Text("Some ".to_string()),
+    ///     Node::Element(bold),
+    ///     Node::Text(" text".to_string()),
+    /// ]).unwrap();
+    /// assert_eq!(root.children(&doc).len(), 3);
+    /// assert_eq!(bold.parent(&doc), Some(root));
+    /// assert_eq!(root.text_content(&doc), "Some bold text");
+    /// ```
+    pub fn push_children(&self, doc: &mut Document, nodes: Vec<Node>) -> Result<()> {
+        let len = self.children(doc).len();
+        self.insert_children(doc, len, nodes)
+    }
     /// Creates a new child element with `name` and pushes it to this element.
     ///
     /// # Example
@@ -577,6 +1965,25 @@ impl Element {
         let elem = elem.finish(doc);
         self.push_child_element(doc, elem).unwrap();
     }
+    /// Finishes `builder` and pushes the new element to this element, returning it.
+    ///
+    /// # Example
+    /// ```
+    /// use edit_xml::{Document, Element};
+    /// let mut doc = Document::new();
+    /// let root = Element::build("root").push_to_root_node(&mut doc);
+    /// let item = root
+    ///     .add_built_child(&mut doc, Element::build("item").attribute("id", "1").add_text("Hello"))
+    ///     .unwrap();
+    /// assert_eq!(item.parent(&doc), Some(root));
+    /// assert_eq!(item.attribute(&doc, "id"), Some("1"));
+    /// assert_eq!(root.find(&doc, "item"), Some(item));
+    /// ```
+    pub fn add_built_child(&self, doc: &mut Document, builder: ElementBuilder) -> Result<Element> {
+        let elem = builder.finish(doc);
+        self.push_child_element(doc, elem)?;
+        Ok(elem)
+    }
     /// Equivalent to `vec.insert()`.
     ///
     /// # Panics
@@ -584,16 +1991,50 @@ impl Element {
     /// Panics if `index > self.children().len()`
     pub fn insert_child(&self, doc: &mut Document, index: usize, node: Node) -> Result<()> {
         if let Node::Element(elem) = node {
-            if elem.is_container() {
-                return Err(EditXMLError::ContainerCannotMove);
+            self.validate_new_child(doc, elem)?;
+            elem.mut_data(doc).parent = Some(*self);
+        }
+        self.mut_data(doc).children.insert(index, node);
+        Ok(())
+    }
+
+    /// Inserts `nodes` at `index`, in order. Equivalent to `vec.splice(index..index, nodes)`.
+    ///
+    /// # Errors
+    /// The children are left unchanged if any of the nodes is invalid.
+    ///    - [EditXMLError::HasAParent]: An element in `nodes` already has a parent, or is in `nodes` more than once.
+    ///    - [EditXMLError::ContainerCannotMove]: `nodes` contains the container element.
+    ///    - [EditXMLError::CyclicParent]: `nodes` contains this element or one of its ancestors.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index > self.children().len()`
+    pub fn insert_children(
+        &self,
+        doc: &mut Document,
+        index: usize,
+        nodes: Vec<Node>,
+    ) -> Result<()> {
+        self.validate_new_children(doc, &nodes)?;
+        for node in &nodes {
+            if let Node::Element(elem) = node {
+                elem.mut_data(doc).parent = Some(*self);
             }
-            let data = elem.mut_data(doc);
-            if data.parent.is_some() {
-                return Err(EditXMLError::HasAParent);
+        }
+        self.children_mut(doc).splice(index..index, nodes);
+        Ok(())
+    }
+
+    /// Checks that `nodes` can be added as children, before any of them is added.
+    fn validate_new_children(&self, doc: &Document, nodes: &[Node]) -> Result<()> {
+        for (i, node) in nodes.iter().enumerate() {
+            if let Node::Element(elem) = node {
+                self.validate_new_child(doc, *elem)?;
+                if nodes[..i].contains(node) {
+                    return Err(EditXMLError::HasAParent);
+                }
             }
-            data.parent = Some(*self);
         }
-        self.mut_data(doc).children.insert(index, node);
         Ok(())
     }
 
@@ -630,27 +2071,246 @@ impl Element {
         removed
     }
 
+    /// Serializes this element and its descendants, without the XML declaration.
+    ///
+    /// ```
+    /// use edit_xml::Document;
+    ///
+    /// let doc = Document::parse_str(r#"<?xml version="1.0"?>
+    /// <root><item>Text</item></root>
+    /// "#).unwrap();
+    /// let item = doc.root_element().unwrap().find(&doc, "item").unwrap();
+    /// assert_eq!(item.outer_xml(&doc).unwrap(), "<item>Text</item>");
+    /// ```
+    pub fn outer_xml(&self, doc: &Document) -> Result<String> {
+        self.outer_xml_with_opts(doc, WriteOptions::default())
+    }
+    /// See [`Element::outer_xml`]. [`WriteOptions::write_decl`] is ignored.
+    pub fn outer_xml_with_opts(&self, doc: &Document, opts: WriteOptions) -> Result<String> {
+        if self.is_container() {
+            return self.inner_xml_with_opts(doc, opts);
+        }
+        doc.write_nodes_str(&[Node::Element(*self)], opts)
+    }
+
+    /// Writes this element and its descendants to `writer`, then flushes it.
+    ///
+    /// Like [`Element::outer_xml_with_opts`], but without building a `String`.
+    /// [`WriteOptions::write_decl`] and [`WriteOptions::preamble`] are ignored.
+    ///
+    /// ```
+    /// use edit_xml::{Document, WriteOptions};
+    ///
+    /// let doc = Document::parse_str(r#"<?xml version="1.0"?>
+    /// <root><item>Text</item></root>
+    /// "#).unwrap();
+    /// let item = doc.root_element().unwrap().find(&doc, "item").unwrap();
+    /// let mut out = Vec::new();
+    /// item.write_to(&doc, &mut out, WriteOptions::default()).unwrap();
+    /// assert_eq!(out, b"<item>Text</item>");
+    /// ```
+    pub fn write_to(
+        &self,
+        doc: &Document,
+        writer: &mut impl std::io::Write,
+        opts: WriteOptions,
+    ) -> Result<()> {
+        if self.is_container() {
+            doc.write_nodes_to(writer, self.children(doc), &opts)?;
+        } else {
+            doc.write_nodes_to(writer, &[Node::Element(*self)], &opts)?;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Serializes the children of this element, without the element itself.
+    ///
+    /// ```
+    /// use edit_xml::Document;
+    ///
+    /// let doc = Document::parse_str(r#"<?xml version="1.0"?>
+    /// <root>Text<b>Bold</b></root>
+    /// "#).unwrap();
+    /// let root = doc.root_element().unwrap();
+    /// assert_eq!(root.inner_xml(&doc).unwrap(), "Text<b>Bold</b>");
+    /// ```
+    pub fn inner_xml(&self, doc: &Document) -> Result<String> {
+        self.inner_xml_with_opts(doc, WriteOptions::default())
+    }
+    /// See [`Element::inner_xml`]. [`WriteOptions::write_decl`] is ignored.
+    pub fn inner_xml_with_opts(&self, doc: &Document, opts: WriteOptions) -> Result<String> {
+        doc.write_nodes_str(self.children(doc), opts)
+    }
+
+    /// Merges adjacent [`Node::Text`] children into one and removes empty text nodes,
+    /// recursively for all descendant elements. Similar to the DOM `normalize()`.
+    ///
+    /// ```
+    /// use edit_xml::{Document, Element, Node};
+    ///
+    /// let mut doc = Document::new();
+    /// let elem = Element::new(&mut doc, "name");
+    /// elem.push_child(&mut doc, Node::Text("Hello".to_string())).unwrap();
+    /// elem.push_child(&mut doc, Node::Text(String::new())).unwrap();
+    /// elem.push_child(&mut doc, Node::Text(", World".to_string())).unwrap();
+    /// elem.normalize(&mut doc);
+    /// assert_eq!(elem.children(&doc), &vec![Node::Text("Hello, World".to_string())]);
+    /// ```
+    pub fn normalize(&self, doc: &mut Document) {
+        let children = std::mem::take(self.children_mut(doc));
+        let mut normalized: Vec<Node> = Vec::with_capacity(children.len());
+        for child in children {
+            match child {
+                Node::Text(text) if text.is_empty() => {}
+                Node::Text(text) => {
+                    if let Some(Node::Text(last)) = normalized.last_mut() {
+                        last.push_str(&text);
+                    } else {
+                        normalized.push(Node::Text(text));
+                    }
+                }
+                Node::Element(elem) => {
+                    elem.normalize(doc);
+                    normalized.push(child);
+                }
+                _ => normalized.push(child),
+            }
+        }
+        *self.children_mut(doc) = normalized;
+    }
+
+    /// Recursively removes [`Node::Text`] children that are empty or only have whitespace.
+    ///
+    /// Text is kept in elements with mixed content, i.e. elements with at least one text child
+    /// that isn't whitespace only, and in elements where `xml:space="preserve"` applies.
+    /// `xml:space` is inherited from the ancestors of this element.
+    ///
+    /// ```
+    /// use edit_xml::{Document, ReadOptions};
+    ///
+    /// let opts = ReadOptions { trim_text: false, ..ReadOptions::default() };
+    /// let mut doc = Document::parse_str_with_opts(r#"<?xml version="1.0"?>
+    /// <root>
+    ///     <a> </a>
+    ///     <p>Some <b>bold</b> text</p>
+    /// </root>"#, opts).unwrap();
+    /// let root = doc.root_element().unwrap();
+    /// root.trim_whitespace_nodes(&mut doc);
+    /// assert_eq!(root.children(&doc).len(), 2);
+    /// let p = root.find(&doc, "p").unwrap();
+    /// assert_eq!(p.text_content(&doc), "Some bold text");
+    /// ```
+    pub fn trim_whitespace_nodes(&self, doc: &mut Document) {
+        let mut preserve = false;
+        let mut current = Some(*self);
+        while let Some(elem) = current {
+            if let Some(space) = elem.attribute(doc, "xml:space") {
+                preserve = space == "preserve";
+                break;
+            }
+            current = elem.parent(doc);
+        }
+        self.trim_whitespace_nodes_inner(doc, preserve);
+    }
+
+    fn trim_whitespace_nodes_inner(&self, doc: &mut Document, preserve: bool) {
+        let is_whitespace = |node: &Node| match node {
+            Node::Text(text) => text
+                .bytes()
+                .all(|b| matches!(b, b' ' | b'\t' | b'\r' | b'\n')),
+            _ => false,
+        };
+        let preserve = match self.attribute(doc, "xml:space") {
+            Some("preserve") => true,
+            Some("default") => false,
+            _ => preserve,
+        };
+        let mixed = self
+            .children(doc)
+            .iter()
+            .any(|node| node.is_text() && !is_whitespace(node));
+        if !preserve && !mixed {
+            self.children_mut(doc).retain(|node| !is_whitespace(node));
+        }
+        for child in self.child_elements(doc) {
+            child.trim_whitespace_nodes_inner(doc, preserve);
+        }
+    }
+
+    /// Replaces this element with `node` in its parent, and returns this element as a [`Node`].
+    ///
+    /// This element is detached afterwards.
+    ///
+    /// # Errors
+    ///    - [EditXMLError::ContainerCannotMove]: This element or `node` is the container element.
+    ///    - [EditXMLError::HasAParent]: `node` is an element that already has a parent.
+    ///    - [EditXMLError::NoParent]: This element has no parent.
+    ///
+    /// ```
+    /// use edit_xml::{Document, Node};
+    ///
+    /// let mut doc = Document::parse_str(r#"<?xml version="1.0"?>
+    /// <root>Hello<name /></root>
+    /// "#).unwrap();
+    /// let root = doc.root_element().unwrap();
+    /// let name = root.find(&doc, "name").unwrap();
+    /// name.replace_with(&mut doc, Node::Text(", World".to_string())).unwrap();
+    /// assert_eq!(root.text_content(&doc), "Hello, World");
+    /// assert!(!name.has_parent(&doc));
+    /// ```
+    pub fn replace_with(&self, doc: &mut Document, node: Node) -> Result<Node> {
+        if self.is_container() {
+            return Err(EditXMLError::ContainerCannotMove);
+        }
+        let (Some(parent), Some(index)) = (self.parent(doc), self.index_in_parent(doc)) else {
+            return Err(EditXMLError::NoParent);
+        };
+        parent.insert_child(doc, index, node)?;
+        Ok(parent.remove_child(doc, index + 1))
+    }
+
     /// Removes itself from its parent. Note that you can't attach this element to other documents.
     pub fn detach(&self, doc: &mut Document) -> Result<()> {
         if self.is_container() {
             return Err(EditXMLError::ContainerCannotMove);
         }
-        let data = self.mut_data(doc);
-        if let Some(parent) = data.parent {
-            let pos = parent
-                .children(doc)
-                .iter()
-                .position(|n| n.as_element() == Some(*self))
-                .unwrap();
+        if let (Some(parent), Some(pos)) = (self.parent(doc), self.index_in_parent(doc)) {
             parent.remove_child(doc, pos);
         }
         Ok(())
     }
+
+    /// Same as [`Element::detach`], but returns this element, so it can be moved elsewhere.
+    ///
+    /// # Errors
+    ///    - [EditXMLError::ContainerCannotMove]: This element is the container.
+    ///
+    /// ```
+    /// use edit_xml::Document;
+    /// let mut doc = Document::parse_str(r#"<?xml version="1.0"?>
+    /// <root><a><item /></a><b /></root>
+    /// "#).unwrap();
+    /// let root = doc.root_element().unwrap();
+    /// let item = root.find(&doc, "a").unwrap().find(&doc, "item").unwrap();
+    /// let b = root.find(&doc, "b").unwrap();
+    ///
+    /// let item = item.take_from_parent(&mut doc).unwrap();
+    /// b.push_child(&mut doc, item).unwrap();
+    /// assert_eq!(item.parent(&doc), Some(b));
+    /// ```
+    pub fn take_from_parent(&self, doc: &mut Document) -> Result<Element> {
+        self.detach(doc)?;
+        Ok(*self)
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{Document, Element, Node};
+    use super::{
+        Document, EditXMLError, Element, Node, ReadOptions, TextExtractOptions, WriteOptions,
+    };
+    use std::borrow::Cow;
 
     #[test]
     fn test_children() {
@@ -687,6 +2347,85 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_first_last_child() {
+        let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+        <outer>
+            inside outer
+            <middle>
+                <inner>
+                    inside
+                </inner>
+                after inside
+            </middle>
+            <after>
+                inside after
+            </after>
+        </outer>
+        "#;
+        let doc = Document::parse_str(xml).unwrap();
+        let outer = doc.root_element().unwrap();
+        let middle = outer.first_child_element(&doc).unwrap();
+        let after = outer.last_child_element(&doc).unwrap();
+        assert_eq!(middle.name(&doc), "middle");
+        assert_eq!(after.name(&doc), "after");
+        assert!(outer.first_child(&doc).unwrap().is_text());
+        assert_eq!(outer.last_child(&doc).unwrap().as_element(), Some(after));
+
+        let inner = middle.first_child_element(&doc).unwrap();
+        assert_eq!(middle.last_child_element(&doc), Some(inner));
+        assert!(middle.last_child(&doc).unwrap().is_text());
+
+        assert_eq!(inner.first_child_element(&doc), None);
+        assert_eq!(inner.last_child_element(&doc), None);
+        let doc = Document::new();
+        assert!(doc.container().first_child(&doc).is_none());
+        assert!(doc.container().last_child(&doc).is_none());
+    }
+
+    #[test]
+    fn test_rename_descendants() {
+        let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+        <outer>
+            <middle>
+                <inner>inside</inner>
+                <p:inner>prefixed</p:inner>
+            </middle>
+            <inner>
+                <middle />
+            </inner>
+        </outer>
+        "#;
+        let mut doc = Document::parse_str(xml).unwrap();
+        let outer = doc.root_element().unwrap();
+        assert_eq!(outer.rename_descendants(&mut doc, "inner", "renamed"), 3);
+        assert_eq!(outer.rename_descendants(&mut doc, "inner", "renamed"), 0);
+        assert_eq!(outer.rename_descendants(&mut doc, "outer", "renamed"), 0);
+        let names: Vec<&str> = outer
+            .child_elements_recursive(&doc)
+            .into_iter()
+            .map(|e| e.full_name(&doc))
+            .collect();
+        assert_eq!(
+            names,
+            vec!["middle", "renamed", "p:renamed", "renamed", "middle"]
+        );
+
+        assert_eq!(outer.set_prefix_all_descendants(&mut doc, "p", "q"), 1);
+        assert_eq!(outer.set_prefix_all_descendants(&mut doc, "", "r"), 4);
+        assert_eq!(outer.set_prefix_all_descendants(&mut doc, "q", ""), 1);
+        let names: Vec<&str> = outer
+            .child_elements_recursive(&doc)
+            .into_iter()
+            .map(|e| e.full_name(&doc))
+            .collect();
+        assert_eq!(
+            names,
+            vec!["r:middle", "r:renamed", "renamed", "r:renamed", "r:middle"]
+        );
+        assert_eq!(outer.full_name(&doc), "outer");
+    }
+
     #[test]
     fn test_namespace() {
         // OG Test had         <root xmlns="ns", xmlns:p="pns">
@@ -722,6 +2461,78 @@ mod tests {
         assert_eq!(container.namespace(&doc).unwrap(), "ns");
     }
 
+    #[test]
+    fn test_set_namespace() {
+        let mut doc = Document::new();
+        let root = Element::build("root").push_to_root_node(&mut doc);
+        let child = Element::build("old:child").push_to(&mut doc, root);
+
+        root.set_namespace(&mut doc, "p", "urn:p");
+        child.set_namespace(&mut doc, "", "urn:default");
+        assert_eq!(root.full_name(&doc), "p:root");
+        assert_eq!(root.namespace(&doc), Some("urn:p"));
+        assert_eq!(child.full_name(&doc), "child");
+        assert_eq!(child.namespace(&doc), Some("urn:default"));
+
+        let xml = doc.write_str().unwrap();
+        assert!(xml.contains(r#"<p:root xmlns:p="urn:p">"#), "{xml}");
+        assert!(xml.contains(r#"<child xmlns="urn:default"/>"#), "{xml}");
+    }
+
+    #[test]
+    fn test_set_attribute_ns() {
+        let mut doc = Document::parse_str(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+            <root xmlns:a="urn:a" xmlns:p="urn:other"><child/></root>
+            "#,
+        )
+        .unwrap();
+        let root = doc.root_element().unwrap();
+        let child = root.find(&doc, "child").unwrap();
+
+        // Declared on the element
+        assert_eq!(
+            child.set_attribute_ns(&mut doc, "q", "attr", "urn:q", "1"),
+            "q"
+        );
+        assert_eq!(child.attribute(&doc, "q:attr"), Some("1"));
+        assert_eq!(
+            child.namespace_decls(&doc).get("q").map(String::as_str),
+            Some("urn:q")
+        );
+        // Existing prefix of an ancestor is reused
+        assert_eq!(
+            child.set_attribute_ns(&mut doc, "b", "attr", "urn:a", "2"),
+            "a"
+        );
+        assert_eq!(child.attribute(&doc, "a:attr"), Some("2"));
+        assert!(!child.namespace_decls(&doc).contains_key("b"));
+        // `p` is bound to another namespace, so it isn't redeclared
+        assert_eq!(
+            child.set_attribute_ns(&mut doc, "p", "attr", "urn:p", "3"),
+            "p1"
+        );
+        assert_eq!(child.attribute(&doc, "p1:attr"), Some("3"));
+        assert_eq!(child.namespace_for_prefix(&doc, "p"), Some("urn:other"));
+        assert_eq!(child.namespace_for_prefix(&doc, "p1"), Some("urn:p"));
+        // Attributes need a prefix to be in a namespace
+        assert_eq!(
+            child.set_attribute_ns(&mut doc, "", "attr", "urn:n", "4"),
+            "ns"
+        );
+        assert_eq!(child.attribute(&doc, "ns:attr"), Some("4"));
+
+        let reparsed = Document::parse_str(&doc.write_str().unwrap()).unwrap();
+        let child = reparsed
+            .root_element()
+            .unwrap()
+            .find(&reparsed, "child")
+            .unwrap();
+        assert_eq!(child.namespace_for_prefix(&reparsed, "q"), Some("urn:q"));
+        assert_eq!(child.namespace_for_prefix(&reparsed, "p1"), Some("urn:p"));
+        assert_eq!(child.attributes(&reparsed).len(), 4);
+    }
+
     #[test]
     fn test_find_text_content() {
         let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
@@ -750,6 +2561,1069 @@ mod tests {
         assert_eq!(doc.root_element().unwrap().text_content(&doc), "TextText2")
     }
 
+    #[test]
+    fn test_text_borrowed() {
+        let doc = Document::parse_str(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+            <root>
+                <text>Text</text>
+                <cdata><![CDATA[<data>]]></cdata>
+                <mixed>a<b>b</b>c</mixed>
+                <comment><!-- Comment --></comment>
+                <empty></empty>
+                <self-closing />
+            </root>
+            "#,
+        )
+        .unwrap();
+        let root = doc.root_element().unwrap();
+        let text = |name| root.find(&doc, name).unwrap().text_borrowed(&doc);
+        assert!(matches!(text("text"), Some(Cow::Borrowed("Text"))));
+        assert!(matches!(text("cdata"), Some(Cow::Borrowed("<data>"))));
+        let mixed = text("mixed");
+        assert!(matches!(mixed, Some(Cow::Owned(_))));
+        assert_eq!(mixed.unwrap(), "abc");
+        assert_eq!(text("comment"), None);
+        assert_eq!(text("empty"), None);
+        assert_eq!(text("self-closing"), None);
+    }
+
+    #[test]
+    fn test_text_content_with() {
+        let doc = Document::parse_str(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+            <root>a<!--comment--><![CDATA[b]]><?c?><child>d<![CDATA[e]]><?f?></child></root>
+            "#,
+        )
+        .unwrap();
+        let root = doc.root_element().unwrap();
+        assert_eq!(root.text_content(&doc), "abcdef");
+        let text = |include_cdata, include_pi, recursive| {
+            root.text_content_with(
+                &doc,
+                TextExtractOptions {
+                    include_cdata,
+                    include_pi,
+                    recursive,
+                },
+            )
+        };
+        assert_eq!(root.text_content_with(&doc, Default::default()), "abde");
+        assert_eq!(text(true, true, true), "abcdef");
+        assert_eq!(text(false, false, true), "ad");
+        assert_eq!(text(false, true, true), "acdf");
+        assert_eq!(text(true, false, false), "ab");
+        assert_eq!(text(false, false, false), "a");
+    }
+
+    #[test]
+    fn test_text_content_joined() {
+        let doc = Document::parse_str(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+            <l><i>a</i><i></i><i>b<![CDATA[c]]><?pi?></i><!--comment--><i><b>d</b></i></l>
+            "#,
+        )
+        .unwrap();
+        let root = doc.root_element().unwrap();
+        assert_eq!(root.text_content_joined(&doc, ","), "a,b,c,d");
+        assert_eq!(root.text_content_joined(&doc, ""), "abcd");
+        let empty = root.child_elements(&doc)[1];
+        assert_eq!(empty.text_content_joined(&doc, ","), "");
+    }
+
+    #[test]
+    fn test_descendant_count() {
+        let mut doc = Document::parse_str(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+            <root>
+                <a><b><c>Text</c><c/></b><!--Comment--></a>
+                <d><![CDATA[Data]]><e><f><g/></f></e></d>
+                Tail
+            </root>
+            "#,
+        )
+        .unwrap();
+        let root = doc.root_element().unwrap();
+        for elem in std::iter::once(root).chain(root.child_elements_recursive(&doc)) {
+            assert_eq!(
+                elem.descendant_count(&doc),
+                elem.children_recursive(&doc).len()
+            );
+            assert_eq!(
+                elem.descendant_element_count(&doc),
+                elem.child_elements_recursive(&doc).len()
+            );
+        }
+        assert_eq!(root.descendant_count(&doc), 12);
+        assert_eq!(root.descendant_element_count(&doc), 8);
+        assert_eq!(doc.container().descendant_element_count(&doc), 9);
+
+        let d = root.find(&doc, "d").unwrap();
+        d.clear_children(&mut doc);
+        assert_eq!(d.descendant_count(&doc), 0);
+        assert_eq!(root.descendant_count(&doc), 8);
+        assert_eq!(root.descendant_element_count(&doc), 5);
+    }
+
+    #[test]
+    fn test_descendant_count_deep_chain() {
+        const DEPTH: usize = 100_000;
+        let mut doc = Document::new();
+        // Built from the leaf up, so each element has no parent when it gets its child
+        let mut elem = Element::new(&mut doc, "leaf");
+        elem.push_child(&mut doc, Node::Text("Text".to_owned()))
+            .unwrap();
+        for _ in 1..DEPTH {
+            let parent = Element::new(&mut doc, "e");
+            parent.push_child(&mut doc, elem).unwrap();
+            elem = parent;
+        }
+        assert_eq!(elem.descendant_count(&doc), DEPTH);
+        assert_eq!(elem.descendant_element_count(&doc), DEPTH - 1);
+    }
+
+    #[test]
+    fn test_replace_in_text() {
+        let mut doc = Document::parse_str(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+            <root a="aa"><p>aaa</p><!--aa--><q><![CDATA[aa]]><r>a b a</r></q></root>
+            "#,
+        )
+        .unwrap();
+        let root = doc.root_element().unwrap();
+        let p = root.child_elements(&doc)[0];
+        assert_eq!(p.replace_in_text(&mut doc, "aa", "x"), 1);
+        assert_eq!(p.text_content(&doc), "xa");
+        assert_eq!(root.replace_in_text(&mut doc, "a", "yy"), 3);
+        assert_eq!(root.replace_in_text(&mut doc, "", "z"), 0);
+        assert_eq!(root.replace_in_text(&mut doc, "missing", "z"), 0);
+        assert_eq!(
+            root.outer_xml_with_opts(&doc, WriteOptions::builder().pretty(false).build())
+                .unwrap(),
+            r#"<root a="aa"><p>xyy</p><!--aa--><q><![CDATA[aa]]><r>yy b yy</r></q></root>"#
+        );
+    }
+
+    #[cfg(feature = "regex")]
+    #[test]
+    fn test_replace_in_text_regex() {
+        let mut doc = Document::parse_str(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+            <root><d>2024-01-31</d><d>1999-12-01 and 2000-02-03</d></root>
+            "#,
+        )
+        .unwrap();
+        let root = doc.root_element().unwrap();
+        let regex = regex::Regex::new(r"(\d{4})-(\d{2})-(\d{2})").unwrap();
+        assert_eq!(root.replace_in_text_regex(&mut doc, &regex, "$3/$2/$1"), 3);
+        assert_eq!(
+            root.text_content_joined(&doc, ","),
+            "31/01/2024,01/12/1999 and 03/02/2000"
+        );
+    }
+
+    #[test]
+    fn test_write_text_content() {
+        let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+        <core>
+            <p>Text</p>
+            <b>Text2</b>
+            <i><u>Text3</u></i>
+        </core>
+        "#;
+        let doc = Document::parse_str(xml).unwrap();
+        let root = doc.root_element().unwrap();
+        let mut buf = String::new();
+        let mut texts = Vec::new();
+        for elem in root.child_elements(&doc) {
+            buf.clear();
+            elem.write_text_content(&doc, &mut buf);
+            texts.push(buf.clone());
+        }
+        assert_eq!(texts, vec!["Text", "Text2", "Text3"]);
+
+        // Appends rather than overwriting
+        root.write_text_content(&doc, &mut buf);
+        assert_eq!(buf, "Text3TextText2Text3");
+    }
+
+    #[test]
+    fn test_attribute_helpers() {
+        let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+        <config enabled="TRUE" debug="no" verbose="1" quiet="False" flag="" size="42" ratio="abc" />
+        "#;
+        let doc = Document::parse_str(xml).unwrap();
+        let config = doc.root_element().unwrap();
+
+        assert!(config.has_attribute(&doc, "flag"));
+        assert!(config.has_attribute(&doc, "enabled"));
+        assert!(!config.has_attribute(&doc, "missing"));
+
+        assert_eq!(config.attribute_bool(&doc, "enabled"), Some(true));
+        assert_eq!(config.attribute_bool(&doc, "debug"), Some(false));
+        assert_eq!(config.attribute_bool(&doc, "verbose"), Some(true));
+        assert_eq!(config.attribute_bool(&doc, "quiet"), Some(false));
+        assert_eq!(config.attribute_bool(&doc, "flag"), None);
+        assert_eq!(config.attribute_bool(&doc, "size"), None);
+        assert_eq!(config.attribute_bool(&doc, "missing"), None);
+
+        assert_eq!(config.attribute_parsed::<u32>(&doc, "size"), Some(Ok(42)));
+        assert!(matches!(
+            config.attribute_parsed::<f64>(&doc, "ratio"),
+            Some(Err(_))
+        ));
+        assert!(config.attribute_parsed::<u32>(&doc, "missing").is_none());
+    }
+
+    #[test]
+    fn test_attribute_tokens() {
+        let mut doc = Document::new();
+        let div = Element::build("div")
+            .attribute("class", " a  b\tc ")
+            .finish(&mut doc);
+
+        assert!(div.attribute_contains_token(&doc, "class", "a"));
+        assert!(div.attribute_contains_token(&doc, "class", "c"));
+        assert!(!div.attribute_contains_token(&doc, "class", "d"));
+        assert!(!div.attribute_contains_token(&doc, "class", ""));
+        assert!(!div.attribute_contains_token(&doc, "missing", "a"));
+
+        assert!(div.add_attribute_token(&mut doc, "class", "d"));
+        assert_eq!(div.attribute(&doc, "class"), Some(" a  b\tc d"));
+        assert!(!div.add_attribute_token(&mut doc, "class", "b"));
+        assert!(!div.add_attribute_token(&mut doc, "class", "e f"));
+        assert!(!div.add_attribute_token(&mut doc, "class", ""));
+        assert_eq!(div.attribute(&doc, "class"), Some(" a  b\tc d"));
+        assert!(div.add_attribute_token(&mut doc, "rel", "next"));
+        assert_eq!(div.attribute(&doc, "rel"), Some("next"));
+
+        assert!(div.remove_attribute_token(&mut doc, "class", "b"));
+        assert_eq!(div.attribute(&doc, "class"), Some("a c d"));
+        assert!(!div.remove_attribute_token(&mut doc, "class", "b"));
+        assert!(!div.remove_attribute_token(&mut doc, "missing", "b"));
+        assert!(div.remove_attribute_token(&mut doc, "rel", "next"));
+        assert_eq!(div.attribute(&doc, "rel"), Some(""));
+        assert!(div.add_attribute_token(&mut doc, "rel", "prev"));
+        assert_eq!(div.attribute(&doc, "rel"), Some("prev"));
+    }
+
+    #[test]
+    fn test_modify_attribute() {
+        let mut doc = Document::new();
+        let counter = Element::build("counter").finish(&mut doc);
+        let increment = |count: Option<&str>| {
+            let count: u32 = count.map_or(0, |count| count.parse().unwrap());
+            Some((count + 1).to_string())
+        };
+
+        // A missing attribute is created
+        counter.modify_attribute(&mut doc, "count", increment);
+        assert_eq!(counter.attribute(&doc, "count"), Some("1"));
+        for _ in 0..9 {
+            counter.modify_attribute(&mut doc, "count", increment);
+        }
+        assert_eq!(counter.attribute(&doc, "count"), Some("10"));
+
+        // Returning None removes the attribute, or leaves it missing
+        counter.modify_attribute(&mut doc, "count", |count| {
+            assert_eq!(count, Some("10"));
+            None
+        });
+        assert!(!counter.has_attribute(&doc, "count"));
+        counter.modify_attribute(&mut doc, "count", |_| None);
+        assert_eq!(counter.attribute_count(&doc), 0);
+    }
+
+    #[test]
+    fn test_name_validation() {
+        let mut doc = Document::new();
+        assert!(Element::try_new(&mut doc, "item").is_ok());
+        assert!(Element::try_new(&mut doc, "pre:item").is_ok());
+        for invalid in ["1item", "my item", " item", "", "pre:1item"] {
+            assert!(
+                matches!(
+                    Element::try_new(&mut doc, invalid),
+                    Err(EditXMLError::InvalidName(_))
+                ),
+                "{invalid} should be invalid"
+            );
+        }
+
+        let elem = Element::new(&mut doc, "pre:item");
+        elem.try_set_name(&mut doc, "renamed").unwrap();
+        assert_eq!(elem.full_name(&doc), "pre:renamed");
+        for invalid in ["1renamed", "re named", "other:renamed"] {
+            assert!(elem.try_set_name(&mut doc, invalid).is_err());
+        }
+        assert_eq!(elem.full_name(&doc), "pre:renamed");
+
+        elem.try_set_attribute(&mut doc, "id", "1").unwrap();
+        elem.try_set_attribute(&mut doc, "xml:lang", "en").unwrap();
+        assert!(elem.try_set_attribute(&mut doc, "2id", "2").is_err());
+        assert!(elem.try_set_attribute(&mut doc, "my id", "2").is_err());
+        assert_eq!(elem.attributes(&doc).len(), 2);
+    }
+
+    #[test]
+    fn test_try_set_full_name() {
+        let mut doc = Document::new();
+        let elem = Element::new(&mut doc, "item");
+        elem.try_set_full_name(&mut doc, "ns:name").unwrap();
+        assert_eq!(elem.full_name(&doc), "ns:name");
+        assert_eq!(elem.prefix(&doc), "ns");
+        assert_eq!(elem.name(&doc), "name");
+        elem.try_set_full_name(&mut doc, "plain").unwrap();
+        assert_eq!(elem.prefix(&doc), "");
+
+        for invalid in ["a:b:c", ":name", "ns:", "1name", "ns:1name", "my name", ""] {
+            assert!(
+                matches!(
+                    elem.try_set_full_name(&mut doc, invalid),
+                    Err(EditXMLError::InvalidName(name)) if name == invalid
+                ),
+                "{invalid} should be invalid"
+            );
+        }
+        assert_eq!(elem.full_name(&doc), "plain");
+
+        // The unchecked version writes invalid names as is
+        elem.set_full_name(&mut doc, "a:b:c");
+        assert_eq!(elem.full_name(&doc), "a:b:c");
+    }
+
+    #[test]
+    fn test_set_text_content() {
+        let mut doc = Document::new_with_root("root", |root| root.add_text("Old"));
+        let root = doc.root_element().unwrap();
+        let children_ptr = root.children(&doc).as_ptr();
+
+        // A single text child is replaced in place
+        root.set_text_content(&mut doc, "New");
+        assert_eq!(root.children(&doc), &vec![Node::Text("New".to_owned())]);
+        assert_eq!(root.children(&doc).as_ptr(), children_ptr);
+
+        // Otherwise, the children are replaced
+        let child = Element::build("child").push_to(&mut doc, root);
+        root.set_text_content(&mut doc, "Text");
+        assert_eq!(root.children(&doc), &vec![Node::Text("Text".to_owned())]);
+        assert_eq!(child.parent(&doc), None);
+
+        root.set_cdata_content(&mut doc, "CData");
+        root.set_text_content(&mut doc, "Text");
+        assert_eq!(root.children(&doc), &vec![Node::Text("Text".to_owned())]);
+
+        let empty = Element::build("empty").push_to(&mut doc, root);
+        empty.set_text_content(&mut doc, "");
+        assert_eq!(empty.children(&doc), &vec![Node::Text(String::new())]);
+    }
+
+    #[test]
+    fn test_set_content() {
+        let mut doc = Document::new_with_root("root", |root| root.add_text("Old"));
+        let root = doc.root_element().unwrap();
+
+        root.set_cdata_content(&mut doc, "a & b < c");
+        assert_eq!(
+            root.children(&doc),
+            &vec![Node::CData("a & b < c".to_owned())]
+        );
+        let written = doc.write_str().unwrap();
+        assert!(written.contains("<![CDATA[a & b < c]]>"));
+        let reparsed = Document::parse_str(&written).unwrap();
+        let reparsed_root = reparsed.root_element().unwrap();
+        assert_eq!(
+            reparsed_root.children(&reparsed),
+            &vec![Node::CData("a & b < c".to_owned())]
+        );
+
+        let child = Element::new(&mut doc, "child");
+        root.set_content_nodes(
+            &mut doc,
+            vec![
+                Node::Text("Text".to_owned()),
+                Node::Element(child),
+                Node::Comment("Comment".to_owned()),
+            ],
+        )
+        .unwrap();
+        assert_eq!(root.children(&doc).len(), 3);
+        assert_eq!(child.parent(&doc), Some(root));
+
+        // Invalid nodes leave the children unchanged
+        let other = Element::build("other").push_to(&mut doc, child);
+        assert!(matches!(
+            root.set_content_nodes(&mut doc, vec![Node::Text("New".to_owned()), other.into()]),
+            Err(EditXMLError::HasAParent)
+        ));
+        let container = doc.container();
+        assert!(matches!(
+            root.set_content_nodes(&mut doc, vec![container.into()]),
+            Err(EditXMLError::ContainerCannotMove)
+        ));
+        assert_eq!(root.children(&doc).len(), 3);
+        assert_eq!(child.parent(&doc), Some(root));
+
+        root.set_content_nodes(&mut doc, Vec::new()).unwrap();
+        assert!(!root.has_children(&doc));
+        assert_eq!(child.parent(&doc), None);
+    }
+
+    #[test]
+    fn test_outer_inner_xml() {
+        let doc = Document::parse_str(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+            <root>
+                <item id="1"><name>First</name><!-- Comment --></item>
+                <empty />
+            </root>
+            "#,
+        )
+        .unwrap();
+        let root = doc.root_element().unwrap();
+        let item = root.find(&doc, "item").unwrap();
+        assert_eq!(
+            item.outer_xml(&doc).unwrap(),
+            "<item id=\"1\">\n  <name>First</name>\n  <!-- Comment -->\n</item>"
+        );
+        assert_eq!(
+            item.inner_xml(&doc).unwrap(),
+            "<name>First</name>\n<!-- Comment -->"
+        );
+        let empty = root.find(&doc, "empty").unwrap();
+        assert_eq!(empty.outer_xml(&doc).unwrap(), "<empty/>");
+        assert_eq!(empty.inner_xml(&doc).unwrap(), "");
+
+        let opts = WriteOptions {
+            indent_size: 0,
+            ..Default::default()
+        };
+        assert_eq!(
+            item.outer_xml_with_opts(&doc, opts).unwrap(),
+            "<item id=\"1\">\n<name>First</name>\n<!-- Comment -->\n</item>"
+        );
+        // The container has no tag, so both are the same
+        assert_eq!(
+            doc.container().outer_xml(&doc).unwrap(),
+            root.outer_xml(&doc).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_intern_names() {
+        let xml =
+            r#"<root><item id="1">1</item><item id="2">2</item><p:item item="3">3</p:item></root>"#;
+        let parse = |intern_names| {
+            let opts = ReadOptions {
+                intern_names,
+                ..ReadOptions::relaxed()
+            };
+            let doc = Document::parse_str_with_opts(xml, opts).unwrap();
+            let items = doc.root_element().unwrap().child_elements(&doc);
+            (doc, items)
+        };
+
+        let (mut doc, items) = parse(true);
+        let name = |doc: &Document, elem: Element| elem.data(doc).full_name.clone();
+        assert!(name(&doc, items[0]).shares_allocation(&name(&doc, items[1])));
+        assert!(!name(&doc, items[0]).shares_allocation(&name(&doc, items[2])));
+        assert_eq!(items[2].full_name(&doc), "p:item");
+        // Attribute names are shared with each other and with element names
+        let key =
+            |doc: &Document, elem: Element| elem.attributes(doc).keys().next().unwrap().clone();
+        assert!(key(&doc, items[0]).shares_allocation(&key(&doc, items[1])));
+        assert!(key(&doc, items[2]).shares_allocation(&name(&doc, items[0])));
+
+        items[0].set_name(&mut doc, "renamed");
+        assert_eq!(items[0].name(&doc), "renamed");
+        assert_eq!(items[1].name(&doc), "item");
+
+        let (doc, items) = parse(false);
+        assert!(!name(&doc, items[0]).shares_allocation(&name(&doc, items[1])));
+        assert!(!key(&doc, items[0]).shares_allocation(&key(&doc, items[1])));
+        assert_eq!(items[0].full_name(&doc), items[1].full_name(&doc));
+    }
+
+    #[test]
+    fn test_write_to() {
+        let doc = Document::parse_str(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+            <root>
+                <item id="1"><name>First</name></item>
+                <item id="2"><name>Second</name></item>
+            </root>
+            "#,
+        )
+        .unwrap();
+        let root = doc.root_element().unwrap();
+        let mut out: Vec<u8> = Vec::new();
+        for item in root.find_all(&doc, "item") {
+            item.write_to(&doc, &mut out, WriteOptions::default())
+                .unwrap();
+            assert_eq!(
+                String::from_utf8(out.clone()).unwrap(),
+                item.outer_xml(&doc).unwrap()
+            );
+            out.clear();
+        }
+
+        let opts = WriteOptions {
+            pretty: false,
+            write_decl: true,
+            preamble: Some("<!-- Preamble -->".to_owned()),
+            ..Default::default()
+        };
+        root.write_to(&doc, &mut out, opts).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "<root><item id=\"1\"><name>First</name></item><item id=\"2\"><name>Second</name></item></root>"
+        );
+    }
+
+    #[test]
+    fn test_set_inner_xml() {
+        let mut doc = Document::parse_str(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+            <root xmlns:p="urn:p"><a><old /></a></root>
+            "#,
+        )
+        .unwrap();
+        let root = doc.root_element().unwrap();
+        let a = root.find(&doc, "a").unwrap();
+        let old = a.find(&doc, "old").unwrap();
+
+        a.set_inner_xml(&mut doc, "<b>hi</b> text").unwrap();
+        assert_eq!(old.parent(&doc), None);
+        let children = a.children(&doc);
+        assert_eq!(children.len(), 2);
+        let b = children[0].as_element().unwrap();
+        assert_eq!(b.parent(&doc), Some(a));
+        assert_eq!(b.text_content(&doc), "hi");
+        assert_eq!(children[1], Node::Text(" text".to_owned()));
+
+        // Prefixes are resolved against the ancestors
+        a.set_inner_xml(
+            &mut doc,
+            r#"<p:item id="1"><p:inner /></p:item><!-- Comment -->"#,
+        )
+        .unwrap();
+        let item = a.first_child_element(&doc).unwrap();
+        assert_eq!(item.namespace(&doc), Some("urn:p"));
+        assert_eq!(item.attribute(&doc, "id"), Some("1"));
+        let inner = item.first_child_element(&doc).unwrap();
+        assert_eq!(inner.parent(&doc), Some(item));
+        assert_eq!(inner.namespace(&doc), Some("urn:p"));
+        assert!(a.children(&doc)[1].is_comment());
+
+        // Invalid fragments leave the children unchanged
+        assert!(a.set_inner_xml(&mut doc, "<unclosed>").is_err());
+        assert_eq!(a.first_child_element(&doc), Some(item));
+
+        a.set_inner_xml(&mut doc, "").unwrap();
+        assert!(!a.has_children(&doc));
+    }
+
+    #[test]
+    fn test_trim_whitespace_nodes() {
+        let opts = ReadOptions {
+            trim_text: false,
+            ..ReadOptions::default()
+        };
+        let mut doc = Document::parse_str_with_opts(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<root>
+    <empty></empty>
+    <mixed>Hello <b>World</b> </mixed>
+    <pre xml:space="preserve">
+        <inner> </inner>
+        <reset xml:space="default"> <c /> </reset>
+    </pre>
+</root>
+"#,
+            opts,
+        )
+        .unwrap();
+        let root = doc.root_element().unwrap();
+        let pre = root.find(&doc, "pre").unwrap();
+        let inner = pre.find(&doc, "inner").unwrap();
+        let reset = pre.find(&doc, "reset").unwrap();
+
+        // Only the elements below `inner` are trimmed, and `xml:space` is inherited
+        inner.trim_whitespace_nodes(&mut doc);
+        assert_eq!(inner.children(&doc), &vec![Node::Text(" ".to_owned())]);
+
+        doc.trim_whitespace_nodes();
+        assert!(doc.root_nodes().iter().all(Node::is_element));
+        assert_eq!(root.children(&doc).len(), 3);
+        assert!(!root.find(&doc, "empty").unwrap().has_children(&doc));
+        let mixed = root.find(&doc, "mixed").unwrap();
+        assert_eq!(mixed.children(&doc).len(), 3);
+        assert_eq!(mixed.children(&doc)[2], Node::Text(" ".to_owned()));
+        assert_eq!(pre.children(&doc).len(), 5);
+        assert_eq!(inner.children(&doc).len(), 1);
+        assert_eq!(reset.children(&doc).len(), 1);
+    }
+
+    #[test]
+    fn test_append_inner_xml() {
+        let mut doc = Document::parse_str(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+            <root><old /></root>
+            "#,
+        )
+        .unwrap();
+        let root = doc.root_element().unwrap();
+        let old = root.find(&doc, "old").unwrap();
+
+        let first = root
+            .append_inner_xml(&mut doc, r#"<item id="1" />"#)
+            .unwrap();
+        let second = root
+            .append_inner_xml(&mut doc, r#"<item id="2">Two</item> tail"#)
+            .unwrap();
+        assert_eq!(first.len(), 1);
+        assert_eq!(second.len(), 2);
+        assert_eq!(second[1], Node::Text(" tail".to_owned()));
+
+        let mut expected = vec![Node::Element(old)];
+        expected.extend(first);
+        expected.extend(second);
+        assert_eq!(root.children(&doc), expected.as_slice());
+        let ids: Vec<&str> = root
+            .find_all(&doc, "item")
+            .into_iter()
+            .filter_map(|item| item.attribute(&doc, "id"))
+            .collect();
+        assert_eq!(ids, vec!["1", "2"]);
+
+        // Invalid fragments leave the children unchanged
+        assert!(root.append_inner_xml(&mut doc, "<unclosed>").is_err());
+        assert_eq!(root.children(&doc), expected.as_slice());
+    }
+
+    #[test]
+    fn test_remove_conflicting_namespace_decls() {
+        let mut doc = Document::parse_str(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+            <root xmlns="urn:default" xmlns:a="urn:a" xmlns:b="urn:b">
+                <child xmlns="urn:default" xmlns:a="urn:a" xmlns:b="urn:other" xmlns:c="urn:c">
+                    <inner xmlns:a="urn:a" xmlns:c="urn:c" xmlns:xml="http://www.w3.org/XML/1998/namespace" />
+                </child>
+            </root>
+            "#,
+        )
+        .unwrap();
+        let root = doc.root_element().unwrap();
+        let child = root.find(&doc, "child").unwrap();
+        let inner = child.find(&doc, "inner").unwrap();
+
+        assert_eq!(root.remove_conflicting_namespace_decls(&mut doc), 0);
+        assert_eq!(root.namespace_decls(&doc).len(), 3);
+
+        // The default namespace and `a` are inherited, `b` overrides the ancestor
+        assert_eq!(child.remove_conflicting_namespace_decls(&mut doc), 2);
+        let mut remaining: Vec<&String> = child.namespace_decls(&doc).keys().collect();
+        remaining.sort();
+        assert_eq!(remaining, vec!["b", "c"]);
+
+        assert_eq!(inner.remove_conflicting_namespace_decls(&mut doc), 3);
+        assert!(inner.namespace_decls(&doc).is_empty());
+        assert_eq!(inner.namespace_for_prefix(&doc, "a"), Some("urn:a"));
+        assert_eq!(inner.namespace_for_prefix(&doc, "b"), Some("urn:other"));
+        assert_eq!(inner.namespace(&doc), Some("urn:default"));
+    }
+
+    #[test]
+    fn test_replace_namespace_uri() {
+        let mut doc = Document::parse_str(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+            <root xmlns="urn:old" xmlns:o="urn:old" xmlns:k="urn:keep">
+                <a xmlns:x="urn:old"><b xmlns="urn:old" /></a>
+                <c xmlns:o="urn:keep" />
+            </root>
+            "#,
+        )
+        .unwrap();
+        let root = doc.root_element().unwrap();
+        let a = root.find(&doc, "a").unwrap();
+        let b = a.find(&doc, "b").unwrap();
+        let c = root.find(&doc, "c").unwrap();
+
+        // Only the subtree is changed
+        assert_eq!(a.replace_namespace_uri(&mut doc, "urn:old", "urn:new"), 2);
+        assert_eq!(a.namespace_for_prefix(&doc, "x"), Some("urn:new"));
+        assert_eq!(b.namespace(&doc), Some("urn:new"));
+        assert_eq!(root.namespace(&doc), Some("urn:old"));
+
+        assert_eq!(
+            root.replace_namespace_uri(&mut doc, "urn:old", "urn:new"),
+            2
+        );
+        assert_eq!(root.namespace_for_prefix(&doc, "o"), Some("urn:new"));
+        assert_eq!(root.namespace_for_prefix(&doc, "k"), Some("urn:keep"));
+        assert_eq!(c.namespace_for_prefix(&doc, "o"), Some("urn:keep"));
+        assert_eq!(
+            root.replace_namespace_uri(&mut doc, "urn:old", "urn:new"),
+            0
+        );
+    }
+
+    #[test]
+    fn test_is_descendant_of() {
+        let mut doc = Document::parse_str(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+            <root>
+                <a><b><c /></b></a>
+                <d />
+            </root>
+            "#,
+        )
+        .unwrap();
+        let root = doc.root_element().unwrap();
+        let a = root.find(&doc, "a").unwrap();
+        let b = a.find(&doc, "b").unwrap();
+        let c = b.find(&doc, "c").unwrap();
+        let d = root.find(&doc, "d").unwrap();
+        let container = doc.container();
+
+        assert!(c.is_descendant_of(&doc, b));
+        assert!(c.is_descendant_of(&doc, a));
+        assert!(c.is_descendant_of(&doc, root));
+        assert!(c.is_descendant_of(&doc, container));
+        assert!(!c.is_descendant_of(&doc, c));
+        assert!(!c.is_descendant_of(&doc, d));
+        assert!(!a.is_descendant_of(&doc, c));
+        assert!(root.is_ancestor_of(&doc, c));
+        assert!(!c.is_ancestor_of(&doc, root));
+        assert!(!d.is_ancestor_of(&doc, c));
+
+        // Moving an element into its own subtree would create a cycle
+        a.detach(&mut doc).unwrap();
+        assert!(!c.is_descendant_of(&doc, root));
+        assert!(matches!(
+            c.push_child(&mut doc, a),
+            Err(EditXMLError::CyclicParent)
+        ));
+        assert!(matches!(
+            a.push_child(&mut doc, a),
+            Err(EditXMLError::CyclicParent)
+        ));
+        assert!(matches!(
+            b.insert_child(&mut doc, 0, Node::Element(a)),
+            Err(EditXMLError::CyclicParent)
+        ));
+        assert!(matches!(
+            c.push_children(&mut doc, vec![Node::Text("x".to_owned()), Node::Element(a)]),
+            Err(EditXMLError::CyclicParent)
+        ));
+        assert_eq!(a.parent(&doc), None);
+        assert!(c.children(&doc).is_empty());
+
+        d.push_child(&mut doc, a).unwrap();
+        assert!(c.is_descendant_of(&doc, d));
+        assert!(c.is_descendant_of(&doc, root));
+    }
+
+    #[test]
+    fn test_take_from_parent() {
+        let mut doc = Document::new_with_root("root", |root| {
+            root.create_element("from", |from| {
+                from.add_text("a")
+                    .create_element("moved", |moved| moved.add_text("Moved"))
+                    .add_text("b")
+            })
+            .create_element("to", |to| to)
+        });
+        let root = doc.root_element().unwrap();
+        let from = root.find(&doc, "from").unwrap();
+        let to = root.find(&doc, "to").unwrap();
+        let moved = from.find(&doc, "moved").unwrap();
+
+        let taken = moved.take_from_parent(&mut doc).unwrap();
+        assert_eq!(taken, moved);
+        assert_eq!(moved.parent(&doc), None);
+        assert_eq!(from.text_content(&doc), "ab");
+        to.push_child(&mut doc, taken).unwrap();
+        assert_eq!(moved.parent(&doc), Some(to));
+        assert_eq!(to.text_content(&doc), "Moved");
+
+        // An element without a parent is returned as is
+        let detached = Element::new(&mut doc, "detached");
+        assert_eq!(detached.take_from_parent(&mut doc).unwrap(), detached);
+
+        let container = doc.container();
+        assert!(matches!(
+            container.take_from_parent(&mut doc),
+            Err(EditXMLError::ContainerCannotMove)
+        ));
+    }
+
+    #[test]
+    fn test_replace_with() {
+        let mut doc = Document::new_with_root("root", |root| {
+            root.add_text("a")
+                .create_element("old", |old| old.add_text("Old"))
+                .add_text("c")
+        });
+        let root = doc.root_element().unwrap();
+        let old = root.find(&doc, "old").unwrap();
+
+        let removed = old
+            .replace_with(&mut doc, Node::Text("b".to_owned()))
+            .unwrap();
+        assert_eq!(removed, Node::Element(old));
+        assert_eq!(old.parent(&doc), None);
+        assert_eq!(old.text_content(&doc), "Old");
+        assert_eq!(
+            root.children(&doc),
+            &vec![
+                Node::Text("a".to_owned()),
+                Node::Text("b".to_owned()),
+                Node::Text("c".to_owned()),
+            ]
+        );
+
+        // Replacing with an element sets its parent
+        let new = Element::new(&mut doc, "new");
+        let b = Element::build("b").push_to(&mut doc, root);
+        b.replace_with(&mut doc, new.into()).unwrap();
+        assert_eq!(new.parent(&doc), Some(root));
+        assert_eq!(new.index_in_parent(&doc), Some(3));
+
+        assert!(matches!(
+            old.replace_with(&mut doc, Node::Text("x".to_owned())),
+            Err(EditXMLError::NoParent)
+        ));
+        let container = doc.container();
+        assert!(matches!(
+            container.replace_with(&mut doc, Node::Text("x".to_owned())),
+            Err(EditXMLError::ContainerCannotMove)
+        ));
+        // Invalid nodes leave the tree unchanged
+        let child = Element::build("child").push_to(&mut doc, new);
+        assert!(matches!(
+            new.replace_with(&mut doc, child.into()),
+            Err(EditXMLError::HasAParent)
+        ));
+        assert!(matches!(
+            new.replace_with(&mut doc, container.into()),
+            Err(EditXMLError::ContainerCannotMove)
+        ));
+        assert_eq!(new.parent(&doc), Some(root));
+        assert_eq!(root.children(&doc).len(), 4);
+    }
+
+    #[test]
+    fn test_deep_clone() {
+        let mut doc = Document::new_with_root("root", |root| {
+            root.create_element("item", |item| {
+                item.attribute("id", "1")
+                    .add_text("Text")
+                    .create_element("inner", |inner| inner)
+            })
+        });
+        let root = doc.root_element().unwrap();
+        let item = root.find(&doc, "item").unwrap();
+        let inner = item.find(&doc, "inner").unwrap();
+
+        let copy = Node::Element(item).clone_deep(&mut doc);
+        let copy = copy.as_element().unwrap();
+        assert_ne!(copy, item);
+        assert_eq!(copy.parent(&doc), None);
+        let copy_inner = copy.find(&doc, "inner").unwrap();
+        assert_ne!(copy_inner, inner);
+        assert_eq!(copy_inner.parent(&doc), Some(copy));
+
+        // Mutating the copy doesn't affect the original
+        copy.set_attribute(&mut doc, "id", "2");
+        copy_inner.set_name(&mut doc, "changed");
+        copy.push_child(&mut doc, Node::Text("More".to_owned()))
+            .unwrap();
+        assert_eq!(item.attribute(&doc, "id"), Some("1"));
+        assert_eq!(inner.name(&doc), "inner");
+        assert_eq!(item.children(&doc).len(), 2);
+        assert_eq!(copy.children(&doc).len(), 3);
+
+        root.push_child(&mut doc, copy).unwrap();
+        assert_eq!(root.child_elements(&doc).len(), 2);
+
+        let text = Node::Text("Text".to_owned());
+        assert_eq!(text.clone_deep(&mut doc), text);
+    }
+
+    #[test]
+    fn test_closest() {
+        let mut doc = Document::parse_str(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+            <root id="r"><x:div id="d"><div><span /></div></x:div></root>
+            "#,
+        )
+        .unwrap();
+        let root = doc.root_element().unwrap();
+        let outer = root.find(&doc, "div").unwrap();
+        let inner = outer.find(&doc, "div").unwrap();
+        let span = inner.find(&doc, "span").unwrap();
+
+        // `self` is checked first
+        assert_eq!(span.closest_named(&doc, "span"), Some(span));
+        assert_eq!(span.closest_named(&doc, "div"), Some(inner));
+        assert_eq!(outer.closest_named(&doc, "div"), Some(outer));
+        assert_eq!(span.closest_named(&doc, "root"), Some(root));
+        assert_eq!(span.closest_named(&doc, "missing"), None);
+        let has_id = |doc: &Document, elem: Element| elem.has_attribute(doc, "id");
+        assert_eq!(span.closest(&doc, has_id), Some(outer));
+
+        // The container is never matched
+        assert_eq!(root.closest(&doc, |_, _| false), None);
+        assert_eq!(root.closest(&doc, |_, elem| elem.is_container()), None);
+
+        let detached = Element::new(&mut doc, "div");
+        assert_eq!(detached.closest_named(&doc, "div"), Some(detached));
+        assert_eq!(detached.closest_named(&doc, "root"), None);
+    }
+
+    #[test]
+    fn test_insert_children() {
+        let mut doc = Document::parse_str(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+            <root><first /><last /></root>
+            "#,
+        )
+        .unwrap();
+        let root = doc.root_element().unwrap();
+        let first = root.find(&doc, "first").unwrap();
+        let last = root.find(&doc, "last").unwrap();
+        let a = Element::new(&mut doc, "a");
+        let b = Element::new(&mut doc, "b");
+
+        let nodes = vec![
+            Node::Element(a),
+            Node::Text("text".to_owned()),
+            Node::Element(b),
+        ];
+        root.insert_children(&mut doc, 1, nodes).unwrap();
+        assert_eq!(
+            root.children(&doc),
+            &vec![
+                Node::Element(first),
+                Node::Element(a),
+                Node::Text("text".to_owned()),
+                Node::Element(b),
+                Node::Element(last),
+            ]
+        );
+        assert_eq!(a.parent(&doc), Some(root));
+        assert_eq!(b.parent(&doc), Some(root));
+
+        // Nothing is inserted if any node is invalid
+        let c = Element::new(&mut doc, "c");
+        for nodes in [
+            vec![Node::Element(c), Node::Element(a)],
+            vec![Node::Element(c), Node::Element(c)],
+            vec![Node::Element(c), Node::Element(doc.container())],
+        ] {
+            assert!(root.insert_children(&mut doc, 0, nodes).is_err());
+            assert_eq!(root.children(&doc).len(), 5);
+            assert_eq!(c.parent(&doc), None);
+        }
+        root.insert_children(&mut doc, 5, vec![]).unwrap();
+        assert_eq!(root.children(&doc).len(), 5);
+    }
+
+    #[test]
+    fn test_text_before_after() {
+        let opts = ReadOptions {
+            trim_text: false,
+            ..ReadOptions::default()
+        };
+        let mut doc = Document::parse_str_with_opts(
+            r#"<?xml version="1.0" encoding="UTF-8"?><p><first />Hello <b>x</b> world<!-- Comment --><i /><last /></p>"#,
+            opts,
+        )
+        .unwrap();
+        let p = doc.root_element().unwrap();
+        let first = p.find(&doc, "first").unwrap();
+        let b = p.find(&doc, "b").unwrap();
+        let i = p.find(&doc, "i").unwrap();
+        let last = p.find(&doc, "last").unwrap();
+
+        assert_eq!(b.text_before(&doc), Some("Hello "));
+        assert_eq!(b.text_after(&doc), Some(" world"));
+        // Missing siblings
+        assert_eq!(first.text_before(&doc), None);
+        assert_eq!(last.text_after(&doc), None);
+        assert_eq!(first.text_after(&doc), Some("Hello "));
+        // Siblings that aren't text
+        assert_eq!(i.text_before(&doc), None);
+        assert_eq!(i.text_after(&doc), None);
+        assert_eq!(p.text_before(&doc), None);
+
+        b.detach(&mut doc).unwrap();
+        assert_eq!(b.text_before(&doc), None);
+        assert_eq!(b.text_after(&doc), None);
+    }
+
+    #[test]
+    fn test_index_in_parent() {
+        let mut doc = Document::new_with_root("root", |root| {
+            root.add_text("Text")
+                .create_element("a", |a| a)
+                .create_element("b", |b| b)
+        });
+        let root = doc.root_element().unwrap();
+        let a = root.find(&doc, "a").unwrap();
+        let b = root.find(&doc, "b").unwrap();
+        assert_eq!(a.index_in_parent(&doc), Some(1));
+        assert_eq!(b.index_in_parent(&doc), Some(2));
+        assert_eq!(root.index_in_parent(&doc), Some(0));
+        assert_eq!(doc.container().index_in_parent(&doc), None);
+
+        a.detach(&mut doc).unwrap();
+        assert_eq!(a.index_in_parent(&doc), None);
+        assert_eq!(b.index_in_parent(&doc), Some(1));
+    }
+
+    #[test]
+    fn test_normalize() {
+        let mut doc = Document::new_with_root("root", |root| root);
+        let root = doc.root_element().unwrap();
+        for text in ["a", "b", "c"] {
+            root.push_child(&mut doc, Node::Text(text.to_owned()))
+                .unwrap();
+        }
+        let child = Element::build("child").push_to(&mut doc, root);
+        for text in ["", "d", "", "e"] {
+            child
+                .push_child(&mut doc, Node::Text(text.to_owned()))
+                .unwrap();
+        }
+        child
+            .push_child(&mut doc, Node::Comment("Comment".to_owned()))
+            .unwrap();
+        child
+            .push_child(&mut doc, Node::Text(String::new()))
+            .unwrap();
+        root.push_child(&mut doc, Node::Text("f".to_owned()))
+            .unwrap();
+
+        root.normalize(&mut doc);
+        assert_eq!(
+            root.children(&doc),
+            &vec![
+                Node::Text("abc".to_owned()),
+                Node::Element(child),
+                Node::Text("f".to_owned()),
+            ]
+        );
+        assert_eq!(
+            child.children(&doc),
+            &vec![
+                Node::Text("de".to_owned()),
+                Node::Comment("Comment".to_owned()),
+            ]
+        );
+        assert_eq!(child.parent(&doc), Some(root));
+    }
+
     #[test]
     fn test_mutate_tree() -> anyhow::Result<()> {
         // Test tree consistency after mutating tree
@@ -50,7 +50,11 @@ impl ElementBreakdown {
     #[cfg_attr(feature = "tracing", tracing::instrument)]
     pub fn new(element: Element, doc: &Document) -> Self {
         let name = element.name(doc).to_owned();
-        let attributes = element.attributes(doc).clone();
+        let attributes = element
+            .attributes(doc)
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.clone()))
+            .collect();
         let namespace_decls = element.namespace_decls(doc).clone();
         let children = get_children(element, doc);
         Self {
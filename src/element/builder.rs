@@ -1,4 +1,4 @@
-use crate::{Document, Node, utils::HashMap};
+use crate::{Document, Name, Node, utils::HashMap};
 
 use super::Element;
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -65,7 +65,7 @@ impl NewNodes {
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct ElementBuilder {
     full_name: String,
-    attributes: HashMap<String, String>,
+    attributes: HashMap<Name, String>,
     namespace_decls: HashMap<String, String>,
     content: Vec<NewNodes>,
 }
@@ -119,7 +119,8 @@ impl ElementBuilder {
         S: Into<String>,
         T: Into<String>,
     {
-        self.attributes.insert(name.into(), value.into());
+        self.attributes
+            .insert(Name::from(name.into()), value.into());
         self
     }
     /// Add a namespace declaration to the element.
@@ -237,7 +238,7 @@ impl ElementBuilder {
             namespace_decls,
             content,
         } = self;
-        let elem = Element::with_data(doc, full_name, attributes, namespace_decls);
+        let elem = Element::with_data(doc, full_name.into(), attributes, namespace_decls);
 
         for node in content {
             node.push_to(doc, elem);
@@ -0,0 +1,105 @@
+use crate::{Document, Element};
+
+/// Matches `names` against the `/` separated `segments` of a path pattern.
+fn matches_segments(segments: &[&str], names: &[&str]) -> bool {
+    match segments.split_first() {
+        None => names.is_empty(),
+        Some((&"**", rest)) => (0..=names.len()).any(|skip| matches_segments(rest, &names[skip..])),
+        Some((segment, rest)) => match names.split_first() {
+            Some((name, names)) => {
+                (*segment == "*" || segment == name) && matches_segments(rest, names)
+            }
+            None => false,
+        },
+    }
+}
+
+impl Element {
+    /// Returns `true` if the path from the root element to this element matches `pattern`.
+    ///
+    /// The pattern is a `/` separated list of local names (without namespace prefixes),
+    /// starting at the root element. A leading `/` is allowed.
+    /// - `*` matches exactly one element with any name.
+    /// - `**` matches any number of elements, including none.
+    ///
+    /// # Example
+    /// ```
+    /// use edit_xml::Document;
+    /// let doc = Document::parse_str(r#"<?xml version="1.0"?>
+    /// <root>
+    ///     <list><item><name>A</name></item></list>
+    /// </root>
+    /// "#).unwrap();
+    /// let root = doc.root_element().unwrap();
+    /// let item = root.find(&doc, "list").unwrap().find(&doc, "item").unwrap();
+    /// assert!(item.matches_path(&doc, "root/list/item"));
+    /// assert!(item.matches_path(&doc, "root/*/item"));
+    /// assert!(item.matches_path(&doc, "**/item"));
+    /// assert!(!item.matches_path(&doc, "*/item"));
+    /// let name = item.find(&doc, "name").unwrap();
+    /// assert!(name.matches_path(&doc, "/root/**/name"));
+    /// ```
+    pub fn matches_path(&self, doc: &Document, pattern: &str) -> bool {
+        if self.is_container() {
+            return false;
+        }
+        let mut names = Vec::new();
+        let mut elem = Some(*self);
+        while let Some(current) = elem.filter(|elem| !elem.is_container()) {
+            names.push(current.name(doc));
+            elem = current.parent(doc);
+        }
+        names.reverse();
+        let pattern = pattern.strip_prefix('/').unwrap_or(pattern);
+        let segments: Vec<&str> = pattern.split('/').collect();
+        matches_segments(&segments, &names)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Document;
+
+    #[test]
+    fn test_matches_path() {
+        let doc = Document::parse_str(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+            <root xmlns:p="urn:p">
+                <a>
+                    <b><p:item /></b>
+                </a>
+                <item />
+            </root>
+            "#,
+        )
+        .unwrap();
+        let root = doc.root_element().unwrap();
+        let deep = root.find(&doc, "a").unwrap().find(&doc, "b").unwrap();
+        let deep = deep.find(&doc, "item").unwrap();
+        let shallow = root.find(&doc, "item").unwrap();
+
+        // Single level
+        assert!(shallow.matches_path(&doc, "root/item"));
+        assert!(shallow.matches_path(&doc, "root/*"));
+        assert!(shallow.matches_path(&doc, "*/*"));
+        assert!(!shallow.matches_path(&doc, "root/*/item"));
+        assert!(!shallow.matches_path(&doc, "item"));
+        assert!(root.matches_path(&doc, "root"));
+        assert!(root.matches_path(&doc, "*"));
+        assert!(!root.matches_path(&doc, "root/*"));
+
+        // Multiple levels. The prefix is ignored
+        assert!(deep.matches_path(&doc, "root/a/b/item"));
+        assert!(deep.matches_path(&doc, "root/*/*/item"));
+        assert!(deep.matches_path(&doc, "**/item"));
+        assert!(deep.matches_path(&doc, "root/**/item"));
+        assert!(deep.matches_path(&doc, "root/**/b/**/item"));
+        assert!(deep.matches_path(&doc, "**"));
+        assert!(!deep.matches_path(&doc, "root/*/item"));
+        assert!(!deep.matches_path(&doc, "**/a"));
+        assert!(shallow.matches_path(&doc, "**/item"));
+        assert!(shallow.matches_path(&doc, "root/**/item"));
+
+        assert!(!doc.container().matches_path(&doc, "**"));
+    }
+}
@@ -0,0 +1,46 @@
+use std::ops::Index;
+
+use crate::Document;
+
+use super::Element;
+
+/// An [`Element`] with a reference to its [`Document`]. Created with [`Element::with`].
+///
+/// Implements [`Index`] for attribute values, so `elem.with(&doc)["id"]` is the value of `id`.
+/// Like slice indexing, it panics if the attribute doesn't exist.
+/// Use [`Element::attribute`] when the attribute may be missing.
+#[derive(Debug, Clone, Copy)]
+pub struct ElementWithDoc<'doc> {
+    element: Element,
+    doc: &'doc Document,
+}
+impl<'doc> ElementWithDoc<'doc> {
+    /// Create a new ElementWithDoc
+    pub fn new(element: Element, doc: &'doc Document) -> Self {
+        Self { element, doc }
+    }
+    /// The element
+    pub fn element(&self) -> Element {
+        self.element
+    }
+    /// The document of the element
+    pub fn document(&self) -> &'doc Document {
+        self.doc
+    }
+}
+impl Index<&str> for ElementWithDoc<'_> {
+    type Output = str;
+
+    /// # Panics
+    ///
+    /// If the element doesn't have the attribute `name`.
+    fn index(&self, name: &str) -> &str {
+        match self.element.attribute(self.doc, name) {
+            Some(value) => value,
+            None => panic!(
+                "Element `{}` has no attribute `{name}`",
+                self.element.full_name(self.doc)
+            ),
+        }
+    }
+}
@@ -1,5 +1,6 @@
 use quick_xml::{
-    Error as XMLError, encoding::EncodingError, escape::EscapeError, events::attributes::AttrError,
+    Error as XMLError, encoding::EncodingError, errors::IllFormedError, escape::EscapeError,
+    events::attributes::AttrError,
 };
 use std::{str::Utf8Error, string::FromUtf8Error, sync::Arc};
 use thiserror::Error;
@@ -18,6 +19,19 @@ pub enum MalformedReason {
     InvalidStandAloneValue,
     #[error("Missing closing tag")]
     MissingClosingTag,
+    /// An XML declaration that isn't at the very start of the document.
+    #[error("XML Declaration must be at the start of the document")]
+    MisplacedDeclaration,
+    #[error("Duplicate attribute `{0}`")]
+    DuplicateAttribute(String),
+    /// A closing tag doesn't match the open element.
+    #[error("Closing tag mismatch. Expected `</{expected}>`, found `</{found}>`")]
+    EndEventMismatch {
+        /// Name of the open element.
+        expected: String,
+        /// Name in the closing tag.
+        found: String,
+    },
 }
 /// Error types
 #[derive(Debug, Error)]
@@ -43,6 +57,25 @@ pub enum EditXMLError {
     /// You need to call `element.detach()` before assigning another parent.
     #[error("Element already has a parent. Call detach() before changing parent.")]
     HasAParent,
+    /// An element can't be a child of itself or of its descendants.
+    #[error("Element cannot be a child of itself or its descendants")]
+    CyclicParent,
+    /// The operation requires the element to have a parent.
+    #[error("Element has no parent")]
+    NoParent,
+    /// The name is not a valid XML name.
+    #[error("Invalid XML name `{0}`")]
+    InvalidName(String),
+    /// The document doesn't have a root element.
+    #[error("Document has no root element")]
+    NoRootElement,
+    /// A limit set in [`ReadOptions`](crate::ReadOptions) was exceeded while parsing.
+    #[error("Limit `{limit}` of {max} exceeded")]
+    LimitExceeded {
+        /// Name of the exceeded option. e.g. `max_elements`
+        limit: &'static str,
+        max: usize,
+    },
     #[error("Attribute Error {0}")]
     AttrError(#[from] AttrError),
     #[error("{0}")]
@@ -52,10 +85,9 @@ pub enum EditXMLError {
 impl From<XMLError> for EditXMLError {
     fn from(err: XMLError) -> EditXMLError {
         match err {
-            //XMLError::EndEventMismatch { expected, found } => Error::MalformedXML(format!(
-            //    "Closing tag mismatch. Expected {}, found {}",
-            //    expected, found,
-            //)),
+            XMLError::IllFormed(IllFormedError::MismatchedEndTag { expected, found }) => {
+                MalformedReason::EndEventMismatch { expected, found }.into()
+            }
             XMLError::Io(err) => EditXMLError::Io(err),
             // TODO XMLError::(_) => Error::CannotDecode,
             err => EditXMLError::OtherXML(err),
@@ -93,6 +125,9 @@ pub enum DecodeError {
     Other,
     #[error("Cannot decode XML")]
     MissingEncoding,
+    /// The document is in an encoding that is recognized but not supported. e.g. UTF-32
+    #[error("Unsupported encoding {0}")]
+    UnsupportedEncoding(&'static str),
     #[error(transparent)]
     EscapeError(#[from] EscapeError),
 }
@@ -0,0 +1,370 @@
+//! Streaming API that reads XML as a sequence of events without building a [`Document`](crate::Document).
+//!
+//! Useful for very large files where only some parts of the document are needed.
+//! The same decoding as [`Document::parse_reader`](crate::Document::parse_reader) is used,
+//! so any encoding supported by the tree parser is supported here as well.
+//!
+//! # Example
+//! ```
+//! use edit_xml::{ReadOptions, events::{read_events, XmlEvent}};
+//!
+//! let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+//! <root>
+//!     <item id="1">First</item>
+//!     <item id="2" />
+//! </root>
+//! "#;
+//! let mut ids = Vec::new();
+//! for event in read_events(xml.as_bytes(), ReadOptions::default()) {
+//!     if let XmlEvent::StartElement { name, attributes, .. } = event.unwrap() {
+//!         if name == "item" {
+//!             ids.push(attributes["id"].clone());
+//!         }
+//!     }
+//! }
+//! assert_eq!(ids, vec!["1", "2"]);
+//! ```
+use std::collections::VecDeque;
+use std::io::Read;
+
+use quick_xml::{Reader, events::Event, name::QName};
+
+use crate::error::{EditXMLError, MalformedReason, Result};
+use crate::parser::{
+    DecodeReader, ReadOptions, collapse_whitespace, decl_encoding, decl_standalone, decl_version,
+    doctype_content, element_attributes, is_trivial_cdata, normalize_line_endings,
+    only_has_whitespace, start_reader,
+};
+use crate::types::StandaloneValue;
+use crate::utils::{HashMap, XMLStringUtils};
+
+/// An event read from an XML document.
+///
+/// All content is decoded into UTF-8 and unescaped in the same way as the tree parser.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum XmlEvent {
+    /// The XML declaration. Only emitted if it is the first item of the document.
+    Declaration {
+        version: String,
+        /// The label of the encoding declared in the document. The content is already decoded.
+        encoding: Option<String>,
+        standalone: Option<StandaloneValue>,
+    },
+    /// A start tag. `<tag />` emits a `StartElement` immediately followed by an `EndElement`.
+    StartElement {
+        /// Full name of the element, including its namespace prefix.
+        name: String,
+        /// Attributes excluding namespace declarations.
+        attributes: HashMap<String, String>,
+        /// `xmlns:prefix=namespace` declarations. Default namespace has empty string as key.
+        namespace_decls: HashMap<String, String>,
+    },
+    /// An end tag.
+    EndElement {
+        /// Full name of the element, including its namespace prefix.
+        name: String,
+    },
+    /// Character data.
+    ///
+    /// Adjacent text is not merged, even with [`ReadOptions::merge_adjacent_text`](crate::ReadOptions::merge_adjacent_text).
+    /// For example, an entity reference is emitted as its own `Text` event.
+    Text(String),
+    Comment(String),
+    CData(String),
+    PI(String),
+    DocType(String),
+}
+
+#[allow(
+    clippy::large_enum_variant,
+    reason = "Only one State exists per EventReader, and it is only replaced once"
+)]
+enum State<R: Read> {
+    NotStarted(R),
+    Reading(Reader<DecodeReader<R>>),
+    Finished,
+}
+
+/// Iterator over the [`XmlEvent`]s of a document. Created with [`read_events`].
+///
+/// The iterator ends after the first error.
+pub struct EventReader<R: Read> {
+    state: State<R>,
+    opts: ReadOptions,
+    buf: Vec<u8>,
+    /// Name of the element to emit an `EndElement` for, after an empty element.
+    pending_end: Option<String>,
+    /// Events read before the reader started, that haven't been emitted yet.
+    queued: VecDeque<XmlEvent>,
+    depth: usize,
+}
+
+/// Reads `reader` as a stream of [`XmlEvent`]s.
+///
+/// The following [`ReadOptions`] are respected: `trim_text`, `ignore_whitespace_only`, `text_mode`,
+/// `require_decl`, `allow_content_before_decl`, `encoding`, `default_namespace`, `normalize_attribute_value_space`, `collapse_empty_attributes`,
+/// `normalize_line_endings`, `collapse_trivial_cdata`, `case_insensitive_names`, `allow_duplicate_attributes`
+/// and `max_attributes_per_element`.
+/// With `ignore_unmatched_end_tags` or `case_insensitive_names`, end tags aren't checked against their start tags,
+/// but an end tag without any open element is still an error.
+/// `empty_text_node` is ignored, because `<tag></tag>` and `<tag />` can be told apart by the events.
+pub fn read_events<R: Read>(reader: R, opts: ReadOptions) -> EventReader<R> {
+    EventReader {
+        state: State::NotStarted(reader),
+        buf: Vec::with_capacity(opts.optimizations.parse_content_buffer_initial_capacity),
+        opts: opts.apply_text_mode(),
+        pending_end: None,
+        queued: VecDeque::new(),
+        depth: 0,
+    }
+}
+
+impl<R: Read> EventReader<R> {
+    fn start(&mut self, reader: R) -> Result<Option<XmlEvent>> {
+        let (xmlreader, (leading, event), _) = start_reader(reader, &self.opts)?;
+        self.state = State::Reading(xmlreader);
+        let event = if let Event::Decl(ev) = event {
+            let encoding = decl_encoding(&ev)?.map(|encoding| encoding.name().to_owned());
+            Some(XmlEvent::Declaration {
+                version: decl_version(&ev)?,
+                encoding,
+                standalone: decl_standalone(&ev)?,
+            })
+        } else if self.opts.require_decl {
+            return Err(MalformedReason::MissingDeclaration.into());
+        } else {
+            self.convert(event)?
+        };
+        if leading.is_empty() {
+            return Ok(event);
+        }
+        for leading in leading {
+            if let Some(leading) = self.convert(leading)? {
+                self.queued.push_back(leading);
+            }
+        }
+        self.queued.extend(event);
+        Ok(self.queued.pop_front())
+    }
+
+    fn read(&mut self) -> Result<Option<XmlEvent>> {
+        loop {
+            let State::Reading(reader) = &mut self.state else {
+                return Ok(None);
+            };
+            // Taken out of self, so the event can borrow it while self is mutated
+            let mut buf = std::mem::take(&mut self.buf);
+            buf.clear();
+            let event = reader.read_event_into(&mut buf)?;
+            if let Event::Eof = event {
+                self.state = State::Finished;
+                if self.depth != 0 {
+                    return Err(MalformedReason::MissingClosingTag.into());
+                }
+                return Ok(None);
+            }
+            let converted = self.convert(event);
+            self.buf = buf;
+            if let Some(event) = converted? {
+                return Ok(Some(event));
+            }
+        }
+    }
+
+    fn element_name(&self, name: QName) -> Result<String> {
+        let mut name = name.into_string()?;
+        if self.opts.case_insensitive_names {
+            name.make_ascii_lowercase();
+        }
+        Ok(name)
+    }
+
+    /// Returns `None` if the event should be skipped.
+    fn convert(&mut self, event: Event) -> Result<Option<XmlEvent>> {
+        let event = match event {
+            Event::Start(ev) => {
+                let (attributes, mut namespace_decls) =
+                    element_attributes(&ev, &self.opts, |name| name.into_owned())?;
+                if self.depth == 0 {
+                    self.opts.add_default_namespace(&mut namespace_decls);
+                }
+                self.depth += 1;
+                XmlEvent::StartElement {
+                    name: self.element_name(ev.name())?,
+                    attributes,
+                    namespace_decls,
+                }
+            }
+            Event::Empty(ev) => {
+                let (attributes, mut namespace_decls) =
+                    element_attributes(&ev, &self.opts, |name| name.into_owned())?;
+                if self.depth == 0 {
+                    self.opts.add_default_namespace(&mut namespace_decls);
+                }
+                let name = self.element_name(ev.name())?;
+                self.pending_end = Some(name.clone());
+                XmlEvent::StartElement {
+                    name,
+                    attributes,
+                    namespace_decls,
+                }
+            }
+            Event::End(ev) => {
+                self.depth = self.depth.checked_sub(1).ok_or(EditXMLError::MalformedXML(
+                    MalformedReason::GenericMalformedTree,
+                ))?;
+                XmlEvent::EndElement {
+                    name: self.element_name(ev.name())?,
+                }
+            }
+            Event::Text(ev) => {
+                if ev.is_empty() || (self.opts.ignore_whitespace_only && only_has_whitespace(&ev)) {
+                    return Ok(None);
+                }
+                let mut text = ev.unescape_to_string()?;
+                if self.opts.normalize_line_endings {
+                    text = normalize_line_endings(text);
+                }
+                if self.opts.collapse_whitespace() {
+                    text = collapse_whitespace(&text);
+                }
+                XmlEvent::Text(text)
+            }
+            Event::GeneralRef(ev) => XmlEvent::Text(ev.unescape_to_string()?),
+            Event::DocType(ev) => XmlEvent::DocType(doctype_content(&ev)?),
+            Event::Comment(ev) => {
+                XmlEvent::Comment(String::from_utf8(ev.escape_ascii().collect())?)
+            }
+            Event::CData(ev) => {
+                let content = String::from_utf8(ev.to_vec())?;
+                if self.opts.collapse_trivial_cdata && is_trivial_cdata(&content) {
+                    XmlEvent::Text(content)
+                } else {
+                    XmlEvent::CData(content)
+                }
+            }
+            Event::PI(ev) => XmlEvent::PI(ev.into_string()?),
+            Event::Decl(_) => return Err(MalformedReason::MisplacedDeclaration.into()),
+            Event::Eof => return Ok(None),
+        };
+        Ok(Some(event))
+    }
+}
+
+impl<R: Read> Iterator for EventReader<R> {
+    type Item = Result<XmlEvent>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(event) = self.queued.pop_front() {
+            return Some(Ok(event));
+        }
+        if let Some(name) = self.pending_end.take() {
+            return Some(Ok(XmlEvent::EndElement { name }));
+        }
+        let result = match std::mem::replace(&mut self.state, State::Finished) {
+            State::NotStarted(reader) => match self.start(reader) {
+                // The first event was skipped, e.g. whitespace only text
+                Ok(None) => self.read(),
+                result => result,
+            },
+            state @ State::Reading(_) => {
+                self.state = state;
+                self.read()
+            }
+            State::Finished => return None,
+        };
+        match result {
+            Ok(event) => event.map(Ok),
+            Err(err) => {
+                self.state = State::Finished;
+                Some(Err(err))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn collect(xml: &str, opts: ReadOptions) -> Result<Vec<XmlEvent>> {
+        read_events(xml.as_bytes(), opts).collect()
+    }
+
+    #[test]
+    fn test_events() {
+        let xml = r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+        <!DOCTYPE root>
+        <root xmlns:p="urn:p" attr="&quot;a&quot;">
+            Text &amp; more
+            <p:empty />
+            <!-- Comment -->
+            <![CDATA[<cdata>]]>
+            <?pi?>
+        </root>
+        "#;
+        let events = collect(xml, ReadOptions::default()).unwrap();
+        let mut attributes = HashMap::new();
+        attributes.insert("attr".to_owned(), "\"a\"".to_owned());
+        let mut namespace_decls = HashMap::new();
+        namespace_decls.insert("p".to_owned(), "urn:p".to_owned());
+        assert_eq!(
+            events,
+            vec![
+                XmlEvent::Declaration {
+                    version: "1.0".to_owned(),
+                    encoding: None,
+                    standalone: Some(StandaloneValue::Yes),
+                },
+                XmlEvent::DocType("root".to_owned()),
+                XmlEvent::StartElement {
+                    name: "root".to_owned(),
+                    attributes,
+                    namespace_decls,
+                },
+                XmlEvent::Text("Text".to_owned()),
+                XmlEvent::Text("&".to_owned()),
+                XmlEvent::Text("more".to_owned()),
+                XmlEvent::StartElement {
+                    name: "p:empty".to_owned(),
+                    attributes: HashMap::new(),
+                    namespace_decls: HashMap::new(),
+                },
+                XmlEvent::EndElement {
+                    name: "p:empty".to_owned()
+                },
+                XmlEvent::Comment(" Comment ".to_owned()),
+                XmlEvent::CData("<cdata>".to_owned()),
+                XmlEvent::PI("pi".to_owned()),
+                XmlEvent::EndElement {
+                    name: "root".to_owned()
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        assert!(matches!(
+            collect("<root />", ReadOptions::default()),
+            Err(EditXMLError::MalformedXML(
+                MalformedReason::MissingDeclaration
+            ))
+        ));
+        let events = collect("<root />", ReadOptions::relaxed()).unwrap();
+        assert_eq!(events.len(), 2);
+        assert!(matches!(
+            collect("<root>", ReadOptions::relaxed()),
+            Err(EditXMLError::MalformedXML(
+                MalformedReason::MissingClosingTag
+            ))
+        ));
+        assert!(collect("<a></b>", ReadOptions::relaxed()).is_err());
+
+        // The iterator ends after an error
+        let mut events = read_events("<a></b><c />".as_bytes(), ReadOptions::relaxed());
+        assert!(events.next().unwrap().is_ok());
+        assert!(events.next().unwrap().is_err());
+        assert!(events.next().is_none());
+    }
+}
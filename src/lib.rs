@@ -60,17 +60,29 @@
 //! doc.write_file(&xml_file);
 //! ```
 //!
+mod diff;
 mod document;
 mod element;
 mod error;
+pub mod events;
+mod name;
 mod parser;
 pub mod types;
 pub mod utils;
+mod validate;
+mod visitor;
 
+pub use crate::diff::DiffOp;
 pub use crate::document::*;
 pub use crate::element::*;
 pub use crate::error::*;
-pub use crate::parser::{ReadOptions, normalize_space};
+pub use crate::name::Name;
+pub use crate::parser::{
+    ParseResult, ParseWarning, ReadOptions, ReadOptionsBuilder, ReadOptionsOptimizations, TextMode,
+    normalize_space,
+};
+pub use crate::validate::{ChildSpec, StructureError, StructureSpec};
+pub use crate::visitor::{VisitControl, Visitor};
 
 // Re-export quick-xml for convenience
 pub use quick_xml;
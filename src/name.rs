@@ -0,0 +1,136 @@
+use std::borrow::Borrow;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::ops::Deref;
+use std::sync::Arc;
+
+/// The name of an element or attribute. Used as the key of [`Element::attributes`](crate::Element::attributes).
+///
+/// It derefs to `str`, and is created from a `String` or `&str` with `.into()`.
+/// Names parsed with [`ReadOptions::intern_names`](crate::ReadOptions::intern_names)
+/// share one allocation with the other names that are equal to them. Otherwise each name owns its own `String`.
+///
+/// ```
+/// use edit_xml::{Document, Element};
+///
+/// let mut doc = Document::new();
+/// let elem = Element::build("rect").attribute("width", "10").finish(&mut doc);
+/// elem.mut_attributes(&mut doc).insert("height".into(), "20".to_owned());
+/// for (name, value) in elem.attributes(&doc) {
+///     let name: &str = name;
+///     assert!(name == "width" || name == "height");
+/// }
+/// ```
+#[derive(Clone)]
+pub struct Name(Repr);
+
+#[derive(Clone)]
+enum Repr {
+    Owned(String),
+    Shared(Arc<str>),
+}
+
+impl Name {
+    /// A name that shares its allocation with `name`.
+    pub(crate) fn shared(name: Arc<str>) -> Self {
+        Self(Repr::Shared(name))
+    }
+    pub fn as_str(&self) -> &str {
+        match &self.0 {
+            Repr::Owned(name) => name,
+            Repr::Shared(name) => name,
+        }
+    }
+    /// Returns `true` if both names point to the same allocation.
+    #[cfg(test)]
+    pub(crate) fn shares_allocation(&self, other: &Name) -> bool {
+        std::ptr::eq(self.as_str(), other.as_str())
+    }
+}
+
+impl Default for Name {
+    fn default() -> Self {
+        Self(Repr::Owned(String::new()))
+    }
+}
+impl Deref for Name {
+    type Target = str;
+    fn deref(&self) -> &str {
+        self.as_str()
+    }
+}
+impl AsRef<str> for Name {
+    fn as_ref(&self) -> &str {
+        self.as_str()
+    }
+}
+impl Borrow<str> for Name {
+    fn borrow(&self) -> &str {
+        self.as_str()
+    }
+}
+// Hash and Eq must match `str` for `Borrow<str>`
+impl Hash for Name {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.as_str().hash(state);
+    }
+}
+impl PartialEq for Name {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_str() == other.as_str()
+    }
+}
+impl Eq for Name {}
+impl PartialEq<str> for Name {
+    fn eq(&self, other: &str) -> bool {
+        self.as_str() == other
+    }
+}
+impl PartialEq<&str> for Name {
+    fn eq(&self, other: &&str) -> bool {
+        self.as_str() == *other
+    }
+}
+impl PartialEq<String> for Name {
+    fn eq(&self, other: &String) -> bool {
+        self.as_str() == other
+    }
+}
+impl PartialOrd for Name {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+impl Ord for Name {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.as_str().cmp(other.as_str())
+    }
+}
+impl fmt::Debug for Name {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self.as_str(), f)
+    }
+}
+impl fmt::Display for Name {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self.as_str(), f)
+    }
+}
+impl From<String> for Name {
+    fn from(name: String) -> Self {
+        Self(Repr::Owned(name))
+    }
+}
+impl From<&str> for Name {
+    fn from(name: &str) -> Self {
+        Self(Repr::Owned(name.to_owned()))
+    }
+}
+impl From<Name> for String {
+    fn from(name: Name) -> Self {
+        match name.0 {
+            Repr::Owned(name) => name,
+            Repr::Shared(name) => name.as_ref().to_owned(),
+        }
+    }
+}
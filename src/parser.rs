@@ -1,14 +1,18 @@
 use crate::document::{Document, Node};
 use crate::element::Element;
 use crate::error::{DecodeError, EditXMLError, MalformedReason, Result};
+use crate::name::Name;
 use crate::types::StandaloneValue;
-use crate::utils::{HashMap, attributes, bytes_owned_to_unescaped_string};
-use crate::utils::{XMLStringUtils, bytes_to_unescaped_string};
+use crate::utils::{HashMap, HashSet, attributes, bytes_owned_to_unescaped_string};
+use crate::utils::{XMLStringUtils, bytes_to_unescaped_string, resolve_known_entity};
 use encoding_rs::Decoder;
 use encoding_rs::{Encoding, UTF_8, UTF_16BE, UTF_16LE};
 use quick_xml::Reader;
-use quick_xml::events::{BytesDecl, BytesStart, Event};
+use quick_xml::events::{BytesDecl, BytesStart, BytesText, Event};
+use std::borrow::Cow;
+use std::hash::Hash;
 use std::io::{BufRead, Read};
+use std::sync::Arc;
 
 pub(crate) struct DecodeReader<R: Read> {
     decoder: Option<Decoder>,
@@ -16,10 +20,17 @@ pub(crate) struct DecodeReader<R: Read> {
     undecoded: Box<[u8]>,
     undecoded_pos: usize,
     undecoded_cap: usize,
-    remaining: [u8; 32], // Is there an encoding with > 32 bytes for a char?
+    /// Undecoded bytes moved to the start of `undecoded` before reading more.
+    ///
+    /// The decoder keeps an incomplete character itself, so bytes are only left over
+    /// when `decoded` was full. At the end of input, the decoder is flushed and an incomplete
+    /// character becomes U+FFFD.
+    remaining: [u8; 32],
     decoded: Box<[u8]>,
     decoded_pos: usize,
     decoded_cap: usize,
+    /// Position in `undecoded` that the contents of `decoded` were decoded from.
+    decoded_from: usize,
     done: bool,
 }
 
@@ -36,15 +47,41 @@ impl<R: Read> DecodeReader<R> {
             decoded: vec![0; 12288].into_boxed_slice(),
             decoded_pos: 0,
             decoded_cap: 0,
+            decoded_from: 0,
             done: false,
         }
     }
 
+    /// Changes the encoding of the bytes that haven't been consumed yet.
+    ///
+    /// Bytes that were already decoded but not consumed are discarded,
+    /// and read again with the new encoding.
     pub(crate) fn set_encoding(&mut self, encoding: Option<&'static Encoding>) {
+        if let Some(decoder) = &self.decoder {
+            self.undecoded_pos = self.decoded_from + self.undecoded_len(decoder.encoding());
+            self.decoded_pos = 0;
+            self.decoded_cap = 0;
+        }
         self.decoder = encoding.map(|e| e.new_decoder_without_bom_handling());
         self.done = false;
     }
 
+    /// Number of bytes from `decoded_from` that were decoded into the consumed part of `decoded`.
+    fn undecoded_len(&self, encoding: &'static Encoding) -> usize {
+        let mut decoder = encoding.new_decoder_without_bom_handling();
+        let mut written = 0;
+        let mut out = [0; 32];
+        let mut len = 0;
+        let undecoded = &self.undecoded[self.decoded_from..self.undecoded_cap];
+        while written < self.decoded_pos && len < undecoded.len() {
+            let (_res, _read, n, _replaced) =
+                decoder.decode_to_utf8(&undecoded[len..len + 1], &mut out, false);
+            written += n;
+            len += 1;
+        }
+        len
+    }
+
     // Call this only when decoder is Some
     fn fill_buf_decode(&mut self) -> std::io::Result<&[u8]> {
         if self.decoded_pos >= self.decoded_cap {
@@ -66,6 +103,7 @@ impl<R: Read> DecodeReader<R> {
             }
 
             // Fill decoded buffer
+            self.decoded_from = self.undecoded_pos;
             let (_res, read, written, _replaced) = self.decoder.as_mut().unwrap().decode_to_utf8(
                 &self.undecoded[self.undecoded_pos..self.undecoded_cap],
                 &mut self.decoded,
@@ -114,6 +152,8 @@ impl<R: Read> BufRead for DecodeReader<R> {
     }
 }
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
 pub struct ReadOptionsOptimizations {
     pub reader_stack_initial_capacity: usize,
     pub document_initial_capacity: usize,
@@ -134,8 +174,43 @@ impl Default for ReadOptionsOptimizations {
         }
     }
 }
+/// How whitespace in text is handled when parsing. Set with [`ReadOptions::text_mode`].
+///
+/// Each mode sets `empty_text_node`, `trim_text` and `ignore_whitespace_only`,
+/// which can be hard to combine correctly by hand.
+///
+/// | Mode            | `empty_text_node` | `trim_text` | `ignore_whitespace_only` |
+/// |-----------------|-------------------|-------------|--------------------------|
+/// | [`Raw`]         | `true`            | `false`     | `false`                  |
+/// | [`Trimmed`]     | `true`            | `true`      | `false`                  |
+/// | [`Collapsed`]   | `true`            | `false`     | `true`                   |
+/// | [`Significant`] | `false`           | `false`     | `true`                   |
+///
+/// [`Raw`]: TextMode::Raw
+/// [`Trimmed`]: TextMode::Trimmed
+/// [`Collapsed`]: TextMode::Collapsed
+/// [`Significant`]: TextMode::Significant
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum TextMode {
+    /// Keep all text as written, including whitespace between elements.
+    Raw,
+    /// Trim whitespace at the start and end of each text node, and drop text nodes that become empty.
+    /// This is what the default options do.
+    Trimmed,
+    /// Drop whitespace only text nodes, and replace each run of whitespace in other text with a single space.
+    /// Whitespace from character references like `&#32;` is kept.
+    Collapsed,
+    /// Drop whitespace only text nodes, and keep other text as written.
+    /// `<tag></tag>` has no children, like `<tag />`.
+    Significant,
+}
 /// Options when parsing xml.
+///
+/// Use [`ReadOptions::builder`] to create options that differ from the defaults.
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
 pub struct ReadOptions {
     /// `<tag></tag>` will have a Node::Text("") as its children, while `<tag />` won't.
     /// Default: `true`
@@ -147,17 +222,109 @@ pub struct ReadOptions {
     /// Only makes sense if `trim_text` is `false`. (If both are `true`, performance takes a hit for no gain)
     /// Default: `false`
     pub ignore_whitespace_only: bool,
+    /// Full names of elements whose text is never trimmed, even if `trim_text` is `true`.
+    /// This applies to all text inside them, including the text of their descendants.
+    ///
+    /// Names are compared after `case_insensitive_names` is applied. Use lowercase names with that option.
+    /// Default: empty
+    pub preserve_whitespace_in: std::collections::HashSet<String>,
+    /// Sets `empty_text_node`, `trim_text` and `ignore_whitespace_only` at once, overriding their values.
+    /// See [`TextMode`]. Leave it as `None` to use the individual options.
+    /// Default: `None`
+    pub text_mode: Option<TextMode>,
     /// Returns error if document doesn't start with XML declaration.
     /// If there is no XML declaration, the parser won't be able to decode encodings other than UTF-8, unless `encoding` below is set.
     /// Default: `true`
     pub require_decl: bool,
+    /// Allow comments, processing instructions and whitespace before the XML declaration.
+    /// They are parsed as root nodes, and the declaration is still read, including its encoding.
+    ///
+    /// This isn't well-formed XML, but some tools write it.
+    /// Default: `false`
+    pub allow_content_before_decl: bool,
     /// If this is set, the parser will start reading with this encoding.
     /// But it will switch to XML declaration's encoding value if it has a different value.
+    /// A declaration without an encoding value doesn't change it.
     /// See [`encoding_rs::Encoding::for_label`] for valid values.
     /// Default: `None`
     pub encoding: Option<String>,
+    /// Declare this as the default namespace (`xmlns="..."`) on the root element,
+    /// unless it already declares one. Unprefixed elements then resolve to it.
+    ///
+    /// This is a non-standard convenience for documents that are missing their namespace declaration,
+    /// like SVG fragments. The declaration is part of the parsed document, so it is written back out.
+    /// Default: `None`
+    pub default_namespace: Option<String>,
 
+    /// Normalize attribute values with [`normalize_space`].
+    /// An attribute value of only whitespace becomes `""`, or `" "` if `collapse_empty_attributes` is `false`.
+    /// Default: `false`
     pub normalize_attribute_value_space: bool,
+    /// With `normalize_attribute_value_space`, an attribute value of only whitespace (spaces, tabs and newlines)
+    /// becomes `""`. If this is `false`, it becomes a single space instead.
+    ///
+    /// Without `normalize_attribute_value_space`, attribute values are left as-is and this has no effect.
+    /// Default: `true`
+    pub collapse_empty_attributes: bool,
+    /// Record the position of each element in the input. See [`Element::source_position`].
+    /// Default: `false`
+    pub track_positions: bool,
+    /// Allow a start tag to have multiple attributes (or namespace declarations) with the same name.
+    /// The last value wins. Otherwise, [`MalformedReason::DuplicateAttribute`] is returned.
+    /// Default: `false`
+    pub allow_duplicate_attributes: bool,
+    /// Replace `\r\n` and `\r` with `\n` in text, as if the line endings were normalized before parsing.
+    /// A `\r` written as the character reference `&#13;` is kept.
+    /// CDATA sections, comments and processing instructions are left unchanged.
+    /// Default: `true`
+    pub normalize_line_endings: bool,
+    /// Parse a CDATA section that doesn't contain `<`, `>`, `&` or `\r` as `Node::Text`.
+    /// Other CDATA sections are kept as `Node::CData`.
+    /// The collapsed text is merged with the text around it, unless `merge_adjacent_text` is `false`.
+    ///
+    /// Only the content matters, e.g. `<![CDATA[a b]]>` becomes text but `<![CDATA[a&b]]>` doesn't.
+    /// The collapsed text is written back escaped, not as a CDATA section.
+    /// Default: `false`
+    pub collapse_trivial_cdata: bool,
+    /// Merge consecutive text, including entity and character references, into a single `Node::Text`.
+    ///
+    /// If this is `false`, references are separate text nodes, so `a&amp;b` is parsed as
+    /// the three text nodes `a`, `&` and `b`.
+    /// Default: `true`
+    pub merge_adjacent_text: bool,
+    /// Discard an end tag that doesn't close the current element, instead of returning an error.
+    /// This includes end tags without any open element, like `</x>` in `<a /></x>`.
+    /// Default: `false`
+    pub ignore_unmatched_end_tags: bool,
+    /// Convert element and attribute names, including namespace prefixes, to ASCII lowercase.
+    /// End tags then match their start tags regardless of case, e.g. `<Div></div>`.
+    ///
+    /// The original case of names is lost, and is not restored when writing the document.
+    /// Use [`Element::find_ignore_case`] to look up an element without changing its name.
+    /// Default: `false`
+    pub case_insensitive_names: bool,
+    /// Share one allocation between elements and attributes with the same name, instead of allocating each name.
+    ///
+    /// Reduces memory use for large documents that repeat the same element and attribute names.
+    /// Renaming an element or setting an attribute later gives it its own name again. See [`Name`]
+    /// Default: `false`
+    pub intern_names: bool,
+    /// Collect [`ParseWarning`]s for problems that don't stop parsing.
+    /// Use [`Document::parse_str_verbose_with_opts`] to get them. Other parse methods discard them.
+    /// Default: `false`
+    pub collect_warnings: bool,
+    /// Maximum number of elements in the document.
+    /// [`EditXMLError::LimitExceeded`] is returned if the document has more elements.
+    /// Default: `None`
+    pub max_elements: Option<usize>,
+    /// Maximum total bytes of text and CDATA content, after unescaping.
+    /// [`EditXMLError::LimitExceeded`] is returned if the document has more text.
+    /// Default: `None`
+    pub max_text_bytes: Option<usize>,
+    /// Maximum number of attributes in a single start tag, including namespace declarations.
+    /// [`EditXMLError::LimitExceeded`] is returned if an element has more attributes.
+    /// Default: `None`
+    pub max_attributes_per_element: Option<usize>,
 
     pub optimizations: ReadOptionsOptimizations,
 }
@@ -168,9 +335,26 @@ impl ReadOptions {
             empty_text_node: true,
             trim_text: true,
             ignore_whitespace_only: true,
+            text_mode: None,
+            preserve_whitespace_in: std::collections::HashSet::new(),
             require_decl: false,
+            allow_content_before_decl: false,
             encoding: None,
+            default_namespace: None,
             normalize_attribute_value_space: false,
+            collapse_empty_attributes: true,
+            allow_duplicate_attributes: false,
+            track_positions: false,
+            normalize_line_endings: true,
+            collapse_trivial_cdata: false,
+            merge_adjacent_text: true,
+            ignore_unmatched_end_tags: false,
+            case_insensitive_names: false,
+            intern_names: false,
+            collect_warnings: false,
+            max_elements: None,
+            max_text_bytes: None,
+            max_attributes_per_element: None,
             optimizations: ReadOptionsOptimizations::default(),
         }
     }
@@ -181,137 +365,501 @@ impl Default for ReadOptions {
             empty_text_node: true,
             trim_text: true,
             ignore_whitespace_only: false,
+            text_mode: None,
+            preserve_whitespace_in: std::collections::HashSet::new(),
             require_decl: true,
+            allow_content_before_decl: false,
             encoding: None,
+            default_namespace: None,
             normalize_attribute_value_space: false,
+            collapse_empty_attributes: true,
+            allow_duplicate_attributes: false,
+            track_positions: false,
+            normalize_line_endings: true,
+            collapse_trivial_cdata: false,
+            merge_adjacent_text: true,
+            ignore_unmatched_end_tags: false,
+            case_insensitive_names: false,
+            intern_names: false,
+            collect_warnings: false,
+            max_elements: None,
+            max_text_bytes: None,
+            max_attributes_per_element: None,
             optimizations: ReadOptionsOptimizations::default(),
         }
     }
 }
 
-//TODO: don't unwrap element_stack.last() or pop(). Invalid XML file can crash the software.
+impl ReadOptions {
+    /// Chain methods to build [`ReadOptions`], starting from the default options.
+    ///
+    /// ```
+    /// use edit_xml::ReadOptions;
+    ///
+    /// let opts = ReadOptions::builder()
+    ///     .require_decl(false)
+    ///     .ignore_whitespace_only(true)
+    ///     .build();
+    /// assert_eq!(opts, ReadOptions::relaxed());
+    ///
+    /// let opts = ReadOptions::builder()
+    ///     .encoding("UTF-16")
+    ///     .max_elements(1000)
+    ///     .build();
+    /// assert_eq!(opts.encoding.as_deref(), Some("UTF-16"));
+    /// ```
+    pub fn builder() -> ReadOptionsBuilder {
+        ReadOptionsBuilder::default()
+    }
+
+    /// Sets the individual text options from `text_mode`, if it is set.
+    pub(crate) fn apply_text_mode(mut self) -> Self {
+        let (empty_text_node, trim_text, ignore_whitespace_only) = match self.text_mode {
+            None => return self,
+            Some(TextMode::Raw) => (true, false, false),
+            Some(TextMode::Trimmed) => (true, true, false),
+            Some(TextMode::Collapsed) => (true, false, true),
+            Some(TextMode::Significant) => (false, false, true),
+        };
+        self.empty_text_node = empty_text_node;
+        self.trim_text = trim_text;
+        self.ignore_whitespace_only = ignore_whitespace_only;
+        self
+    }
+
+    /// Adds [`ReadOptions::default_namespace`] to the namespace declarations of a root element.
+    pub(crate) fn add_default_namespace(&self, namespace_decls: &mut HashMap<String, String>) {
+        if let Some(namespace) = &self.default_namespace {
+            namespace_decls
+                .entry(String::new())
+                .or_insert_with(|| namespace.clone());
+        }
+    }
+
+    /// Whether runs of whitespace in text are replaced with a single space. See [`TextMode::Collapsed`]
+    pub(crate) fn collapse_whitespace(&self) -> bool {
+        self.text_mode == Some(TextMode::Collapsed)
+    }
+}
+/// Builder for [`ReadOptions`]. Created with [`ReadOptions::builder`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ReadOptionsBuilder {
+    opts: ReadOptions,
+}
+impl From<ReadOptions> for ReadOptionsBuilder {
+    fn from(opts: ReadOptions) -> Self {
+        Self { opts }
+    }
+}
+impl ReadOptionsBuilder {
+    /// See [`ReadOptions::empty_text_node`]
+    pub fn empty_text_node(mut self, empty_text_node: bool) -> Self {
+        self.opts.empty_text_node = empty_text_node;
+        self
+    }
+    /// See [`ReadOptions::trim_text`]
+    pub fn trim_text(mut self, trim_text: bool) -> Self {
+        self.opts.trim_text = trim_text;
+        self
+    }
+    /// See [`ReadOptions::ignore_whitespace_only`]
+    pub fn ignore_whitespace_only(mut self, ignore_whitespace_only: bool) -> Self {
+        self.opts.ignore_whitespace_only = ignore_whitespace_only;
+        self
+    }
+    /// Adds to [`ReadOptions::preserve_whitespace_in`]
+    pub fn preserve_whitespace_in<I, S>(mut self, names: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.opts
+            .preserve_whitespace_in
+            .extend(names.into_iter().map(Into::into));
+        self
+    }
+    /// See [`ReadOptions::text_mode`]
+    pub fn text_mode(mut self, text_mode: TextMode) -> Self {
+        self.opts.text_mode = Some(text_mode);
+        self
+    }
+    /// See [`ReadOptions::require_decl`]
+    pub fn require_decl(mut self, require_decl: bool) -> Self {
+        self.opts.require_decl = require_decl;
+        self
+    }
+    /// See [`ReadOptions::allow_content_before_decl`]
+    pub fn allow_content_before_decl(mut self, allow_content_before_decl: bool) -> Self {
+        self.opts.allow_content_before_decl = allow_content_before_decl;
+        self
+    }
+    /// See [`ReadOptions::normalize_attribute_value_space`]
+    pub fn normalize_attribute_value_space(
+        mut self,
+        normalize_attribute_value_space: bool,
+    ) -> Self {
+        self.opts.normalize_attribute_value_space = normalize_attribute_value_space;
+        self
+    }
+    /// See [`ReadOptions::normalize_line_endings`]
+    pub fn normalize_line_endings(mut self, normalize_line_endings: bool) -> Self {
+        self.opts.normalize_line_endings = normalize_line_endings;
+        self
+    }
+    /// See [`ReadOptions::collapse_trivial_cdata`]
+    pub fn collapse_trivial_cdata(mut self, collapse_trivial_cdata: bool) -> Self {
+        self.opts.collapse_trivial_cdata = collapse_trivial_cdata;
+        self
+    }
+    /// See [`ReadOptions::merge_adjacent_text`]
+    pub fn merge_adjacent_text(mut self, merge_adjacent_text: bool) -> Self {
+        self.opts.merge_adjacent_text = merge_adjacent_text;
+        self
+    }
+    /// See [`ReadOptions::ignore_unmatched_end_tags`]
+    pub fn ignore_unmatched_end_tags(mut self, ignore_unmatched_end_tags: bool) -> Self {
+        self.opts.ignore_unmatched_end_tags = ignore_unmatched_end_tags;
+        self
+    }
+    /// See [`ReadOptions::case_insensitive_names`]
+    pub fn case_insensitive_names(mut self, case_insensitive_names: bool) -> Self {
+        self.opts.case_insensitive_names = case_insensitive_names;
+        self
+    }
+    /// See [`ReadOptions::intern_names`]
+    pub fn intern_names(mut self, intern_names: bool) -> Self {
+        self.opts.intern_names = intern_names;
+        self
+    }
+    /// See [`ReadOptions::collect_warnings`]
+    pub fn collect_warnings(mut self, collect_warnings: bool) -> Self {
+        self.opts.collect_warnings = collect_warnings;
+        self
+    }
+    /// See [`ReadOptions::collapse_empty_attributes`]
+    pub fn collapse_empty_attributes(mut self, collapse_empty_attributes: bool) -> Self {
+        self.opts.collapse_empty_attributes = collapse_empty_attributes;
+        self
+    }
+    /// See [`ReadOptions::track_positions`]
+    pub fn track_positions(mut self, track_positions: bool) -> Self {
+        self.opts.track_positions = track_positions;
+        self
+    }
+    /// See [`ReadOptions::allow_duplicate_attributes`]
+    pub fn allow_duplicate_attributes(mut self, allow_duplicate_attributes: bool) -> Self {
+        self.opts.allow_duplicate_attributes = allow_duplicate_attributes;
+        self
+    }
+    /// See [`ReadOptions::encoding`]
+    pub fn encoding<S: Into<String>>(mut self, encoding: S) -> Self {
+        self.opts.encoding = Some(encoding.into());
+        self
+    }
+    /// See [`ReadOptions::default_namespace`]
+    pub fn default_namespace<S: Into<String>>(mut self, default_namespace: S) -> Self {
+        self.opts.default_namespace = Some(default_namespace.into());
+        self
+    }
+    /// See [`ReadOptions::max_elements`]
+    pub fn max_elements(mut self, max_elements: usize) -> Self {
+        self.opts.max_elements = Some(max_elements);
+        self
+    }
+    /// See [`ReadOptions::max_text_bytes`]
+    pub fn max_text_bytes(mut self, max_text_bytes: usize) -> Self {
+        self.opts.max_text_bytes = Some(max_text_bytes);
+        self
+    }
+    /// See [`ReadOptions::max_attributes_per_element`]
+    pub fn max_attributes_per_element(mut self, max_attributes_per_element: usize) -> Self {
+        self.opts.max_attributes_per_element = Some(max_attributes_per_element);
+        self
+    }
+    /// See [`ReadOptions::optimizations`]
+    pub fn optimizations(mut self, optimizations: ReadOptionsOptimizations) -> Self {
+        self.opts.optimizations = optimizations;
+        self
+    }
+    pub fn build(self) -> ReadOptions {
+        self.opts
+    }
+}
+
+/// A problem found while parsing that didn't stop it. Collected with [`ReadOptions::collect_warnings`].
+///
+/// `position` is the byte offset of the markup's `<` or `&` in the parsed input,
+/// counted like [`Element::source_position`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseWarning {
+    /// An entity reference that isn't known, in text or an attribute value.
+    ///
+    /// It is kept as is. This only happens with the `soft-fail-unescape` feature, otherwise it is an error.
+    UnknownEntity { name: String, position: usize },
+    /// An end tag that was discarded, because of [`ReadOptions::ignore_unmatched_end_tags`].
+    IgnoredEndTag { name: String, position: usize },
+}
+
+/// A parsed [`Document`] and the [`ParseWarning`]s found while parsing it.
+/// Returned by [`Document::parse_str_verbose`].
+#[derive(Debug)]
+pub struct ParseResult {
+    pub document: Document,
+    pub warnings: Vec<ParseWarning>,
+}
+
 pub(crate) struct DocumentParser {
     doc: Document,
     read_opts: ReadOptions,
-    encoding: Option<&'static Encoding>,
     element_stack: Vec<Element>,
+    /// See [`start_reader`]
+    position_offset: u64,
+    /// Position just after the event being handled.
+    event_end_position: u64,
+    element_count: usize,
+    text_bytes: usize,
+    /// Element and attribute names seen so far. Only used with [`ReadOptions::intern_names`]
+    names: HashSet<Arc<str>>,
+    /// Only used with [`ReadOptions::collect_warnings`]
+    warnings: Vec<ParseWarning>,
+    /// Number of open elements listed in [`ReadOptions::preserve_whitespace_in`]
+    preserving_whitespace: usize,
 }
 
 impl DocumentParser {
-    pub(crate) fn parse_reader<R: Read>(reader: R, opts: ReadOptions) -> Result<Document> {
+    fn new(opts: ReadOptions) -> Self {
+        let opts = opts.apply_text_mode();
         let doc = Document::new_with_store_size(opts.optimizations.document_initial_capacity);
         let mut element_stack =
             Vec::with_capacity(opts.optimizations.reader_stack_initial_capacity);
         element_stack.push(Element::container().0);
-        let mut parser = DocumentParser {
+        DocumentParser {
             doc,
             read_opts: opts,
-            encoding: None,
             element_stack,
-        };
-        parser.parse_start(reader)?;
+            position_offset: 0,
+            event_end_position: 0,
+            element_count: 0,
+            text_bytes: 0,
+            names: HashSet::new(),
+            warnings: Vec::new(),
+            preserving_whitespace: 0,
+        }
+    }
+
+    pub(crate) fn parse_reader<R: Read>(reader: R, opts: ReadOptions) -> Result<Document> {
+        let mut parser = Self::new(opts);
+        let (xmlreader, (leading, event), position_offset) =
+            start_reader(reader, &parser.read_opts)?;
+        parser.position_offset = position_offset;
+        parser.parse_start(xmlreader, leading, event)?;
+        Ok(parser.doc)
+    }
+
+    /// Parses a `str`, which is already valid UTF-8, without decoding it.
+    pub(crate) fn parse_str(str: &str, opts: ReadOptions) -> Result<Document> {
+        let mut parser = Self::new(opts);
+        let (xmlreader, (leading, event)) = start_str_reader(str, &parser.read_opts)?;
+        parser.parse_start(xmlreader, leading, event)?;
         Ok(parser.doc)
     }
 
+    /// [`DocumentParser::parse_str`] that also returns the collected warnings.
+    pub(crate) fn parse_str_verbose(str: &str, opts: ReadOptions) -> Result<ParseResult> {
+        let mut parser = Self::new(opts);
+        let (xmlreader, (leading, event)) = start_str_reader(str, &parser.read_opts)?;
+        parser.parse_start(xmlreader, leading, event)?;
+        Ok(ParseResult {
+            document: parser.doc,
+            warnings: parser.warnings,
+        })
+    }
+
+    fn preserves_whitespace(&self, element: Element) -> bool {
+        !self.read_opts.preserve_whitespace_in.is_empty()
+            && self
+                .read_opts
+                .preserve_whitespace_in
+                .contains(element.full_name(&self.doc))
+    }
+
+    fn warn(&mut self, warning: ParseWarning) {
+        #[cfg(feature = "tracing")]
+        tracing::warn!(?warning, "Parse warning");
+        if self.read_opts.collect_warnings {
+            self.warnings.push(warning);
+        }
+    }
+
     fn handle_decl(&mut self, ev: &BytesDecl) -> Result<()> {
-        self.doc.version = String::from_utf8(ev.version()?.to_vec())?;
-        self.encoding = match ev.encoding() {
-            Some(res) => {
-                let encoding = Encoding::for_label(&res?).ok_or(DecodeError::MissingEncoding)?;
-                if encoding == UTF_8 {
-                    None
-                } else {
-                    Some(encoding)
-                }
-            }
-            None => None,
-        };
-        self.doc.standalone = match ev.standalone() {
-            Some(res) => {
-                let standalone_value = res?;
-                Some(StandaloneValue::try_from(standalone_value.as_ref())?)
-            }
+        self.doc.version = decl_version(ev)?;
+        self.doc.standalone = decl_standalone(ev)?;
+        self.doc.declared_encoding = match ev.encoding() {
+            Some(encoding) => Some(String::from_utf8(encoding?.into_owned())?),
             None => None,
         };
         Ok(())
     }
-    #[inline(always)]
-    fn element_attributes(
-        &self,
+    /// Create a new element and push it to the parent element.
+    ///
+    /// `tag_len` is the length of the raw tag excluding its content. (`<>` or `</>`)
+    fn create_element(
+        &mut self,
+        parent: Element,
         ev: &BytesStart,
-    ) -> Result<(HashMap<String, String>, HashMap<String, String>)> {
-        let mut attributes =
-            HashMap::with_capacity(self.read_opts.optimizations.attribute_initial_capacity);
-        let mut namespace_decls =
-            HashMap::with_capacity(self.read_opts.optimizations.namespace_initial_capacity);
-
-        for attr in ev.attributes() {
-            let attr = attr?;
-            // Key is converted to string.
-            let (key, prefix) = attr.key.decompose();
-            let value = if self.read_opts.normalize_attribute_value_space {
-                let value = normalize_space(&attr.value);
-                bytes_owned_to_unescaped_string(value)?
-            } else {
-                bytes_to_unescaped_string(&attr.value)?
-            };
-
-            if prefix.map(attributes::is_xlmns).unwrap_or(false) {
-                // Has a prefix of `xmlns` so it is going in
-                let key = key.into_string()?;
-                namespace_decls.insert(key, value);
-            } else if attributes::is_xlmns(key) {
-                // The attribute is just `xmlns` meaning it is empty string
-                namespace_decls.insert(String::default(), value);
-            } else {
-                let key = attr.key.into_string()?;
-                attributes.insert(key, value);
+        tag_len: usize,
+    ) -> Result<Element> {
+        self.element_count += 1;
+        if let Some(max) = self
+            .read_opts
+            .max_elements
+            .filter(|max| self.element_count > *max)
+        {
+            return Err(EditXMLError::LimitExceeded {
+                limit: "max_elements",
+                max,
+            });
+        }
+        let mut full_name = Cow::Borrowed(std::str::from_utf8(ev.name().into_inner())?);
+        if self.read_opts.case_insensitive_names {
+            full_name.to_mut().make_ascii_lowercase();
+        }
+        let full_name = intern_name(
+            self.read_opts.intern_names.then_some(&mut self.names),
+            full_name,
+        );
+        let (attributes, mut namespace_decls) = element_attributes(ev, &self.read_opts, |name| {
+            intern_name(self.read_opts.intern_names.then_some(&mut self.names), name)
+        })?;
+        if parent.is_container() {
+            self.read_opts.add_default_namespace(&mut namespace_decls);
+        }
+        let elem = Element::with_data(&mut self.doc, full_name, attributes, namespace_decls);
+        let start = (self.event_end_position as usize).saturating_sub(ev.len() + tag_len);
+        if self.read_opts.track_positions {
+            elem.set_source_position(&mut self.doc, start);
+        }
+        if self.read_opts.collect_warnings {
+            for attr in ev.attributes().with_checks(false).flatten() {
+                let Ok(value) = std::str::from_utf8(&attr.value) else {
+                    continue;
+                };
+                for name in unknown_entities(value) {
+                    self.warn(ParseWarning::UnknownEntity {
+                        name: name.to_owned(),
+                        position: start,
+                    });
+                }
             }
         }
-        Ok((attributes, namespace_decls))
+        self.push_node(parent, Node::Element(elem))?;
+        Ok(elem)
     }
-    /// Create a new element and push it to the parent element.
-    fn create_element(&mut self, parent: Element, ev: &BytesStart) -> Result<Element> {
-        let full_name = ev.name().into_string()?;
-        let (attributes, namespace_decls) = self.element_attributes(ev)?;
-        let elem = Element::with_data(&mut self.doc, full_name, attributes, namespace_decls);
+
+    /// The element that new nodes are pushed to.
+    fn current_parent(&self) -> Result<Element> {
+        self.element_stack
+            .last()
+            .copied()
+            .ok_or(MalformedReason::GenericMalformedTree.into())
+    }
+
+    fn push_node(&mut self, parent: Element, node: Node) -> Result<()> {
         parent
-            .push_child(&mut self.doc, Node::Element(elem))
-            .unwrap();
-        Ok(elem)
+            .push_child(&mut self.doc, node)
+            .map_err(|_| MalformedReason::GenericMalformedTree.into())
+    }
+
+    fn count_text_bytes(&mut self, len: usize) -> Result<()> {
+        self.text_bytes += len;
+        if let Some(max) = self
+            .read_opts
+            .max_text_bytes
+            .filter(|max| self.text_bytes > *max)
+        {
+            return Err(EditXMLError::LimitExceeded {
+                limit: "max_text_bytes",
+                max,
+            });
+        }
+        Ok(())
+    }
+
+    /// Pushes a new text node, or appends the text to the last text node of the current parent
+    /// with [`ReadOptions::merge_adjacent_text`].
+    fn push_text(&mut self, content: String) -> Result<()> {
+        self.count_text_bytes(content.len())?;
+        let parent = self.current_parent()?;
+        if self.read_opts.merge_adjacent_text
+            && let Some(Node::Text(last_text)) = parent.children_mut(&mut self.doc).last_mut()
+        {
+            last_text.push_str(&content);
+            return Ok(());
+        }
+        self.push_node(parent, Node::Text(content))
     }
 
     // Returns true if document parsing is finished.
     fn handle_event(&mut self, event: Event) -> Result<bool> {
         match event {
             Event::Start(ref ev) => {
-                let parent = *self.element_stack.last().ok_or(EditXMLError::MalformedXML(
-                    MalformedReason::GenericMalformedTree,
-                ))?;
-                let element = self.create_element(parent, ev)?;
+                let parent = self.current_parent()?;
+                let element = self.create_element(parent, ev, 2)?;
+                if self.preserves_whitespace(element) {
+                    self.preserving_whitespace += 1;
+                }
                 self.element_stack.push(element);
-                Ok(false)
             }
-            Event::End(_) => {
-                let elem = self.element_stack.pop().ok_or(EditXMLError::MalformedXML(
-                    MalformedReason::GenericMalformedTree,
-                ))?;
-                // quick-xml checks if tag names match for us
+            Event::End(ev) => {
+                // quick-xml checks if tag names match for us, unless these options are set
+                if self.read_opts.ignore_unmatched_end_tags || self.read_opts.case_insensitive_names
+                {
+                    let current = self.current_parent()?;
+                    let found = ev.name().into_string()?;
+                    let expected = current.full_name(&self.doc);
+                    let matches = if self.read_opts.case_insensitive_names {
+                        expected.eq_ignore_ascii_case(&found)
+                    } else {
+                        expected == found
+                    };
+                    if current.is_container() || !matches {
+                        if self.read_opts.ignore_unmatched_end_tags {
+                            let position =
+                                (self.event_end_position as usize).saturating_sub(ev.len() + 3);
+                            self.warn(ParseWarning::IgnoredEndTag {
+                                name: found,
+                                position,
+                            });
+                            return Ok(false);
+                        }
+                        if !current.is_container() {
+                            return Err(MalformedReason::EndEventMismatch {
+                                expected: expected.to_owned(),
+                                found,
+                            }
+                            .into());
+                        }
+                    }
+                }
+                // The container is never popped
+                if self.element_stack.len() <= 1 {
+                    return Err(MalformedReason::GenericMalformedTree.into());
+                }
+                let elem = self.current_parent()?;
+                self.element_stack.pop();
+                if self.preserves_whitespace(elem) {
+                    self.preserving_whitespace -= 1;
+                }
                 if self.read_opts.empty_text_node {
                     // distinguish <tag></tag> and <tag />
                     if !elem.has_children(&self.doc) {
-                        elem.push_child(&mut self.doc, Node::Text(String::new()))
-                            .unwrap();
+                        self.push_node(elem, Node::Text(String::new()))?;
                     }
                 }
-                Ok(false)
             }
             Event::Empty(ref ev) => {
-                let parent = *self.element_stack.last().ok_or(EditXMLError::MalformedXML(
-                    MalformedReason::GenericMalformedTree,
-                ))?;
-                self.create_element(parent, ev)?;
-                Ok(false)
+                let parent = self.current_parent()?;
+                self.create_element(parent, ev, 3)?;
             }
             // Comment, CData, and PI content should not be escaped,
             // but quick-xml assumes only CDATA is not escaped.
@@ -324,19 +872,15 @@ impl DocumentParser {
                     return Ok(false);
                 }
                 // NOTE: Was Unescaped
-                let content = ev.unescape_to_string()?;
-                let parent = *self.element_stack.last().ok_or(EditXMLError::MalformedXML(
-                    MalformedReason::GenericMalformedTree,
-                ))?;
-                let children = parent.children_mut(&mut self.doc);
-
-                let Some(Node::Text(last_text)) = children.last_mut() else {
-                    let node = Node::Text(content);
-                    parent.push_child(&mut self.doc, node).unwrap();
-                    return Ok(false);
-                };
-                last_text.push_str(&content);
-                Ok(false)
+                let mut text = ev.unescape_to_string()?;
+                // References are separate events, so a `&#13;` is never normalized
+                if self.read_opts.normalize_line_endings {
+                    text = normalize_line_endings(text);
+                }
+                if self.read_opts.collapse_whitespace() {
+                    text = collapse_whitespace(&text);
+                }
+                self.push_text(text)?;
             }
             Event::GeneralRef(ev) => {
                 if self.read_opts.ignore_whitespace_only && only_has_whitespace(&ev) {
@@ -346,158 +890,76 @@ impl DocumentParser {
                 if ev.is_empty() {
                     return Ok(false);
                 }
+                if ev.resolve_char_ref()?.is_none() {
+                    let name = ev.decode()?;
+                    if resolve_known_entity(&name).is_none() {
+                        let position =
+                            (self.event_end_position as usize).saturating_sub(ev.len() + 2);
+                        self.warn(ParseWarning::UnknownEntity {
+                            name: name.into_owned(),
+                            position,
+                        });
+                    }
+                }
                 // NOTE: Was Unescaped
-                let content = ev.unescape_to_string()?;
-                // Append this to the last text node
-
-                let parent = *self.element_stack.last().ok_or(EditXMLError::MalformedXML(
-                    MalformedReason::GenericMalformedTree,
-                ))?;
-                let children = parent.children_mut(&mut self.doc);
-                let Some(Node::Text(last_text)) = children.last_mut() else {
-                    let node = Node::Text(content);
-                    parent.push_child(&mut self.doc, node).unwrap();
-                    return Ok(false);
-                };
-                last_text.push_str(&content);
-                Ok(false)
+                self.push_text(ev.unescape_to_string()?)?;
             }
             Event::DocType(ev) => {
-                // Event::DocType comes with one leading whitespace. Strip the whitespace.
-                let raw = ev.unescape_to_string()?.into_bytes();
-                let content = if !raw.is_empty() && raw[0] == b' ' {
-                    String::from_utf8(raw[1..].to_vec())?
-                } else {
-                    String::from_utf8(raw.to_vec())?
-                };
-                let node = Node::DocType(content);
-                let parent = *self.element_stack.last().ok_or(EditXMLError::MalformedXML(
-                    MalformedReason::GenericMalformedTree,
-                ))?;
-                parent.push_child(&mut self.doc, node).unwrap();
-                Ok(false)
+                let node = Node::DocType(doctype_content(&ev)?);
+                let parent = self.current_parent()?;
+                self.push_node(parent, node)?;
             }
             Event::Comment(ev) => {
                 let content = String::from_utf8(ev.escape_ascii().collect())?;
-                let node = Node::Comment(content);
-                let parent = *self.element_stack.last().ok_or(EditXMLError::MalformedXML(
-                    MalformedReason::GenericMalformedTree,
-                ))?;
-                parent.push_child(&mut self.doc, node).unwrap();
-                Ok(false)
+                let parent = self.current_parent()?;
+                self.push_node(parent, Node::Comment(content))?;
             }
             Event::CData(ev) => {
                 let content = String::from_utf8(ev.to_vec())?;
-                let node = Node::CData(content);
-                let parent = *self.element_stack.last().ok_or(EditXMLError::MalformedXML(
-                    MalformedReason::GenericMalformedTree,
-                ))?;
-                parent.push_child(&mut self.doc, node).unwrap();
-                Ok(false)
+                if self.read_opts.collapse_trivial_cdata && is_trivial_cdata(&content) {
+                    self.push_text(content)?;
+                } else {
+                    self.count_text_bytes(content.len())?;
+                    let parent = self.current_parent()?;
+                    self.push_node(parent, Node::CData(content))?;
+                }
             }
             Event::PI(ev) => {
                 let content = ev.into_string()?;
-                let node = Node::PI(content);
-                let parent = *self.element_stack.last().ok_or(EditXMLError::MalformedXML(
-                    MalformedReason::GenericMalformedTree,
-                ))?;
-                parent.push_child(&mut self.doc, node).unwrap();
-                Ok(false)
+                let parent = self.current_parent()?;
+                self.push_node(parent, Node::PI(content))?;
             }
-            Event::Decl(_) => Err(EditXMLError::MalformedXML(MalformedReason::UnexpectedItem(
-                "XML Declaration",
-            ))),
-            Event::Eof => Ok(true),
+            // The first event is handled by parse_start, so this declaration isn't at the start.
+            Event::Decl(_) => return Err(MalformedReason::MisplacedDeclaration.into()),
+            Event::Eof => return Ok(true),
         }
+        Ok(false)
     }
 
-    // Sniff encoding and consume BOM
-    fn sniff_encoding<R: Read>(
+    /// Handles the events read by [`start_reader`] or [`start_str_reader`], then the rest of the document.
+    ///
+    /// `leading` are the events before `event`. See [`ReadOptions::allow_content_before_decl`]
+    fn parse_start<B: BufRead>(
         &mut self,
-        decodereader: &mut DecodeReader<R>,
-    ) -> Result<Option<&'static Encoding>> {
-        let bytes = decodereader.fill_buf()?;
-        let encoding = match bytes {
-            [0x3c, 0x3f, ..] => None, // UTF-8 '<?'
-            [0xfe, 0xff, ..] => {
-                // UTF-16 BE BOM
-                decodereader.consume(2);
-                Some(UTF_16BE)
-            }
-            [0xff, 0xfe, ..] => {
-                // UTF-16 LE BOM
-                decodereader.consume(2);
-                Some(UTF_16LE)
-            }
-            [0xef, 0xbb, 0xbf, ..] => {
-                // UTF-8 BOM
-                decodereader.consume(3);
-                None
-            }
-            [0x00, 0x3c, 0x00, 0x3f, ..] => Some(UTF_16BE),
-            [0x3c, 0x00, 0x3f, 0x00, ..] => Some(UTF_16LE),
-            _ => None, // Try decoding it with UTF-8
-        };
-        Ok(encoding)
-    }
-
-    // Look at the document decl and figure out the document encoding
-    fn parse_start<R: Read>(&mut self, reader: R) -> Result<()> {
-        #[cfg(feature = "tracing")]
-        tracing::debug!(?self.read_opts, "Parsing Start");
-        let mut decodereader = DecodeReader::new(reader, None);
-        let mut init_encoding = self.sniff_encoding(&mut decodereader)?;
-        if let Some(enc) = &self.read_opts.encoding {
-            init_encoding =
-                Some(Encoding::for_label(enc.as_bytes()).ok_or(DecodeError::MissingEncoding)?)
-        }
-        #[cfg(feature = "tracing")]
-        tracing::debug!(?init_encoding, "Initial Encoding");
-        decodereader.set_encoding(init_encoding);
-        let mut xmlreader = Reader::from_reader(decodereader);
-        xmlreader.config_mut().trim_text(self.read_opts.trim_text);
-
-        let mut buf = Vec::with_capacity(200);
-
-        // Skip first event if it only has whitespace
-        let event = match xmlreader.read_event_into(&mut buf)? {
-            Event::Text(ev) => {
-                if ev.is_empty() {
-                    #[cfg(feature = "tracing")]
-                    tracing::trace!("Skipping empty text event");
-                    xmlreader.read_event_into(&mut buf)?
-                } else if self.read_opts.ignore_whitespace_only && only_has_whitespace(&ev) {
-                    #[cfg(feature = "tracing")]
-                    tracing::trace!("Skipping whitespace only text event");
-                    xmlreader.read_event_into(&mut buf)?
-                } else {
-                    #[cfg(feature = "tracing")]
-
-                    tracing::trace!("First Event is Text");
-                    Event::Text(ev)
-                }
-            }
-            ev => ev,
-        };
-        #[cfg(feature = "tracing")]
-        tracing::debug!(?event, "First Event");
-        if let Event::Decl(ev) = event {
+        xmlreader: Reader<B>,
+        leading: Vec<Event>,
+        event: Event,
+    ) -> Result<()> {
+        self.event_end_position = self.position_offset + xmlreader.buffer_position();
+        let event = if let Event::Decl(ev) = event {
             self.handle_decl(&ev)?;
-            // Encoding::for_label("UTF-16") defaults to UTF-16 LE, even though it could be UTF-16 BE
-            if self.encoding != init_encoding
-                && !(self.encoding == Some(UTF_16LE) && init_encoding == Some(UTF_16BE))
-            {
-                let mut decode_reader = xmlreader.into_inner();
-                decode_reader.set_encoding(self.encoding);
-                xmlreader = Reader::from_reader(decode_reader);
-                xmlreader.config_mut().trim_text(self.read_opts.trim_text);
-            }
+            None
         } else if self.read_opts.require_decl {
             #[cfg(feature = "tracing")]
             tracing::debug!(?self.read_opts, ?event, "XML Declaration is required");
             return Err(MalformedReason::MissingDeclaration.into());
-        } else if self.handle_event(event)? {
-            return Ok(());
+        } else {
+            Some(event)
+        };
+        for event in leading.into_iter().chain(event) {
+            if self.handle_event(event)? {
+                return Ok(());
+            }
         }
         // Handle rest of the events
         self.parse_content(xmlreader)
@@ -510,8 +972,17 @@ impl DocumentParser {
                 .parse_content_buffer_initial_capacity,
         ); // reduce time increasing capacity at start.
 
+        let preserve_whitespace =
+            self.read_opts.trim_text && !self.read_opts.preserve_whitespace_in.is_empty();
         loop {
+            if preserve_whitespace {
+                // The text after the last start or end tag is read with this
+                reader
+                    .config_mut()
+                    .trim_text(self.preserving_whitespace == 0);
+            }
             let ev = reader.read_event_into(&mut buf)?;
+            self.event_end_position = self.position_offset + reader.buffer_position();
 
             if self.handle_event(ev)? {
                 if self.element_stack.len() == 1 {
@@ -525,6 +996,297 @@ impl DocumentParser {
     }
 }
 
+pub(crate) fn decl_version(ev: &BytesDecl) -> Result<String> {
+    Ok(String::from_utf8(ev.version()?.to_vec())?)
+}
+
+/// Returns `None` if the declaration doesn't have an encoding or it is UTF-8.
+pub(crate) fn decl_encoding(ev: &BytesDecl) -> Result<Option<&'static Encoding>> {
+    match ev.encoding() {
+        Some(res) => {
+            let encoding = Encoding::for_label(&res?).ok_or(DecodeError::MissingEncoding)?;
+            if encoding == UTF_8 {
+                Ok(None)
+            } else {
+                Ok(Some(encoding))
+            }
+        }
+        None => Ok(None),
+    }
+}
+
+pub(crate) fn decl_standalone(ev: &BytesDecl) -> Result<Option<StandaloneValue>> {
+    match ev.standalone() {
+        Some(res) => {
+            let standalone_value = res?;
+            Ok(Some(StandaloneValue::try_from(standalone_value.as_ref())?))
+        }
+        None => Ok(None),
+    }
+}
+
+/// Returns the name shared through `names`, or a name that owns `name` if `names` is `None`.
+///
+/// See [`ReadOptions::intern_names`]
+fn intern_name(names: Option<&mut HashSet<Arc<str>>>, name: Cow<str>) -> Name {
+    let Some(names) = names else {
+        return name.into_owned().into();
+    };
+    if let Some(name) = names.get(name.as_ref()) {
+        return Name::shared(name.clone());
+    }
+    let name: Arc<str> = name.into();
+    names.insert(name.clone());
+    Name::shared(name)
+}
+
+/// Reads the attributes and namespace declarations of a start tag.
+///
+/// Attribute names are created with `attribute_name`, after [`ReadOptions::case_insensitive_names`] is applied.
+#[inline(always)]
+pub(crate) fn element_attributes<K: Eq + Hash>(
+    ev: &BytesStart,
+    opts: &ReadOptions,
+    mut attribute_name: impl FnMut(Cow<str>) -> K,
+) -> Result<(HashMap<K, String>, HashMap<String, String>)> {
+    let mut attributes = HashMap::with_capacity(opts.optimizations.attribute_initial_capacity);
+    let mut namespace_decls = HashMap::with_capacity(opts.optimizations.namespace_initial_capacity);
+
+    // Duplicates are checked below, so namespace declarations are covered as well.
+    for (index, attr) in ev.attributes().with_checks(false).enumerate() {
+        if let Some(max) = opts.max_attributes_per_element.filter(|max| index >= *max) {
+            return Err(EditXMLError::LimitExceeded {
+                limit: "max_attributes_per_element",
+                max,
+            });
+        }
+        let attr = attr?;
+        // Key is converted to string.
+        let (key, prefix) = attr.key.decompose();
+        let value = if opts.normalize_attribute_value_space {
+            let value = normalize_space(&attr.value);
+            if value.is_empty() && !attr.value.is_empty() && !opts.collapse_empty_attributes {
+                " ".to_owned()
+            } else {
+                bytes_owned_to_unescaped_string(value)?
+            }
+        } else {
+            bytes_to_unescaped_string(&attr.value)?
+        };
+
+        let inserted = if prefix.map(attributes::is_xlmns).unwrap_or(false) {
+            // Has a prefix of `xmlns` so it is going in
+            let mut key = key.into_string()?;
+            if opts.case_insensitive_names {
+                key.make_ascii_lowercase();
+            }
+            insert_attribute(&mut namespace_decls, key, value, opts)
+        } else if attributes::is_xlmns(key) {
+            // The attribute is just `xmlns` meaning it is empty string
+            insert_attribute(&mut namespace_decls, String::default(), value, opts)
+        } else {
+            let mut key = Cow::Borrowed(std::str::from_utf8(attr.key.into_inner())?);
+            if opts.case_insensitive_names {
+                key.to_mut().make_ascii_lowercase();
+            }
+            insert_attribute(&mut attributes, attribute_name(key), value, opts)
+        };
+        if !inserted {
+            return Err(MalformedReason::DuplicateAttribute(attr.key.into_string()?).into());
+        }
+    }
+    Ok((attributes, namespace_decls))
+}
+
+/// Returns `false` if the attribute is a duplicate and [`ReadOptions::allow_duplicate_attributes`] is not set.
+fn insert_attribute<K: Eq + Hash>(
+    map: &mut HashMap<K, String>,
+    key: K,
+    value: String,
+    opts: &ReadOptions,
+) -> bool {
+    if !opts.allow_duplicate_attributes && map.contains_key(&key) {
+        return false;
+    }
+    map.insert(key, value);
+    true
+}
+
+/// The content of a [`Node::DocType`], as written but without the surrounding whitespace.
+///
+/// It is not unescaped, since references in the DTD are not resolved.
+pub(crate) fn doctype_content(ev: &BytesText) -> Result<String> {
+    Ok(ev.decode()?.trim_matches(XML_WHITESPACE).to_owned())
+}
+
+/// Whitespace characters in XML. `S` in the specification
+pub(crate) const XML_WHITESPACE: [char; 4] = [' ', '\t', '\r', '\n'];
+
+// Sniff encoding and consume BOM
+fn sniff_encoding<R: Read>(
+    decodereader: &mut DecodeReader<R>,
+) -> Result<Option<&'static Encoding>> {
+    let bytes = decodereader.fill_buf()?;
+    let encoding = match bytes {
+        // UTF-32 BOMs and '<' patterns. Must be checked before UTF-16, as the LE BOM starts with the UTF-16 LE BOM.
+        // encoding_rs doesn't support UTF-32, so fail instead of misdecoding.
+        [0x00, 0x00, 0xfe, 0xff, ..]
+        | [0xff, 0xfe, 0x00, 0x00, ..]
+        | [0x00, 0x00, 0x00, 0x3c, ..]
+        | [0x3c, 0x00, 0x00, 0x00, ..] => {
+            return Err(DecodeError::UnsupportedEncoding("UTF-32").into());
+        }
+        [0x3c, 0x3f, ..] => None, // UTF-8 '<?'
+        [0xfe, 0xff, ..] => {
+            // UTF-16 BE BOM
+            decodereader.consume(2);
+            Some(UTF_16BE)
+        }
+        [0xff, 0xfe, ..] => {
+            // UTF-16 LE BOM
+            decodereader.consume(2);
+            Some(UTF_16LE)
+        }
+        [0xef, 0xbb, 0xbf, ..] => {
+            // UTF-8 BOM
+            decodereader.consume(3);
+            None
+        }
+        [0x00, 0x3c, 0x00, 0x3f, ..] => Some(UTF_16BE),
+        [0x3c, 0x00, 0x3f, 0x00, ..] => Some(UTF_16LE),
+        _ => None, // Try decoding it with UTF-8
+    };
+    Ok(encoding)
+}
+
+/// Look at the document decl and figure out the document encoding.
+///
+/// Returns the reader positioned after the first event, the first event,
+/// and the number of bytes read before the returned reader was created. (Non zero if it was recreated to switch encodings)
+/// Leading whitespace is skipped, so the first event is the XML declaration if the document has one.
+pub(crate) fn start_reader<R: Read>(
+    reader: R,
+    opts: &ReadOptions,
+) -> Result<(Reader<DecodeReader<R>>, FirstEvents, u64)> {
+    #[cfg(feature = "tracing")]
+    tracing::debug!(?opts, "Parsing Start");
+    let mut decodereader = DecodeReader::new(reader, None);
+    let mut init_encoding = sniff_encoding(&mut decodereader)?;
+    if let Some(enc) = &opts.encoding {
+        init_encoding =
+            Some(Encoding::for_label(enc.as_bytes()).ok_or(DecodeError::MissingEncoding)?)
+    }
+    #[cfg(feature = "tracing")]
+    tracing::debug!(?init_encoding, "Initial Encoding");
+    decodereader.set_encoding(init_encoding);
+    let mut xmlreader = Reader::from_reader(decodereader);
+    configure_reader(&mut xmlreader, opts);
+
+    let (leading, event) = read_first_event(&mut xmlreader, opts)?;
+    let mut position_offset = 0;
+    if let Event::Decl(ev) = &event {
+        // Without an encoding declaration, keep reading with the sniffed or requested encoding
+        let encoding = match ev.encoding() {
+            Some(_) => decl_encoding(ev)?,
+            None => init_encoding,
+        };
+        // Encoding::for_label("UTF-16") defaults to UTF-16 LE, even though it could be UTF-16 BE
+        if encoding != init_encoding
+            && !(encoding == Some(UTF_16LE) && init_encoding == Some(UTF_16BE))
+        {
+            position_offset = xmlreader.buffer_position();
+            let mut decode_reader = xmlreader.into_inner();
+            decode_reader.set_encoding(encoding);
+            xmlreader = Reader::from_reader(decode_reader);
+            configure_reader(&mut xmlreader, opts);
+        }
+    }
+    Ok((xmlreader, (leading, event), position_offset))
+}
+
+/// [`start_reader`] for input that is already decoded.
+///
+/// Encoding sniffing and decoding are skipped, and the encoding declared in the document
+/// or set in [`ReadOptions::encoding`] is ignored. (An unknown encoding label is still an error)
+/// A leading byte order mark is skipped.
+pub(crate) fn start_str_reader<'a>(
+    str: &'a str,
+    opts: &ReadOptions,
+) -> Result<(Reader<&'a [u8]>, FirstEvents)> {
+    #[cfg(feature = "tracing")]
+    tracing::debug!(?opts, "Parsing Start");
+    let str = str.strip_prefix('\u{FEFF}').unwrap_or(str);
+    let mut xmlreader = Reader::from_str(str);
+    configure_reader(&mut xmlreader, opts);
+    let (leading, event) = read_first_event(&mut xmlreader, opts)?;
+    if let Event::Decl(ev) = &event {
+        decl_encoding(ev)?;
+    }
+    Ok((xmlreader, (leading, event)))
+}
+
+/// Applies the [`ReadOptions`] that quick-xml handles itself.
+fn configure_reader<B>(xmlreader: &mut Reader<B>, opts: &ReadOptions) {
+    let config = xmlreader.config_mut();
+    config.trim_text(opts.trim_text);
+    // Otherwise, end tag names are checked by the parser instead
+    config.check_end_names = !opts.ignore_unmatched_end_tags && !opts.case_insensitive_names;
+    config.allow_unmatched_ends = opts.ignore_unmatched_end_tags;
+}
+
+/// The events before the declaration, and the first other event. See [`read_first_event`]
+pub(crate) type FirstEvents = (Vec<Event<'static>>, Event<'static>);
+
+/// Reads the first event. A first text event that is empty, or whitespace only with `ignore_whitespace_only`, is skipped.
+///
+/// With [`ReadOptions::allow_content_before_decl`], the comments, processing instructions and whitespace
+/// before the first other event are returned separately, so that event can be the declaration.
+fn read_first_event<B: BufRead>(
+    xmlreader: &mut Reader<B>,
+    opts: &ReadOptions,
+) -> Result<FirstEvents> {
+    let mut buf = Vec::with_capacity(200);
+    let mut leading = Vec::new();
+    if opts.allow_content_before_decl {
+        loop {
+            let event = xmlreader.read_event_into(&mut buf)?.into_owned();
+            match event {
+                Event::Comment(_) | Event::PI(_) => leading.push(event),
+                Event::Text(ref ev) if only_has_whitespace(ev) => leading.push(event),
+                event => {
+                    #[cfg(feature = "tracing")]
+                    tracing::debug!(?leading, ?event, "First Event after leading content");
+                    return Ok((leading, event));
+                }
+            }
+        }
+    }
+
+    // Skip first event if it only has whitespace
+    let event = match xmlreader.read_event_into(&mut buf)? {
+        Event::Text(ev) => {
+            if ev.is_empty() {
+                #[cfg(feature = "tracing")]
+                tracing::trace!("Skipping empty text event");
+                xmlreader.read_event_into(&mut buf)?
+            } else if opts.ignore_whitespace_only && only_has_whitespace(&ev) {
+                #[cfg(feature = "tracing")]
+                tracing::trace!("Skipping whitespace only text event");
+                xmlreader.read_event_into(&mut buf)?
+            } else {
+                #[cfg(feature = "tracing")]
+                tracing::trace!("First Event is Text");
+                Event::Text(ev)
+            }
+        }
+        ev => ev,
+    }
+    .into_owned();
+    #[cfg(feature = "tracing")]
+    tracing::debug!(?event, "First Event");
+    Ok((leading, event))
+}
+
 /// Returns true if byte is an XML whitespace character
 #[allow(clippy::match_like_matches_macro)]
 #[inline(always)]
@@ -536,10 +1298,51 @@ fn is_whitespace(byte: u8) -> bool {
 }
 
 /// Returns true if bytes.len() == 0 or bytes only has a whitespace-like character.
-fn only_has_whitespace(bytes: &[u8]) -> bool {
+pub(crate) fn only_has_whitespace(bytes: &[u8]) -> bool {
     bytes.iter().all(|b| is_whitespace(*b))
 }
 
+/// Replaces `\r\n` and `\r` with `\n`.
+pub(crate) fn normalize_line_endings(text: String) -> String {
+    if !text.contains('\r') {
+        return text;
+    }
+    text.replace("\r\n", "\n").replace('\r', "\n")
+}
+
+/// Names of the entity references in `raw` that can't be resolved. See [`ParseWarning::UnknownEntity`]
+fn unknown_entities(raw: &str) -> impl Iterator<Item = &str> {
+    raw.split('&')
+        .skip(1)
+        .filter_map(|rest| rest.split_once(';'))
+        .map(|(name, _)| name)
+        .filter(|name| !name.starts_with('#') && resolve_known_entity(name).is_none())
+}
+
+/// Returns true if the CDATA content can be parsed as text without changing it.
+/// See [`ReadOptions::collapse_trivial_cdata`]
+pub(crate) fn is_trivial_cdata(content: &str) -> bool {
+    !content.contains(['<', '>', '&', '\r'])
+}
+
+/// Replaces each run of whitespace with a single space. Unlike [`normalize_space`], nothing is trimmed.
+pub(crate) fn collapse_whitespace(text: &str) -> String {
+    let mut collapsed = String::with_capacity(text.len());
+    let mut last_space = false;
+    for c in text.chars() {
+        if matches!(c, ' ' | '\t' | '\r' | '\n') {
+            if !last_space {
+                collapsed.push(' ');
+                last_space = true;
+            }
+        } else {
+            collapsed.push(c);
+            last_space = false;
+        }
+    }
+    collapsed
+}
+
 /// #xD(\r), #xA(\n), #x9(\t) is normalized into #x20.
 /// Leading and trailing spaces(#x20) are discarded
 /// and sequence of spaces are replaced by a single space.
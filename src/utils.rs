@@ -1,5 +1,6 @@
 #![allow(clippy::wrong_self_convention)]
 pub mod encoding;
+pub mod names;
 use core::str;
 use std::{borrow::Cow, ops::Deref};
 
@@ -14,6 +15,10 @@ use crate::EditXMLError;
 pub type HashMap<K, V> = std::collections::HashMap<K, V>;
 #[cfg(feature = "ahash")]
 pub type HashMap<K, V> = ahash::AHashMap<K, V>;
+#[cfg(not(feature = "ahash"))]
+pub type HashSet<K> = std::collections::HashSet<K>;
+#[cfg(feature = "ahash")]
+pub type HashSet<K> = ahash::AHashSet<K>;
 
 /// Trait for converting quick-xml types to string
 pub trait XMLStringUtils {
@@ -55,6 +60,9 @@ impl XMLStringUtils for BytesRef<'_> {
         String::from_utf8(self.to_vec()).map_err(EditXMLError::from)
     }
     fn unescape_to_string(&self) -> Result<String, EditXMLError> {
+        if let Some(ch) = self.resolve_char_ref()? {
+            return Ok(ch.to_string());
+        }
         let decoded = self.decode()?;
         resolve_entity(decoded.as_ref())
             .map(|s| s.to_owned())
@@ -101,11 +109,17 @@ impl XMLStringUtils for BytesPI<'_> {
         bytes_to_unescaped_string(self.content())
     }
 }
-pub(crate) fn resolve_entity(entity: &str) -> Option<&str> {
+/// Resolves a named entity, without the fallback of the `soft-fail-unescape` feature.
+pub(crate) fn resolve_known_entity(entity: &str) -> Option<&'static str> {
     #[cfg(not(feature = "escape-html"))]
     let result = quick_xml::escape::resolve_xml_entity(entity);
     #[cfg(feature = "escape-html")]
     let result = quick_xml::escape::resolve_html5_entity(entity);
+    result
+}
+
+pub(crate) fn resolve_entity(entity: &str) -> Option<&str> {
+    let result = resolve_known_entity(entity);
 
     #[cfg(feature = "soft-fail-unescape")]
     match result {
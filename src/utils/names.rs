@@ -0,0 +1,90 @@
+//! Validation of XML names.
+//!
+//! See [Names and Tokens](https://www.w3.org/TR/xml/#NT-Name)
+//! and [Namespaces in XML](https://www.w3.org/TR/xml-names/#NT-QName).
+
+/// Returns true if `c` can be the first character of a name. (`NameStartChar` excluding `:`)
+fn is_name_start_char(c: char) -> bool {
+    matches!(c,
+        'A'..='Z'
+        | '_'
+        | 'a'..='z'
+        | '\u{C0}'..='\u{D6}'
+        | '\u{D8}'..='\u{F6}'
+        | '\u{F8}'..='\u{2FF}'
+        | '\u{370}'..='\u{37D}'
+        | '\u{37F}'..='\u{1FFF}'
+        | '\u{200C}'..='\u{200D}'
+        | '\u{2070}'..='\u{218F}'
+        | '\u{2C00}'..='\u{2FEF}'
+        | '\u{3001}'..='\u{D7FF}'
+        | '\u{F900}'..='\u{FDCF}'
+        | '\u{FDF0}'..='\u{FFFD}'
+        | '\u{10000}'..='\u{EFFFF}'
+    )
+}
+/// Returns true if `c` can be part of a name. (`NameChar` excluding `:`)
+fn is_name_char(c: char) -> bool {
+    is_name_start_char(c)
+        || matches!(c,
+            '-'
+            | '.'
+            | '0'..='9'
+            | '\u{B7}'
+            | '\u{300}'..='\u{36F}'
+            | '\u{203F}'..='\u{2040}'
+        )
+}
+
+/// Returns true if `name` is a valid `NCName`. (A name without a namespace prefix)
+///
+/// ```
+/// use edit_xml::utils::names::is_valid_ncname;
+/// assert!(is_valid_ncname("item"));
+/// assert!(!is_valid_ncname("1item"));
+/// assert!(!is_valid_ncname("pre:item"));
+/// ```
+pub fn is_valid_ncname(name: &str) -> bool {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(c) if is_name_start_char(c) => chars.all(is_name_char),
+        _ => false,
+    }
+}
+
+/// Returns true if `name` is a valid `QName`. (A name with an optional namespace prefix)
+///
+/// ```
+/// use edit_xml::utils::names::is_valid_qname;
+/// assert!(is_valid_qname("item"));
+/// assert!(is_valid_qname("pre:item"));
+/// assert!(!is_valid_qname("pre:"));
+/// assert!(!is_valid_qname("my item"));
+/// ```
+pub fn is_valid_qname(name: &str) -> bool {
+    match name.split_once(':') {
+        Some((prefix, local)) => is_valid_ncname(prefix) && is_valid_ncname(local),
+        None => is_valid_ncname(name),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn test_names() {
+        for valid in ["a", "_a", "a1", "a-b.c", "élément", "名前", "a·b"] {
+            assert!(is_valid_ncname(valid), "{valid} should be valid");
+            assert!(is_valid_qname(valid), "{valid} should be valid");
+        }
+        for invalid in ["", "1a", "-a", ".a", "a b", " a", "a ", "a<b", "a\"", "a=b"] {
+            assert!(!is_valid_ncname(invalid), "{invalid} should be invalid");
+            assert!(!is_valid_qname(invalid), "{invalid} should be invalid");
+        }
+        assert!(is_valid_qname("p:a"));
+        assert!(!is_valid_ncname("p:a"));
+        for invalid in [":a", "a:", "p:1a", "a:b:c", "p :a"] {
+            assert!(!is_valid_qname(invalid), "{invalid} should be invalid");
+        }
+    }
+}
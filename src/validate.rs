@@ -0,0 +1,243 @@
+use crate::{Document, Element};
+use thiserror::Error;
+
+/// How many times a child element can occur. Part of a [`StructureSpec`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChildSpec {
+    /// Name of the child element, without its namespace prefix.
+    pub name: String,
+    pub min: usize,
+    /// `None` if there is no maximum.
+    pub max: Option<usize>,
+}
+
+/// Expected child elements of an element. Checked with [`Element::assert_structure`].
+///
+/// This is a lightweight alternative to schema validation, for checking that a document has the elements
+/// that are needed. Only the direct child elements are checked, by name without the namespace prefix, like [`Element::find`].
+///
+/// ```
+/// use edit_xml::StructureSpec;
+///
+/// let spec = StructureSpec::new()
+///     .required("name")
+///     .optional("description")
+///     .occurrences("server", 1, Some(3))
+///     .deny_unknown();
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct StructureSpec {
+    pub children: Vec<ChildSpec>,
+    /// Child elements that are not in `children` are an error. (default: `false`)
+    pub deny_unknown: bool,
+}
+
+impl StructureSpec {
+    /// A spec without any children, that allows unknown children.
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// The child element must occur exactly once.
+    pub fn required(self, name: impl Into<String>) -> Self {
+        self.occurrences(name, 1, Some(1))
+    }
+    /// The child element can occur at most once.
+    pub fn optional(self, name: impl Into<String>) -> Self {
+        self.occurrences(name, 0, Some(1))
+    }
+    /// The child element must occur between `min` and `max` times, inclusive.
+    pub fn occurrences(mut self, name: impl Into<String>, min: usize, max: Option<usize>) -> Self {
+        self.children.push(ChildSpec {
+            name: name.into(),
+            min,
+            max,
+        });
+        self
+    }
+    /// Child elements not declared in this spec are an error.
+    pub fn deny_unknown(mut self) -> Self {
+        self.deny_unknown = true;
+        self
+    }
+}
+
+/// A reason an element doesn't match a [`StructureSpec`]. Returned by [`Element::assert_structure`].
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum StructureError {
+    /// A child element with a minimum occurrence of at least one is missing.
+    #[error("Missing required element `{name}`")]
+    Missing { name: String },
+    /// The child element occurs, but fewer times than the minimum.
+    #[error("Element `{name}` occurs {found} times, expected at least {min}")]
+    TooFew {
+        name: String,
+        min: usize,
+        found: usize,
+    },
+    /// The child element occurs more times than the maximum.
+    #[error("Element `{name}` occurs {found} times, expected at most {max}")]
+    TooMany {
+        name: String,
+        max: usize,
+        found: usize,
+    },
+    /// A child element that isn't in the spec, with [`StructureSpec::deny_unknown`].
+    #[error("Unexpected element `{name}`")]
+    Unexpected { element: Element, name: String },
+}
+
+impl Element {
+    /// Checks the child elements of this element against `spec`. Returns every issue found.
+    ///
+    /// Errors are in the order of `spec.children`, followed by unexpected elements in document order.
+    ///
+    /// ```
+    /// use edit_xml::{Document, StructureError, StructureSpec};
+    ///
+    /// let doc = Document::parse_str(r#"<?xml version="1.0"?>
+    /// <config><port>80</port><port>443</port></config>
+    /// "#).unwrap();
+    /// let config = doc.root_element().unwrap();
+    ///
+    /// let spec = StructureSpec::new().required("host").occurrences("port", 1, None);
+    /// assert_eq!(
+    ///     config.assert_structure(&doc, &spec),
+    ///     Err(vec![StructureError::Missing { name: "host".to_string() }])
+    /// );
+    /// ```
+    pub fn assert_structure(
+        &self,
+        doc: &Document,
+        spec: &StructureSpec,
+    ) -> Result<(), Vec<StructureError>> {
+        let children = self.child_elements(doc);
+        let mut errors = Vec::new();
+        for child_spec in &spec.children {
+            let found = children
+                .iter()
+                .filter(|child| child.name(doc) == child_spec.name)
+                .count();
+            let name = child_spec.name.clone();
+            if found == 0 && child_spec.min > 0 {
+                errors.push(StructureError::Missing { name });
+            } else if found < child_spec.min {
+                errors.push(StructureError::TooFew {
+                    name,
+                    min: child_spec.min,
+                    found,
+                });
+            } else if let Some(max) = child_spec.max.filter(|max| found > *max) {
+                errors.push(StructureError::TooMany { name, max, found });
+            }
+        }
+        if spec.deny_unknown {
+            for child in children {
+                let name = child.name(doc);
+                if !spec.children.iter().any(|spec| spec.name == name) {
+                    errors.push(StructureError::Unexpected {
+                        element: child,
+                        name: name.to_owned(),
+                    });
+                }
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> Document {
+        Document::parse_str(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+            <config>
+                <name>App</name>
+                <server>a</server>
+                <server>b</server>
+                <server>c</server>
+                <c:debug xmlns:c="urn:c">true</c:debug>
+            </config>
+            "#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_valid_structure() {
+        let doc = config();
+        let root = doc.root_element().unwrap();
+        let spec = StructureSpec::new()
+            .required("name")
+            .optional("description")
+            .occurrences("server", 1, None)
+            .optional("debug")
+            .deny_unknown();
+        assert_eq!(root.assert_structure(&doc, &spec), Ok(()));
+        assert_eq!(root.assert_structure(&doc, &StructureSpec::new()), Ok(()));
+    }
+
+    #[test]
+    fn test_missing_required() {
+        let doc = config();
+        let root = doc.root_element().unwrap();
+        let spec = StructureSpec::new()
+            .required("name")
+            .required("version")
+            .occurrences("port", 2, None)
+            .occurrences("server", 4, None);
+        assert_eq!(
+            root.assert_structure(&doc, &spec),
+            Err(vec![
+                StructureError::Missing {
+                    name: "version".to_owned()
+                },
+                StructureError::Missing {
+                    name: "port".to_owned()
+                },
+                StructureError::TooFew {
+                    name: "server".to_owned(),
+                    min: 4,
+                    found: 3
+                },
+            ])
+        );
+    }
+
+    #[test]
+    fn test_too_many_occurrences() {
+        let doc = config();
+        let root = doc.root_element().unwrap();
+        let spec = StructureSpec::new()
+            .required("name")
+            .occurrences("server", 1, Some(2))
+            .required("server")
+            .deny_unknown();
+        let debug = root.find(&doc, "debug").unwrap();
+        assert_eq!(
+            root.assert_structure(&doc, &spec),
+            Err(vec![
+                StructureError::TooMany {
+                    name: "server".to_owned(),
+                    max: 2,
+                    found: 3
+                },
+                StructureError::TooMany {
+                    name: "server".to_owned(),
+                    max: 1,
+                    found: 3
+                },
+                StructureError::Unexpected {
+                    element: debug,
+                    name: "debug".to_owned()
+                },
+            ])
+        );
+    }
+}
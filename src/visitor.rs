@@ -0,0 +1,214 @@
+//! Pre-order traversal of a document using the visitor pattern.
+//!
+//! Implement [`Visitor`] and pass it to [`Document::accept`] or [`Element::accept`].
+//!
+//! # Example
+//! ```
+//! use edit_xml::{Document, Element, Visitor, VisitControl};
+//!
+//! struct ElementCounter(usize);
+//! impl Visitor for ElementCounter {
+//!     fn visit_element(&mut self, _doc: &Document, _element: Element) -> VisitControl {
+//!         self.0 += 1;
+//!         VisitControl::Continue
+//!     }
+//! }
+//!
+//! let doc = Document::parse_str(r#"<?xml version="1.0"?>
+//! <root>
+//!     <a><b /></a>
+//!     <c />
+//! </root>
+//! "#).unwrap();
+//! let mut counter = ElementCounter(0);
+//! doc.accept(&mut counter);
+//! assert_eq!(counter.0, 4);
+//! ```
+use crate::{Document, Element, Node};
+
+/// Returned by [`Visitor`] methods to control the traversal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum VisitControl {
+    /// Continue the traversal, including the children of the current element.
+    #[default]
+    Continue,
+    /// Do not visit the children of the current element.
+    ///
+    /// Only meaningful when returned from [`Visitor::visit_element`].
+    SkipChildren,
+    /// Stop the traversal entirely.
+    Stop,
+}
+
+/// Visits the nodes of a document in pre-order.
+///
+/// Every method defaults to [`VisitControl::Continue`], so only the relevant ones need to be implemented.
+pub trait Visitor {
+    /// Called for each element, before its children are visited.
+    fn visit_element(&mut self, doc: &Document, element: Element) -> VisitControl {
+        let _ = (doc, element);
+        VisitControl::Continue
+    }
+    /// Called for each [`Node::Text`]
+    fn visit_text(&mut self, doc: &Document, text: &str) -> VisitControl {
+        let _ = (doc, text);
+        VisitControl::Continue
+    }
+    /// Called for each [`Node::Comment`]
+    fn visit_comment(&mut self, doc: &Document, comment: &str) -> VisitControl {
+        let _ = (doc, comment);
+        VisitControl::Continue
+    }
+    /// Called for each [`Node::CData`]
+    fn visit_cdata(&mut self, doc: &Document, cdata: &str) -> VisitControl {
+        let _ = (doc, cdata);
+        VisitControl::Continue
+    }
+    /// Called for each [`Node::PI`]
+    fn visit_pi(&mut self, doc: &Document, pi: &str) -> VisitControl {
+        let _ = (doc, pi);
+        VisitControl::Continue
+    }
+    /// Called for each [`Node::DocType`]
+    fn visit_doctype(&mut self, doc: &Document, doctype: &str) -> VisitControl {
+        let _ = (doc, doctype);
+        VisitControl::Continue
+    }
+}
+
+/// Returns `true` if the traversal was stopped.
+fn walk_node<V: Visitor + ?Sized>(doc: &Document, node: &Node, visitor: &mut V) -> bool {
+    let control = match node {
+        Node::Element(element) => return walk_element(doc, *element, visitor),
+        Node::Text(text) => visitor.visit_text(doc, text),
+        Node::Comment(comment) => visitor.visit_comment(doc, comment),
+        Node::CData(cdata) => visitor.visit_cdata(doc, cdata),
+        Node::PI(pi) => visitor.visit_pi(doc, pi),
+        Node::DocType(doctype) => visitor.visit_doctype(doc, doctype),
+    };
+    control == VisitControl::Stop
+}
+/// Returns `true` if the traversal was stopped.
+fn walk_element<V: Visitor + ?Sized>(doc: &Document, element: Element, visitor: &mut V) -> bool {
+    // The container is invisible, so only its children are visited.
+    if !element.is_container() {
+        match visitor.visit_element(doc, element) {
+            VisitControl::Continue => {}
+            VisitControl::SkipChildren => return false,
+            VisitControl::Stop => return true,
+        }
+    }
+    element
+        .children(doc)
+        .iter()
+        .any(|child| walk_node(doc, child, visitor))
+}
+
+impl Element {
+    /// Visits this element and all of its descendants in pre-order.
+    ///
+    /// Returns [`VisitControl::Stop`] if the visitor stopped the traversal early,
+    /// otherwise [`VisitControl::Continue`].
+    pub fn accept<V: Visitor + ?Sized>(&self, doc: &Document, visitor: &mut V) -> VisitControl {
+        if walk_element(doc, *self, visitor) {
+            VisitControl::Stop
+        } else {
+            VisitControl::Continue
+        }
+    }
+}
+impl Document {
+    /// Visits every root node of the document and their descendants in pre-order.
+    ///
+    /// Returns [`VisitControl::Stop`] if the visitor stopped the traversal early,
+    /// otherwise [`VisitControl::Continue`].
+    pub fn accept<V: Visitor + ?Sized>(&self, visitor: &mut V) -> VisitControl {
+        self.container().accept(self, visitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const XML: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+    <root>
+        <a>Text<b /></a>
+        <!-- Comment -->
+        <c>
+            <d />
+        </c>
+    </root>
+    "#;
+
+    #[derive(Default)]
+    struct Counter {
+        elements: usize,
+        texts: usize,
+        comments: usize,
+    }
+    impl Visitor for Counter {
+        fn visit_element(&mut self, _doc: &Document, _element: Element) -> VisitControl {
+            self.elements += 1;
+            VisitControl::Continue
+        }
+        fn visit_text(&mut self, _doc: &Document, _text: &str) -> VisitControl {
+            self.texts += 1;
+            VisitControl::Continue
+        }
+        fn visit_comment(&mut self, _doc: &Document, _comment: &str) -> VisitControl {
+            self.comments += 1;
+            VisitControl::Continue
+        }
+    }
+
+    #[test]
+    fn test_count_elements() {
+        let doc = Document::parse_str(XML).unwrap();
+        let mut counter = Counter::default();
+        assert_eq!(doc.accept(&mut counter), VisitControl::Continue);
+        assert_eq!(counter.elements, 5);
+        assert_eq!(counter.texts, 1);
+        assert_eq!(counter.comments, 1);
+
+        let c = doc.root_element().unwrap().find(&doc, "c").unwrap();
+        let mut counter = Counter::default();
+        c.accept(&doc, &mut counter);
+        assert_eq!(counter.elements, 2);
+    }
+
+    #[test]
+    fn test_skip_children_and_stop() {
+        let doc = Document::parse_str(XML).unwrap();
+
+        struct SkipA(Vec<String>);
+        impl Visitor for SkipA {
+            fn visit_element(&mut self, doc: &Document, element: Element) -> VisitControl {
+                self.0.push(element.name(doc).to_owned());
+                if element.name(doc) == "a" {
+                    VisitControl::SkipChildren
+                } else {
+                    VisitControl::Continue
+                }
+            }
+        }
+        let mut skip = SkipA(Vec::new());
+        doc.accept(&mut skip);
+        assert_eq!(skip.0, vec!["root", "a", "c", "d"]);
+
+        struct StopAtB(Vec<String>);
+        impl Visitor for StopAtB {
+            fn visit_element(&mut self, doc: &Document, element: Element) -> VisitControl {
+                self.0.push(element.name(doc).to_owned());
+                if element.name(doc) == "b" {
+                    VisitControl::Stop
+                } else {
+                    VisitControl::Continue
+                }
+            }
+        }
+        let mut stop = StopAtB(Vec::new());
+        assert_eq!(doc.accept(&mut stop), VisitControl::Stop);
+        assert_eq!(stop.0, vec!["root", "a", "b"]);
+    }
+}
@@ -0,0 +1,46 @@
+use edit_xml::{
+    Document, ReadOptions,
+    events::{XmlEvent, read_events},
+};
+mod test_utils;
+
+#[test]
+fn count_start_tags_in_large_file() -> anyhow::Result<()> {
+    test_utils::setup_logger();
+    let path = test_utils::bench_files_dir().join("large.hidden.xml");
+
+    let mut start_tags = 0;
+    let mut end_tags = 0;
+    for event in read_events(std::fs::File::open(&path)?, ReadOptions::default()) {
+        match event? {
+            XmlEvent::StartElement { .. } => start_tags += 1,
+            XmlEvent::EndElement { .. } => end_tags += 1,
+            _ => {}
+        }
+    }
+    assert_eq!(start_tags, end_tags);
+    // Parsing the file into a Document is slow in debug builds, so the count is hardcoded.
+    assert_eq!(start_tags, 476646);
+    Ok(())
+}
+
+#[test]
+fn utf16_events() -> anyhow::Result<()> {
+    test_utils::setup_logger();
+    let path = test_utils::documents_dir().join("encoding2.xml");
+    let events = read_events(std::fs::File::open(&path)?, ReadOptions::default())
+        .collect::<Result<Vec<_>, _>>()?;
+    assert!(matches!(events[0], XmlEvent::Declaration { .. }));
+
+    let doc = Document::parse_file(&path)?;
+    let root = doc.root_element().unwrap();
+    let first_start = events
+        .iter()
+        .find_map(|event| match event {
+            XmlEvent::StartElement { name, .. } => Some(name.as_str()),
+            _ => None,
+        })
+        .unwrap();
+    assert_eq!(first_start, root.full_name(&doc));
+    Ok(())
+}
@@ -1,4 +1,7 @@
-use edit_xml::{Document, Node, ReadOptions};
+use edit_xml::{
+    DecodeError, Document, EditXMLError, Element, MalformedReason, Node, ParseWarning, ReadOptions,
+    Source, TextMode, VisitControl, Visitor,
+};
 mod test_utils;
 
 #[test]
@@ -82,7 +85,7 @@ fn test_unescape() {
 
     let doctype = &doc.root_nodes()[3];
     if let Node::DocType(doc) = doctype {
-        assert_eq!(doc, "&");
+        assert_eq!(doc, "&amp;");
     } else {
         panic!("Expected doctype");
     }
@@ -91,3 +94,963 @@ fn test_unescape() {
     assert!(matches!(pi, Node::PI(_)));
     assert_eq!(pi.text_content(&doc), "<&amp;");
 }
+
+#[test]
+fn test_duplicate_attributes() {
+    test_utils::setup_logger();
+    let opts = ReadOptions {
+        require_decl: false,
+        ..Default::default()
+    };
+    for xml in [
+        r#"<root a="1" a="2" />"#,
+        r#"<root xmlns:p="1" xmlns:p="2" />"#,
+        r#"<root xmlns="1" xmlns="2" />"#,
+    ] {
+        let err = Document::parse_str_with_opts(xml, opts.clone()).unwrap_err();
+        assert!(
+            matches!(
+                err,
+                EditXMLError::MalformedXML(MalformedReason::DuplicateAttribute(_))
+            ),
+            "Unexpected error for {xml}: {err:?}"
+        );
+    }
+
+    let opts = ReadOptions {
+        allow_duplicate_attributes: true,
+        ..opts
+    };
+    let doc =
+        Document::parse_str_with_opts(r#"<root a="1" a="2" xmlns:p="1" xmlns:p="2" />"#, opts)
+            .unwrap();
+    let root = doc.root_element().unwrap();
+    assert_eq!(root.attribute(&doc, "a"), Some("2"));
+    assert_eq!(
+        root.namespace_decls(&doc).get("p").map(String::as_str),
+        Some("2")
+    );
+}
+
+#[test]
+fn test_misplaced_declaration() {
+    test_utils::setup_logger();
+    for xml in [
+        r#"<a/><?xml version="1.0"?>"#,
+        r#"<?xml version="1.0"?><a/><?xml version="1.0"?>"#,
+        r#"<?xml version="1.0"?><a><?xml version="1.0"?></a>"#,
+    ] {
+        let err = Document::parse_str_with_opts(xml, ReadOptions::relaxed()).unwrap_err();
+        assert!(
+            matches!(
+                err,
+                EditXMLError::MalformedXML(MalformedReason::MisplacedDeclaration)
+            ),
+            "Unexpected error for {xml}: {err:?}"
+        );
+    }
+}
+
+#[test]
+fn test_end_event_mismatch() {
+    test_utils::setup_logger();
+    let xml = "<root><a>Text</b></root>";
+    let err = Document::parse_str_with_opts(xml, ReadOptions::relaxed()).unwrap_err();
+    match &err {
+        EditXMLError::MalformedXML(MalformedReason::EndEventMismatch { expected, found }) => {
+            assert_eq!(expected, "a");
+            assert_eq!(found, "b");
+        }
+        err => panic!("Unexpected error: {err:?}"),
+    }
+    assert_eq!(
+        err.to_string(),
+        "Malformed XML: Closing tag mismatch. Expected `</a>`, found `</b>`"
+    );
+}
+
+#[test]
+fn test_track_positions() {
+    test_utils::setup_logger();
+    let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+<root attr="1">
+    <a>Text<b/></a>
+    <!-- <fake> -->
+    <c x="&quot;" />
+</root>"#;
+    let opts = ReadOptions {
+        track_positions: true,
+        ..ReadOptions::default()
+    };
+    let doc = Document::parse_str_with_opts(xml, opts).unwrap();
+    struct Collect(Vec<Element>);
+    impl Visitor for Collect {
+        fn visit_element(&mut self, _doc: &Document, element: Element) -> VisitControl {
+            self.0.push(element);
+            VisitControl::Continue
+        }
+    }
+    let mut collect = Collect(Vec::new());
+    doc.accept(&mut collect);
+    let elements = collect.0;
+    assert_eq!(elements.len(), 4);
+    let mut last = None;
+    for element in elements {
+        let position = element.source_position(&doc).unwrap();
+        let name = element.name(&doc);
+        assert!(
+            xml[position..].starts_with(&format!("<{name}")),
+            "{name} is not at {position}"
+        );
+        assert!(last < Some(position), "Positions are not increasing");
+        last = Some(position);
+    }
+
+    let doc = Document::parse_str(xml).unwrap();
+    assert_eq!(doc.root_element().unwrap().source_position(&doc), None);
+}
+
+#[test]
+fn test_merge_adjacent_text() {
+    test_utils::setup_logger();
+    let doc =
+        Document::parse_str_with_opts("<root>a&amp;b</root>", ReadOptions::relaxed()).unwrap();
+    let root = doc.root_element().unwrap();
+    assert_eq!(root.children(&doc), &vec![Node::Text("a&b".to_owned())]);
+    assert_eq!(root.first_text(&doc), Some("a&b"));
+
+    let opts = ReadOptions::builder()
+        .require_decl(false)
+        .merge_adjacent_text(false)
+        .build();
+    let doc = Document::parse_str_with_opts("<root>a&amp;b</root>", opts).unwrap();
+    let root = doc.root_element().unwrap();
+    assert_eq!(
+        root.children(&doc),
+        &vec![
+            Node::Text("a".to_owned()),
+            Node::Text("&".to_owned()),
+            Node::Text("b".to_owned()),
+        ]
+    );
+
+    let xml = r#"<root>a&amp;b&#60;c&lt;<![CDATA[d]]>e&gt;f</root>"#;
+    for trim_text in [true, false] {
+        let opts = ReadOptions {
+            trim_text,
+            ..ReadOptions::relaxed()
+        };
+        let doc = Document::parse_str_with_opts(xml, opts).unwrap();
+        let root = doc.root_element().unwrap();
+        assert_eq!(
+            root.children(&doc),
+            &vec![
+                Node::Text("a&b<c<".to_owned()),
+                Node::CData("d".to_owned()),
+                Node::Text("e>f".to_owned()),
+            ]
+        );
+    }
+}
+
+/// Malformed input must return an error, never panic.
+#[test]
+fn test_malformed_input_does_not_panic() {
+    let mut inputs: Vec<Vec<u8>> = Vec::new();
+    for name in ["doc.xml", "encoding1.xml", "encoding2.xml", "nodes.xml"] {
+        let content = std::fs::read(test_utils::documents_dir().join(name)).unwrap();
+        for end in 0..content.len() {
+            inputs.push(content[..end].to_vec());
+        }
+    }
+    for garbage in [
+        "</a>",
+        "<a></a></a>",
+        "<a><b></a></b>",
+        "<?xml?>",
+        "<?xml version=\"1.0\" encoding=\"unknown\"?><a/>",
+        "<?xml version=\"1.0\" standalone=\"maybe\"?><a/>",
+        "<a b=\"1\" b=\"2\"/>",
+        "<a b=1/>",
+        "<a>&unknown;</a>",
+        "<a>&#0;</a>",
+        "<a>&#xFFFFFFFF;</a>",
+        "<!DOCTYPE",
+        "<![CDATA[",
+        "<!--",
+        "<a xmlns:=\"\"/>",
+        "\u{FEFF}<a/>",
+    ] {
+        inputs.push(garbage.as_bytes().to_vec());
+    }
+    // Pseudo random bytes biased towards markup characters
+    let mut state: u32 = 0x2545_F491;
+    for _ in 0..500 {
+        let mut bytes = Vec::new();
+        for _ in 0..(state % 64) {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            bytes.push(b"<>/?!=\"'&;#x:a- \n\xFE\xFF\x00"[(state % 20) as usize]);
+        }
+        inputs.push(bytes);
+    }
+
+    for input in inputs {
+        for opts in [ReadOptions::default(), ReadOptions::relaxed()] {
+            let _ = Document::parse_reader_with_opts(input.as_slice(), opts.clone());
+            let _: Vec<_> = edit_xml::events::read_events(input.as_slice(), opts).collect();
+        }
+    }
+}
+
+#[test]
+fn test_limits() {
+    test_utils::setup_logger();
+    let xml = r#"<root><a>Text</a><b><![CDATA[CDATA]]></b><c>&amp;</c></root>"#;
+    let with_limits = |max_elements, max_text_bytes| ReadOptions {
+        max_elements,
+        max_text_bytes,
+        ..ReadOptions::relaxed()
+    };
+    // 4 elements and 10 bytes of text
+    assert!(Document::parse_str_with_opts(xml, with_limits(Some(4), Some(10))).is_ok());
+
+    let err = Document::parse_str_with_opts(xml, with_limits(Some(3), None)).unwrap_err();
+    assert!(
+        matches!(
+            err,
+            EditXMLError::LimitExceeded {
+                limit: "max_elements",
+                max: 3
+            }
+        ),
+        "{err:?}"
+    );
+    let err = Document::parse_str_with_opts(xml, with_limits(None, Some(9))).unwrap_err();
+    assert!(
+        matches!(
+            err,
+            EditXMLError::LimitExceeded {
+                limit: "max_text_bytes",
+                max: 9
+            }
+        ),
+        "{err:?}"
+    );
+}
+
+#[test]
+fn test_max_attributes_per_element() {
+    test_utils::setup_logger();
+    let attributes: String = (0..100).map(|i| format!(" a{i}=\"{i}\"")).collect();
+    let xml = format!(r#"<root xmlns:p="urn:p"><big{attributes}/></root>"#);
+    let opts = |max| ReadOptions {
+        max_attributes_per_element: Some(max),
+        ..ReadOptions::relaxed()
+    };
+
+    let doc = Document::parse_str_with_opts(&xml, opts(100)).unwrap();
+    let big = doc.root_element().unwrap().find(&doc, "big").unwrap();
+    assert_eq!(big.attributes(&doc).len(), 100);
+
+    let err = Document::parse_str_with_opts(&xml, opts(99)).unwrap_err();
+    assert!(
+        matches!(
+            err,
+            EditXMLError::LimitExceeded {
+                limit: "max_attributes_per_element",
+                max: 99
+            }
+        ),
+        "{err:?}"
+    );
+    // Namespace declarations count as attributes
+    let err = Document::parse_str_with_opts(&xml, opts(0)).unwrap_err();
+    assert!(
+        matches!(
+            err,
+            EditXMLError::LimitExceeded {
+                limit: "max_attributes_per_element",
+                max: 0
+            }
+        ),
+        "{err:?}"
+    );
+    let events: Result<Vec<_>, _> =
+        edit_xml::events::read_events(xml.as_bytes(), opts(99)).collect();
+    assert!(matches!(events, Err(EditXMLError::LimitExceeded { .. })));
+}
+
+#[test]
+fn test_allow_content_before_decl() {
+    test_utils::setup_logger();
+    let xml = r#"<!-- Generated --><?tool run?>
+<?xml version="1.1" standalone="yes"?>
+<root>Text</root>"#;
+    assert!(matches!(
+        Document::parse_str(xml),
+        Err(EditXMLError::MalformedXML(
+            MalformedReason::MissingDeclaration
+        ))
+    ));
+
+    let opts = ReadOptions {
+        allow_content_before_decl: true,
+        ..ReadOptions::default()
+    };
+    let doc = Document::parse_str_with_opts(xml, opts.clone()).unwrap();
+    assert!(
+        doc.write_str()
+            .unwrap()
+            .starts_with(r#"<?xml version="1.1" encoding="UTF-8" standalone="yes"?>"#)
+    );
+    let children = doc.container().children(&doc);
+    assert_eq!(children.len(), 3);
+    assert_eq!(children[0], Node::Comment(" Generated ".to_owned()));
+    assert_eq!(children[1], Node::PI("tool run".to_owned()));
+    assert_eq!(doc.root_element().unwrap().text_content(&doc), "Text");
+
+    // The declared encoding is still used
+    let mut bytes = b"<!-- c --><?xml version=\"1.0\" encoding=\"windows-1252\"?><root>".to_vec();
+    bytes.push(0xE9);
+    bytes.extend_from_slice(b"</root>");
+    let doc = Document::parse_reader_with_opts(bytes.as_slice(), opts.clone()).unwrap();
+    assert_eq!(doc.root_element().unwrap().text_content(&doc), "\u{e9}");
+
+    // Still required if there is no declaration at all
+    assert!(matches!(
+        Document::parse_str_with_opts("<!-- c --><root/>", opts.clone()),
+        Err(EditXMLError::MalformedXML(
+            MalformedReason::MissingDeclaration
+        ))
+    ));
+    // Or if it comes after other content
+    assert!(
+        Document::parse_str_with_opts(r#"<!-- c --><root/><?xml version="1.0"?>"#, opts.clone())
+            .is_err()
+    );
+
+    let events: Vec<_> = edit_xml::events::read_events(xml.as_bytes(), opts)
+        .collect::<Result<_, _>>()
+        .unwrap();
+    assert_eq!(events.len(), 6, "{events:?}");
+    assert!(matches!(&events[0], edit_xml::events::XmlEvent::Comment(c) if c == " Generated "));
+    assert!(
+        matches!(&events[2], edit_xml::events::XmlEvent::Declaration { version, .. } if version == "1.1")
+    );
+}
+
+#[test]
+fn test_encoding_override_without_declaration() {
+    test_utils::setup_logger();
+    let opts = || ReadOptions {
+        encoding: Some("windows-1252".to_owned()),
+        require_decl: false,
+        ..ReadOptions::default()
+    };
+    let parse = |input: &[u8]| {
+        let doc = Document::parse_reader_with_opts(input, opts()).unwrap();
+        doc.root_element().unwrap().text_content(&doc)
+    };
+    // No declaration, including leading whitespace, text and comments before the root
+    assert_eq!(parse(b"<root>caf\xe9</root>"), "caf\u{e9}");
+    assert_eq!(parse(b"  \n<root>caf\xe9</root>"), "caf\u{e9}");
+    assert_eq!(parse(b"\xe9 <root>caf\xe9</root>"), "caf\u{e9}");
+    assert_eq!(parse(b"<!-- \xe9 --><root>caf\xe9</root>"), "caf\u{e9}");
+    // A declaration without encoding keeps the override
+    assert_eq!(
+        parse(b"<?xml version=\"1.0\"?><root>caf\xe9</root>"),
+        "caf\u{e9}"
+    );
+    // A declared encoding replaces the override, for content read before and after the switch
+    let long = "x".repeat(20000);
+    let input = [
+        b"<?xml version=\"1.0\" encoding=\"UTF-8\"?><root>caf\xc3\xa9".as_slice(),
+        long.as_bytes(),
+        b"\xc3\xa9</root>",
+    ]
+    .concat();
+    assert_eq!(parse(&input), format!("caf\u{e9}{long}\u{e9}"));
+
+    // A str is already decoded, so the override doesn't apply
+    let doc = Document::parse_str_with_opts("<root>caf\u{e9}</root>", opts()).unwrap();
+    assert_eq!(doc.root_element().unwrap().text_content(&doc), "caf\u{e9}");
+}
+
+#[test]
+fn test_utf16_declaration_without_encoding() {
+    test_utils::setup_logger();
+    let xml = "<?xml version=\"1.0\"?><root>한국어</root>";
+    let utf16_le: Vec<u8> = xml.encode_utf16().flat_map(u16::to_le_bytes).collect();
+    let doc = Document::parse_reader(utf16_le.as_slice()).unwrap();
+    assert_eq!(doc.root_element().unwrap().text_content(&doc), "한국어");
+}
+
+#[test]
+fn test_declared_encoding() {
+    test_utils::setup_logger();
+    let xml = "<?xml version=\"1.0\" encoding=\"UTF-16\"?><root>한국어</root>";
+    let utf16_le: Vec<u8> = [0xff, 0xfe]
+        .into_iter()
+        .chain(xml.encode_utf16().flat_map(u16::to_le_bytes))
+        .collect();
+    let doc = Document::parse_reader(utf16_le.as_slice()).unwrap();
+    assert_eq!(doc.declared_encoding(), Some("UTF-16"));
+    assert_eq!(doc.root_element().unwrap().text_content(&doc), "한국어");
+
+    let doc = Document::parse_str(xml).unwrap();
+    assert_eq!(doc.declared_encoding(), Some("UTF-16"));
+    let doc = Document::parse_str("<?xml version=\"1.0\"?><root />").unwrap();
+    assert_eq!(doc.declared_encoding(), None);
+    let doc = Document::parse_str_with_opts("<root />", ReadOptions::relaxed()).unwrap();
+    assert_eq!(doc.declared_encoding(), None);
+}
+
+#[test]
+fn test_utf32_is_unsupported() {
+    test_utils::setup_logger();
+    let xml = r#"<?xml version="1.0" encoding="UTF-32"?><root />"#;
+    let utf32_be: Vec<u8> = xml.chars().flat_map(|c| (c as u32).to_be_bytes()).collect();
+    let utf32_le: Vec<u8> = xml.chars().flat_map(|c| (c as u32).to_le_bytes()).collect();
+    let with_bom = |bom: &[u8], content: &[u8]| [bom, content].concat();
+    for input in [
+        with_bom(&[0x00, 0x00, 0xfe, 0xff], &utf32_be),
+        with_bom(&[0xff, 0xfe, 0x00, 0x00], &utf32_le),
+        utf32_be,
+        utf32_le,
+    ] {
+        let err = Document::parse_reader(input.as_slice()).unwrap_err();
+        assert!(
+            matches!(
+                err,
+                EditXMLError::CannotDecode(DecodeError::UnsupportedEncoding("UTF-32"))
+            ),
+            "{err:?}"
+        );
+        let err = edit_xml::events::read_events(input.as_slice(), ReadOptions::default())
+            .next()
+            .unwrap()
+            .unwrap_err();
+        assert!(
+            matches!(
+                err,
+                EditXMLError::CannotDecode(DecodeError::UnsupportedEncoding("UTF-32"))
+            ),
+            "{err:?}"
+        );
+    }
+}
+
+#[test]
+fn test_truncated_multibyte_at_eof() {
+    test_utils::setup_logger();
+    let utf16_le = |xml: &str| -> Vec<u8> {
+        [0xff, 0xfe]
+            .into_iter()
+            .chain(xml.encode_utf16().flat_map(u16::to_le_bytes))
+            .collect()
+    };
+    // Long enough to cross the boundaries of the decoding buffers
+    let text = "é𝄞".repeat(5000);
+    let mut input = utf16_le(&format!(
+        r#"<?xml version="1.0" encoding="UTF-16"?><root>{text}</root>"#
+    ));
+    // Half of a UTF-16 code unit becomes a replacement character
+    input.push(b'<');
+    let doc = Document::parse_reader(input.as_slice()).unwrap();
+    assert_eq!(doc.root_element().unwrap().text_content(&doc), text);
+    assert_eq!(
+        doc.container().children(&doc).last(),
+        Some(&Node::Text("\u{FFFD}".to_owned()))
+    );
+    // Half of a surrogate pair
+    let mut input = utf16_le(r#"<?xml version="1.0" encoding="UTF-16"?><root/>𝄞"#);
+    input.truncate(input.len() - 2);
+    let doc = Document::parse_reader(input.as_slice()).unwrap();
+    assert_eq!(
+        doc.container().children(&doc).last(),
+        Some(&Node::Text("\u{FFFD}".to_owned()))
+    );
+
+    // Ending anywhere inside the text is an error, not a panic
+    let input = utf16_le(&format!(
+        r#"<?xml version="1.0" encoding="UTF-16"?><root>{text}</root>"#
+    ));
+    for end in (0..input.len() - 1)
+        .step_by(997)
+        .chain(input.len() - 12..input.len() - 1)
+    {
+        assert!(Document::parse_reader(&input[..end]).is_err(), "{end}");
+    }
+
+    // Without decoding, incomplete UTF-8 is a decode error
+    for input in [
+        b"<?xml version=\"1.0\"?><root/>\xC3".as_slice(),
+        b"\xEF\xBB\xBF<?xml version=\"1.0\"?><root/>\xE2\x82".as_slice(),
+        b"<?xml version=\"1.0\"?><root>a\xF0\x9D</root>".as_slice(),
+    ] {
+        let err = Document::parse_reader(input).unwrap_err();
+        assert!(
+            matches!(
+                err,
+                EditXMLError::CannotDecode(DecodeError::UTF8(_) | DecodeError::FromUTF8(_))
+            ),
+            "{err:?}"
+        );
+    }
+}
+
+#[test]
+fn test_collapse_empty_attributes() {
+    test_utils::setup_logger();
+    let xml = "<root blank=\" \t\n \" empty=\"\" text=\"  a  b \" />";
+    let parse = |normalize_attribute_value_space, collapse_empty_attributes| {
+        let opts = ReadOptions {
+            normalize_attribute_value_space,
+            collapse_empty_attributes,
+            ..ReadOptions::relaxed()
+        };
+        let doc = Document::parse_str_with_opts(xml, opts).unwrap();
+        let root = doc.root_element().unwrap();
+        let attr = |name| root.attribute(&doc, name).unwrap().to_owned();
+        (attr("blank"), attr("empty"), attr("text"))
+    };
+    let owned = |blank: &str, text: &str| (blank.to_owned(), String::new(), text.to_owned());
+    assert_eq!(parse(false, false), owned(" \t\n ", "  a  b "));
+    assert_eq!(parse(false, true), owned(" \t\n ", "  a  b "));
+    assert_eq!(parse(true, false), owned(" ", "a b"));
+    assert_eq!(parse(true, true), owned("", "a b"));
+
+    // Only setting normalize_attribute_value_space still collapses the value to ""
+    for opts in [
+        ReadOptions {
+            normalize_attribute_value_space: true,
+            ..ReadOptions::relaxed()
+        },
+        ReadOptions::builder()
+            .require_decl(false)
+            .normalize_attribute_value_space(true)
+            .build(),
+    ] {
+        let doc = Document::parse_str_with_opts(xml, opts).unwrap();
+        let root = doc.root_element().unwrap();
+        assert_eq!(root.attribute(&doc, "blank"), Some(""));
+    }
+}
+
+#[test]
+fn test_parse_str_is_not_decoded() {
+    test_utils::setup_logger();
+    // The declared encoding is ignored, as a str is already decoded
+    for xml in [
+        "<?xml version=\"1.0\" encoding=\"UTF-16\"?><root>한국어</root>",
+        "\u{FEFF}<?xml version=\"1.0\" encoding=\"UTF-8\"?><root>한국어</root>",
+        "\n  <?xml version=\"1.0\"?><root>한국어</root>",
+    ] {
+        let doc = Document::parse_str(xml).unwrap();
+        let root = doc.root_element().unwrap();
+        assert_eq!(root.text_content(&doc), "한국어", "{xml:?}");
+    }
+    let opts = ReadOptions {
+        encoding: Some("UTF-16".to_owned()),
+        ..ReadOptions::relaxed()
+    };
+    let doc = Document::parse_str_with_opts("<root>Text</root>", opts).unwrap();
+    assert_eq!(doc.root_element().unwrap().text_content(&doc), "Text");
+
+    assert!(Document::parse_str("<?xml version=\"1.0\" encoding=\"unknown\"?><root />").is_err());
+}
+
+#[test]
+fn test_load_sources() {
+    test_utils::setup_logger();
+    let path = test_utils::documents_dir().join("nodes.xml");
+    let xml = std::fs::read_to_string(&path).unwrap();
+    let expected = Document::parse_str(&xml).unwrap().write_str().unwrap();
+    let loaded = [
+        Document::load(path.as_path()).unwrap(),
+        Document::load(&path).unwrap(),
+        Document::load(xml.as_str()).unwrap(),
+        Document::load(&xml).unwrap(),
+        Document::load(xml.as_bytes()).unwrap(),
+        Document::load(Source::reader(std::io::Cursor::new(xml.as_bytes()))).unwrap(),
+        Document::load(
+            std::fs::File::open(&path)
+                .map(|file| Box::new(file) as Box<dyn std::io::Read>)
+                .unwrap(),
+        )
+        .unwrap(),
+    ];
+    for doc in loaded {
+        assert_eq!(doc.write_str().unwrap(), expected);
+    }
+
+    let opts = ReadOptions {
+        require_decl: true,
+        ..ReadOptions::default()
+    };
+    assert!(Document::load_with_opts("<root />", opts).is_err());
+    assert!(matches!(
+        Document::load(test_utils::documents_dir().join("missing.xml").as_path()),
+        Err(EditXMLError::Io(_))
+    ));
+}
+
+#[test]
+fn test_normalize_line_endings() {
+    test_utils::setup_logger();
+    let xml = "<root>a\r\nb\rc\nd&#13;e\r\n<![CDATA[f\r\ng\r]]></root>";
+    let parse = |normalize_line_endings| {
+        let opts = ReadOptions {
+            trim_text: false,
+            normalize_line_endings,
+            ..ReadOptions::relaxed()
+        };
+        let doc = Document::parse_str_with_opts(xml, opts).unwrap();
+        doc.root_element().unwrap().children(&doc).clone()
+    };
+    assert_eq!(
+        parse(true),
+        vec![
+            Node::Text("a\nb\nc\nd\re\n".to_owned()),
+            Node::CData("f\r\ng\r".to_owned()),
+        ]
+    );
+    assert_eq!(
+        parse(false),
+        vec![
+            Node::Text("a\r\nb\rc\nd\re\r\n".to_owned()),
+            Node::CData("f\r\ng\r".to_owned()),
+        ]
+    );
+}
+
+#[test]
+fn test_collapse_trivial_cdata() {
+    test_utils::setup_logger();
+    let xml =
+        "<root><a><![CDATA[hello]]></a><b>x <![CDATA[y]]> z</b><c><![CDATA[a<b & c]]></c></root>";
+    let parse = |collapse_trivial_cdata| {
+        let opts = ReadOptions {
+            trim_text: false,
+            collapse_trivial_cdata,
+            ..ReadOptions::relaxed()
+        };
+        let doc = Document::parse_str_with_opts(xml, opts).unwrap();
+        let children = doc
+            .root_element()
+            .unwrap()
+            .child_elements(&doc)
+            .into_iter()
+            .map(|elem| elem.children(&doc).clone())
+            .collect::<Vec<_>>();
+        (children, doc.write_str().unwrap())
+    };
+
+    let (children, written) = parse(false);
+    assert_eq!(
+        children,
+        vec![
+            vec![Node::CData("hello".to_owned())],
+            vec![
+                Node::Text("x ".to_owned()),
+                Node::CData("y".to_owned()),
+                Node::Text(" z".to_owned()),
+            ],
+            vec![Node::CData("a<b & c".to_owned())],
+        ]
+    );
+    // CDATA is always written back as a CDATA section
+    assert!(written.contains("<![CDATA[hello]]>"));
+    assert!(written.contains("<![CDATA[y]]>"));
+
+    let (children, written) = parse(true);
+    assert_eq!(
+        children,
+        vec![
+            vec![Node::Text("hello".to_owned())],
+            vec![Node::Text("x y z".to_owned())],
+            vec![Node::CData("a<b & c".to_owned())],
+        ]
+    );
+    assert!(written.contains("<a>hello</a>"));
+    assert!(written.contains("<![CDATA[a<b & c]]>"));
+}
+
+#[test]
+fn test_ignore_unmatched_end_tags() {
+    test_utils::setup_logger();
+    let opts = ReadOptions {
+        ignore_unmatched_end_tags: true,
+        ..ReadOptions::relaxed()
+    };
+    for xml in ["<a></b></a>", "<a>Text</x></a>", "</x><a/>", "<a /></x>"] {
+        assert!(
+            Document::parse_str_with_opts(xml, ReadOptions::relaxed()).is_err(),
+            "{xml}"
+        );
+    }
+
+    let doc = Document::parse_str_with_opts("<a></b></a>", opts.clone()).unwrap();
+    let a = doc.root_element().unwrap();
+    assert_eq!(a.name(&doc), "a");
+    assert_eq!(a.child_elements(&doc).len(), 0);
+
+    let doc = Document::parse_str_with_opts("<a><b>Text</c></b></a></a>", opts.clone()).unwrap();
+    let a = doc.root_element().unwrap();
+    assert_eq!(doc.root_nodes().len(), 1);
+    assert_eq!(a.name(&doc), "a");
+    let b = a.first_child_element(&doc).unwrap();
+    assert_eq!(b.name(&doc), "b");
+    assert_eq!(b.children(&doc), &vec![Node::Text("Text".to_owned())]);
+
+    let doc = Document::parse_str_with_opts("</x><p:a></a></p:a><b/>", opts.clone()).unwrap();
+    let names: Vec<&str> = doc
+        .root_nodes()
+        .iter()
+        .filter_map(|node| node.as_element())
+        .map(|elem| elem.full_name(&doc))
+        .collect();
+    assert_eq!(names, vec!["p:a", "b"]);
+
+    // Unclosed elements are still an error
+    assert!(Document::parse_str_with_opts("<a><b></a>", opts).is_err());
+}
+
+#[test]
+fn test_collect_warnings() {
+    test_utils::setup_logger();
+    let xml = r#"<root></a><b></b></c></root>"#;
+    let opts = |collect_warnings| ReadOptions {
+        ignore_unmatched_end_tags: true,
+        collect_warnings,
+        ..ReadOptions::relaxed()
+    };
+    let result = Document::parse_str_verbose_with_opts(xml, opts(true)).unwrap();
+    assert_eq!(
+        result.warnings,
+        vec![
+            ParseWarning::IgnoredEndTag {
+                name: "a".to_owned(),
+                position: 6
+            },
+            ParseWarning::IgnoredEndTag {
+                name: "c".to_owned(),
+                position: 17
+            },
+        ]
+    );
+    let root = result.document.root_element().unwrap();
+    assert_eq!(root.child_elements(&result.document).len(), 1);
+
+    let result = Document::parse_str_verbose_with_opts(xml, opts(false)).unwrap();
+    assert!(result.warnings.is_empty());
+
+    let result =
+        Document::parse_str_verbose(r#"<?xml version="1.0"?><root a="&amp;">&lt;&#65;</root>"#)
+            .unwrap();
+    assert!(result.warnings.is_empty());
+}
+
+#[cfg(feature = "soft-fail-unescape")]
+#[test]
+fn test_unknown_entity_warning() {
+    test_utils::setup_logger();
+    let xml = r#"<?xml version="1.0"?><root a="&foo; &amp;">a&bar;b</root>"#;
+    let result = Document::parse_str_verbose(xml).unwrap();
+    assert_eq!(
+        result.warnings,
+        vec![
+            ParseWarning::UnknownEntity {
+                name: "foo".to_owned(),
+                position: 21
+            },
+            ParseWarning::UnknownEntity {
+                name: "bar".to_owned(),
+                position: 44
+            },
+        ]
+    );
+}
+
+#[test]
+fn test_case_insensitive_names() {
+    test_utils::setup_logger();
+    let xml = r#"<HTML xmlns:SVG="urn:svg"><Body Class="main" ID="1"><DIV>Text</div><SVG:Rect /></BODY></html>"#;
+    assert!(Document::parse_str_with_opts(xml, ReadOptions::relaxed()).is_err());
+
+    let opts = ReadOptions {
+        case_insensitive_names: true,
+        ..ReadOptions::relaxed()
+    };
+    let doc = Document::parse_str_with_opts(xml, opts.clone()).unwrap();
+    let html = doc.root_element().unwrap();
+    assert_eq!(html.full_name(&doc), "html");
+    assert_eq!(html.namespace_for_prefix(&doc, "svg"), Some("urn:svg"));
+    let body = html.find(&doc, "body").unwrap();
+    assert_eq!(
+        body.attributes_sorted(&doc),
+        vec![("class", "main"), ("id", "1")]
+    );
+    let div = body.find(&doc, "div").unwrap();
+    assert_eq!(div.text_content(&doc), "Text");
+    let rect = body.find(&doc, "rect").unwrap();
+    assert_eq!(rect.full_name(&doc), "svg:rect");
+    assert_eq!(rect.namespace(&doc), Some("urn:svg"));
+
+    // Names that only differ in case are now duplicates
+    assert!(Document::parse_str_with_opts(r#"<a x="1" X="2" />"#, opts.clone()).is_err());
+    // Mismatched end tags are still an error
+    let err = Document::parse_str_with_opts("<a><b></c></a>", opts).unwrap_err();
+    assert!(
+        matches!(
+            err,
+            EditXMLError::MalformedXML(MalformedReason::EndEventMismatch { .. })
+        ),
+        "{err:?}"
+    );
+}
+
+#[test]
+fn test_find_ignore_case() {
+    test_utils::setup_logger();
+    let xml = "<Root><Item id=\"1\" /><ns:ITEM id=\"2\" /></Root>";
+    let doc = Document::parse_str_with_opts(xml, ReadOptions::relaxed()).unwrap();
+    let root = doc.root_element().unwrap();
+    let item = root.find_ignore_case(&doc, "item").unwrap();
+    assert_eq!(item.attribute(&doc, "id"), Some("1"));
+    // Stored names are unchanged
+    assert_eq!(item.name(&doc), "Item");
+    assert!(root.find(&doc, "item").is_none());
+    assert!(root.find_ignore_case(&doc, "ns:item").is_none());
+    assert!(root.find_ignore_case(&doc, "missing").is_none());
+}
+
+#[test]
+fn test_text_mode() {
+    test_utils::setup_logger();
+    let xml = "<root>\n  <p>  Hello,\n    <b>big</b>   world  </p>\n  <e></e>\n  <r>a&#32;&#32; b</r>\n</root>";
+    let parse = |text_mode| {
+        let opts = ReadOptions::builder()
+            .require_decl(false)
+            .text_mode(text_mode)
+            .build();
+        let doc = Document::parse_str_with_opts(xml, opts).unwrap();
+        let root = doc.root_element().unwrap();
+        let text = |node: &Node| match node {
+            Node::Text(text) => text.clone(),
+            Node::Element(elem) => format!("<{}>", elem.name(&doc)),
+            _ => unreachable!(),
+        };
+        let children = |elem: Element| elem.children(&doc).iter().map(text).collect::<Vec<_>>();
+        let [p, e, r] = root.child_elements(&doc)[..] else {
+            panic!("expected 3 elements");
+        };
+        (children(root), children(p), children(e), children(r))
+    };
+    let strings = |strs: &[&str]| strs.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+    let elements = strings(&["<p>", "<e>", "<r>"]);
+
+    let (root, p, e, r) = parse(TextMode::Raw);
+    assert_eq!(
+        root,
+        strings(&["\n  ", "<p>", "\n  ", "<e>", "\n  ", "<r>", "\n"])
+    );
+    assert_eq!(p, strings(&["  Hello,\n    ", "<b>", "   world  "]));
+    assert_eq!(e, strings(&[""]));
+    assert_eq!(r, strings(&["a   b"]));
+
+    let (root, p, e, _) = parse(TextMode::Trimmed);
+    assert_eq!(root, elements);
+    assert_eq!(p, strings(&["Hello,", "<b>", "world"]));
+    assert_eq!(e, strings(&[""]));
+
+    let (root, p, e, r) = parse(TextMode::Collapsed);
+    assert_eq!(root, elements);
+    assert_eq!(p, strings(&[" Hello, ", "<b>", " world "]));
+    assert_eq!(e, strings(&[""]));
+    // Whitespace from character references is kept
+    assert_eq!(r, strings(&["a   b"]));
+
+    let (root, p, e, r) = parse(TextMode::Significant);
+    assert_eq!(root, elements);
+    assert_eq!(p, strings(&["  Hello,\n    ", "<b>", "   world  "]));
+    assert!(e.is_empty());
+    assert_eq!(r, strings(&["a   b"]));
+
+    // text_mode overrides the individual options
+    let opts = ReadOptions {
+        trim_text: true,
+        text_mode: Some(TextMode::Raw),
+        ..ReadOptions::relaxed()
+    };
+    let doc = Document::parse_str_with_opts(xml, opts).unwrap();
+    assert_eq!(doc.root_element().unwrap().children(&doc).len(), 7);
+}
+
+#[test]
+fn test_default_namespace() {
+    test_utils::setup_logger();
+    let svg = "http://www.w3.org/2000/svg";
+    let xml = r#"<svg xmlns:x="urn:x"><g><rect/><x:rect/></g></svg>"#;
+    let opts = ReadOptions::builder()
+        .require_decl(false)
+        .default_namespace(svg)
+        .build();
+    let doc = Document::parse_str_with_opts(xml, opts.clone()).unwrap();
+    let root = doc.root_element().unwrap();
+    assert_eq!(root.namespace(&doc), Some(svg));
+    let g = root.find(&doc, "g").unwrap();
+    assert_eq!(g.namespace(&doc), Some(svg));
+    assert!(g.namespace_decls(&doc).is_empty());
+    let [rect, x_rect] = g.child_elements(&doc)[..] else {
+        panic!("expected 2 elements");
+    };
+    assert_eq!(rect.namespace(&doc), Some(svg));
+    assert_eq!(x_rect.namespace(&doc), Some("urn:x"));
+
+    // An existing default namespace is kept
+    let xml = r#"<svg xmlns="urn:svg"><g/></svg>"#;
+    let doc = Document::parse_str_with_opts(xml, opts).unwrap();
+    let root = doc.root_element().unwrap();
+    assert_eq!(
+        root.find(&doc, "g").unwrap().namespace(&doc),
+        Some("urn:svg")
+    );
+
+    let doc = Document::parse_str_with_opts("<svg><g/></svg>", ReadOptions::relaxed()).unwrap();
+    assert_eq!(doc.root_element().unwrap().namespace(&doc), None);
+}
+
+#[test]
+fn test_preserve_whitespace_in() {
+    test_utils::setup_logger();
+    let xml = "<doc>\n  <p>  Trimmed  </p>\n  <code>\n    fn main() {\n        <b> x </b>\n    }\n  </code>\n  <p> After </p>\n</doc>";
+    let opts = ReadOptions::builder()
+        .require_decl(false)
+        .preserve_whitespace_in(["code", "pre"])
+        .build();
+    let doc = Document::parse_str_with_opts(xml, opts).unwrap();
+    let root = doc.root_element().unwrap();
+    let [p, code, after] = root.child_elements(&doc)[..] else {
+        panic!("expected 3 elements");
+    };
+    assert_eq!(p.text_content(&doc), "Trimmed");
+    assert_eq!(after.text_content(&doc), "After");
+    assert_eq!(
+        code.children(&doc)[0],
+        Node::Text("\n    fn main() {\n        ".to_owned())
+    );
+    assert_eq!(
+        code.text_content(&doc),
+        "\n    fn main() {\n         x \n    }\n  "
+    );
+    // Whitespace between the siblings is still trimmed away
+    assert_eq!(root.children(&doc).len(), 3);
+
+    // Without the option, the code is trimmed too
+    let doc = Document::parse_str_with_opts(xml, ReadOptions::relaxed()).unwrap();
+    let code = doc.root_element().unwrap().find(&doc, "code").unwrap();
+    assert_eq!(code.text_content(&doc), "fn main() {x}");
+}
@@ -23,6 +23,11 @@ pub fn test_dir() -> std::path::PathBuf {
 pub fn documents_dir() -> std::path::PathBuf {
     test_dir().join("documents")
 }
+pub fn bench_files_dir() -> std::path::PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("perf-comparison")
+        .join("bench_files")
+}
 pub fn iter_read_options() -> impl Iterator<Item = ReadOptions> {
     let empty_text_node_opts = [true, false];
     let trim_text = [true, false];
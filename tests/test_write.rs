@@ -8,7 +8,7 @@ fn test_escape() -> anyhow::Result<()> {
 <root attr="&amp;gt;&amp;lt;&amp;amp;&amp;quot;&amp;apos;attrval">
   <inner xmlns:ns="&amp;gt;&amp;lt;&amp;amp;&amp;quot;&amp;apos;nsval">&gt;&lt;&amp;&quot;&apos;text</inner>
 </root>
-<!--&lt;&amp;amp;--><![CDATA[<&amp;]]><!DOCTYPE  &lt;&amp;amp;>
+<!--&lt;&amp;amp;--><![CDATA[<&amp;]]><!DOCTYPE <&amp;>
 <?<&amp;?>"#;
 
     let mut doc = Document::new();
@@ -32,3 +32,49 @@ fn test_escape() -> anyhow::Result<()> {
     assert_eq!(xml, expected);
     Ok(())
 }
+
+#[test]
+fn test_doctype_round_trip() -> anyhow::Result<()> {
+    test_utils::setup_logger();
+    let system = r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE note SYSTEM "note.dtd">
+<note/>"#;
+    let doc = Document::parse_str(system)?;
+    assert_eq!(doc.doctype(), Some(r#"note SYSTEM "note.dtd""#));
+    assert_eq!(doc.write_str()?, system);
+
+    let internal_subset = r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE note [
+  <!ENTITY writer "Tove &amp; Jani">
+  <!ELEMENT note (#PCDATA)>
+]>
+<note/>"#;
+    let doc = Document::parse_str(internal_subset)?;
+    assert_eq!(
+        doc.doctype(),
+        Some("note [\n  <!ENTITY writer \"Tove &amp; Jani\">\n  <!ELEMENT note (#PCDATA)>\n]")
+    );
+    assert_eq!(doc.write_str()?, internal_subset);
+
+    // Whitespace around the content is normalized to a single space
+    let mut doc = Document::parse_str(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?><!DOCTYPE\n  html  ><html/>",
+    )?;
+    assert_eq!(doc.doctype(), Some("html"));
+    doc.set_doctype(Some(
+        r#"  html PUBLIC "-//W3C//DTD XHTML 1.0 Strict//EN" "xhtml1-strict.dtd""#.to_owned(),
+    ));
+    let written = doc.write_str()?;
+    assert!(
+        written.contains(
+            r#"<!DOCTYPE html PUBLIC "-//W3C//DTD XHTML 1.0 Strict//EN" "xhtml1-strict.dtd">"#
+        ),
+        "{written}"
+    );
+    let reparsed = Document::parse_str(&written)?;
+    assert_eq!(
+        reparsed.doctype(),
+        Some(r#"html PUBLIC "-//W3C//DTD XHTML 1.0 Strict//EN" "xhtml1-strict.dtd""#)
+    );
+    Ok(())
+}